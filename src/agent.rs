@@ -0,0 +1,115 @@
+//! `nvdash agent`: serves this machine's GPUs to nvdash windows elsewhere.
+//!
+//! A poller thread reads every device at the poll interval and keeps the
+//! last few minutes of frames; the HTTP side hands them out:
+//!
+//! - `GET /frames?since=MS` — frames newer than `MS` (Unix ms), one JSON
+//!   object per line as in recordings. `since=0` returns all the history
+//!   the agent holds, which is how a window backfills on connecting.
+//! - `GET /snapshot` — the newest frame as one JSON object.
+//!
+//! There is no authentication, so the agent listens on loopback unless
+//! `--listen` says otherwise; bind to a private interface or tunnel it.
+//! Process command lines and working directories can carry secrets and are
+//! left out unless the agent was started with `--share-cmdline`.
+
+use crate::gpu::{GpuMonitor, GpuSnapshot};
+use crate::http::{self, Request, Response};
+use crate::record::Frame;
+use std::collections::VecDeque;
+use std::io;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:9836";
+/// History kept for windows that connect later
+pub const RETENTION_MS: i64 = 10 * 60 * 1000;
+const JSON: &str = "application/json";
+const JSON_LINES: &str = "application/x-ndjson";
+
+pub fn run(
+    monitor: &mut GpuMonitor,
+    listener: TcpListener,
+    interval: Duration,
+    share_cmdline: bool,
+) -> io::Result<()> {
+    let frames = Mutex::new(VecDeque::<Frame>::new());
+    let stop = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !stop.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                let mut snapshots = monitor.snapshot_all();
+                if !share_cmdline {
+                    redact(&mut snapshots);
+                }
+                let frame = Frame::now(snapshots);
+                if let Ok(mut frames) = frames.lock() {
+                    let oldest = frame.timestamp_ms - RETENTION_MS;
+                    frames.push_back(frame);
+                    while frames.front().is_some_and(|f| f.timestamp_ms < oldest) {
+                        frames.pop_front();
+                    }
+                }
+                std::thread::sleep(interval.saturating_sub(started.elapsed()));
+            }
+        });
+        let result = http::serve(listener, |req| respond(req, &frames));
+        stop.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+/// Blank out each process's command line and working directory
+fn redact(snapshots: &mut [GpuSnapshot]) {
+    for snap in snapshots {
+        let mig = snap.mig_instances.iter_mut().flat_map(|m| &mut m.processes);
+        for proc in snap.processes.iter_mut().chain(mig) {
+            proc.cmdline.clear();
+            proc.cwd.clear();
+        }
+    }
+}
+
+/// Frames taken after `since` (Unix ms), copied out so the poller isn't
+/// kept waiting while they are serialized; `None` if the poller panicked
+fn frames_after(frames: &Mutex<VecDeque<Frame>>, since: i64) -> Option<Vec<Frame>> {
+    let frames = frames.lock().ok()?;
+    let start = frames.partition_point(|f| f.timestamp_ms <= since);
+    Some(frames.range(start..).cloned().collect())
+}
+
+fn respond(req: &Request, frames: &Mutex<VecDeque<Frame>>) -> Response {
+    match req.path.as_str() {
+        "/frames" => {
+            let since: i64 = req.param("since").and_then(|s| s.parse().ok()).unwrap_or(0);
+            let Some(frames) = frames_after(frames, since) else {
+                return Response::unavailable();
+            };
+            let mut body = String::new();
+            for frame in &frames {
+                if let Ok(line) = serde_json::to_string(frame) {
+                    body.push_str(&line);
+                    body.push('\n');
+                }
+            }
+            Response::ok(JSON_LINES, body)
+        }
+        "/snapshot" => {
+            let latest = frames.lock().ok().and_then(|f| f.back().cloned());
+            match latest.map(|f| serde_json::to_string(&f)) {
+                Some(Ok(body)) => Response::ok(JSON, body + "\n"),
+                _ => Response::unavailable(),
+            }
+        }
+        "/" => Response::ok(
+            "text/html; charset=utf-8",
+            "<html><body><h1>nvdash agent</h1><a href=\"/snapshot\">/snapshot</a> \
+             <a href=\"/frames?since=0\">/frames</a></body></html>\n"
+                .into(),
+        ),
+        _ => Response::not_found(),
+    }
+}
@@ -0,0 +1,624 @@
+//! Threshold alerts over snapshot metrics.
+//!
+//! A rule fires once its condition has held for `for_s` seconds and clears
+//! only after the value has backed off by `hysteresis`, so a reading that
+//! hovers around the threshold doesn't flap. The engine is driven purely by
+//! the snapshots and timestamps it is given; it never reads the clock.
+
+use crate::gpu::{GpuRef, GpuSnapshot, IDLE_UTIL};
+use crate::text;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Snapshot value a rule can watch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    GpuUtil,
+    MemoryUtil,
+    VramUsedMb,
+    /// VRAM used as a percentage of `vram_total_mb`
+    VramPct,
+    Temperature,
+    FanSpeed,
+    PowerDrawW,
+    /// Power draw as a percentage of the enforced limit
+    PowerPct,
+    ClockGraphicsMhz,
+    ClockMemoryMhz,
+    ClockSmMhz,
+    /// Volatile corrected ECC error count
+    EccCorrected,
+    /// Volatile uncorrected ECC error count
+    EccUncorrected,
+    /// Most VRAM any one process holds while the GPU is idle; 0 while busy.
+    /// Above a threshold for a while, someone is reserving a GPU they
+    /// aren't using.
+    IdleProcessVramMb,
+}
+
+impl Metric {
+    /// Current value, or `None` if the device doesn't report it
+    pub fn value(self, snapshot: &GpuSnapshot) -> Option<f64> {
+        let v = match self {
+            Metric::GpuUtil => snapshot.gpu_util()? as f64,
+            Metric::MemoryUtil => snapshot.memory_util()? as f64,
+            Metric::VramUsedMb => snapshot.vram_used_mb as f64,
+            Metric::VramPct => {
+                if snapshot.vram_total_mb == 0 {
+                    return None;
+                }
+                snapshot.vram_used_mb as f64 / snapshot.vram_total_mb as f64 * 100.0
+            }
+            Metric::Temperature => snapshot.temperature as f64,
+            Metric::FanSpeed => snapshot.fan_speed? as f64,
+            Metric::PowerDrawW => snapshot.power_draw_w,
+            Metric::PowerPct => {
+                if snapshot.power_limit_w <= 0.0 {
+                    return None;
+                }
+                text::power_pct(snapshot)
+            }
+            Metric::ClockGraphicsMhz => snapshot.clock_graphics_mhz as f64,
+            Metric::ClockMemoryMhz => snapshot.clock_memory_mhz as f64,
+            Metric::ClockSmMhz => snapshot.clock_sm_mhz as f64,
+            Metric::EccCorrected => snapshot.ecc?.volatile_corrected as f64,
+            Metric::EccUncorrected => snapshot.ecc?.volatile_uncorrected as f64,
+            // Unknown utilization says nothing about idleness
+            Metric::IdleProcessVramMb => {
+                if snapshot.gpu_util()? >= IDLE_UTIL {
+                    return Some(0.0);
+                }
+                let held = snapshot.all_processes().map(|(p, _)| p.vram_mb).max();
+                held.unwrap_or(0) as f64
+            }
+        };
+        Some(v)
+    }
+
+    /// `83°C`, `95%`, `450W`
+    pub fn fmt(self, value: f64) -> String {
+        match self {
+            Metric::Temperature => format!("{:.0}°C", value),
+            Metric::GpuUtil
+            | Metric::MemoryUtil
+            | Metric::VramPct
+            | Metric::FanSpeed
+            | Metric::PowerPct => format!("{:.0}%", value),
+            Metric::VramUsedMb | Metric::IdleProcessVramMb => text::fmt_mb(value as u64),
+            Metric::PowerDrawW => format!("{:.0}W", value),
+            Metric::ClockGraphicsMhz | Metric::ClockMemoryMhz | Metric::ClockSmMhz => {
+                format!("{:.0}MHz", value)
+            }
+            Metric::EccCorrected | Metric::EccUncorrected => format!("{:.0} errors", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Above,
+    Below,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub metric: Metric,
+    pub op: Op,
+    pub threshold: f64,
+    /// How far back past the threshold the value must go to clear
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// How long the condition must hold before firing
+    #[serde(default)]
+    pub for_s: u64,
+    /// GPUs the rule applies to, by local index or UUID; empty means all
+    #[serde(default)]
+    pub gpus: Vec<GpuRef>,
+}
+
+impl Rule {
+    fn triggered(&self, value: f64) -> bool {
+        match self.op {
+            Op::Above => value > self.threshold,
+            Op::Below => value < self.threshold,
+        }
+    }
+
+    fn cleared(&self, value: f64) -> bool {
+        match self.op {
+            Op::Above => value <= self.threshold - self.hysteresis,
+            Op::Below => value >= self.threshold + self.hysteresis,
+        }
+    }
+
+    fn applies_to(&self, snapshot: &GpuSnapshot) -> bool {
+        self.gpus.is_empty() || self.gpus.iter().any(|g| g.matches(snapshot))
+    }
+}
+
+/// Rules used when the config doesn't list any
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule {
+            name: "Hot".into(),
+            metric: Metric::Temperature,
+            op: Op::Above,
+            threshold: 83.0,
+            hysteresis: 3.0,
+            for_s: 30,
+            gpus: Vec::new(),
+        },
+        Rule {
+            name: "VRAM full".into(),
+            metric: Metric::VramPct,
+            op: Op::Above,
+            threshold: 95.0,
+            hysteresis: 2.0,
+            for_s: 0,
+            gpus: Vec::new(),
+        },
+        Rule {
+            name: "Power capped".into(),
+            metric: Metric::PowerPct,
+            op: Op::Above,
+            threshold: 98.0,
+            hysteresis: 5.0,
+            for_s: 300,
+            gpus: Vec::new(),
+        },
+        Rule {
+            name: "Idle reservation".into(),
+            metric: Metric::IdleProcessVramMb,
+            op: Op::Above,
+            threshold: 1024.0,
+            hysteresis: 0.0,
+            for_s: 30 * 60,
+            gpus: Vec::new(),
+        },
+        Rule {
+            name: "Uncorrected ECC".into(),
+            metric: Metric::EccUncorrected,
+            op: Op::Above,
+            threshold: 0.0,
+            hysteresis: 0.0,
+            for_s: 0,
+            gpus: Vec::new(),
+        },
+    ]
+}
+
+/// A rule firing on one GPU
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub rule: usize,
+    pub gpu: u32,
+    /// When the rule fired (Unix ms)
+    pub since_ms: i64,
+    /// Latest value of the watched metric
+    pub value: f64,
+}
+
+/// A rule starting or stopping firing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transition {
+    Fired(Alert),
+    Cleared { rule: usize, gpu: u32 },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct State {
+    /// Where the GPU was listed at its last update
+    gpu: u32,
+    /// When the condition started holding, while not yet firing
+    pending_since: Option<i64>,
+    /// When the rule fired, while firing
+    firing_since: Option<i64>,
+    value: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    /// Keyed by rule index and the GPU's host and UUID, which unlike its
+    /// index stay put when agents come, go or change size
+    states: HashMap<(usize, String, GpuRef), State>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            states: HashMap::new(),
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Replace the rule set; does nothing if it is unchanged, otherwise
+    /// every rule starts over
+    pub fn set_rules(&mut self, rules: &[Rule]) {
+        if self.rules != rules {
+            self.rules = rules.to_vec();
+            self.states.clear();
+        }
+    }
+
+    /// Feed one poll's snapshots taken at `t_ms` (Unix ms). Returns the
+    /// rules that started or stopped firing. Alerts on GPUs no longer in
+    /// `snapshots` clear.
+    pub fn update(&mut self, t_ms: i64, snapshots: &[GpuSnapshot]) -> Vec<Transition> {
+        let mut transitions = Vec::new();
+        let keys: Vec<(String, GpuRef)> = snapshots
+            .iter()
+            .map(|s| (s.host.clone(), GpuRef::of(s)))
+            .collect();
+        self.states.retain(|(rule, host, gpu), state| {
+            let present = keys.iter().any(|(h, g)| h == host && g == gpu);
+            if !present && state.firing_since.is_some() {
+                transitions.push(Transition::Cleared {
+                    rule: *rule,
+                    gpu: state.gpu,
+                });
+            }
+            present
+        });
+
+        for (r, rule) in self.rules.iter().enumerate() {
+            for (snap, (host, gpu)) in snapshots.iter().zip(&keys) {
+                if !rule.applies_to(snap) {
+                    continue;
+                }
+                let state = self
+                    .states
+                    .entry((r, host.clone(), gpu.clone()))
+                    .or_default();
+                state.gpu = snap.index;
+                // A metric the device doesn't report can't hold a rule open
+                let Some(value) = rule.metric.value(snap) else {
+                    if state.firing_since.take().is_some() {
+                        transitions.push(Transition::Cleared {
+                            rule: r,
+                            gpu: snap.index,
+                        });
+                    }
+                    state.pending_since = None;
+                    continue;
+                };
+                state.value = value;
+
+                if state.firing_since.is_some() {
+                    if rule.cleared(value) {
+                        state.firing_since = None;
+                        transitions.push(Transition::Cleared {
+                            rule: r,
+                            gpu: snap.index,
+                        });
+                    }
+                    continue;
+                }
+
+                if !rule.triggered(value) {
+                    state.pending_since = None;
+                    continue;
+                }
+                let since = *state.pending_since.get_or_insert(t_ms);
+                if t_ms - since >= rule.for_s as i64 * 1000 {
+                    state.pending_since = None;
+                    state.firing_since = Some(t_ms);
+                    transitions.push(Transition::Fired(Alert {
+                        rule: r,
+                        gpu: snap.index,
+                        since_ms: t_ms,
+                        value,
+                    }));
+                }
+            }
+        }
+        transitions
+    }
+
+    /// Alerts currently firing, ordered by rule then GPU
+    pub fn firing(&self) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self
+            .states
+            .iter()
+            .filter_map(|(&(rule, _, _), s)| {
+                s.firing_since.map(|since_ms| Alert {
+                    rule,
+                    gpu: s.gpu,
+                    since_ms,
+                    value: s.value,
+                })
+            })
+            .collect();
+        alerts.sort_by_key(|a| (a.rule, a.gpu));
+        alerts
+    }
+
+    /// Smallest VRAM threshold of the idle-reservation rules firing on
+    /// `gpu`: processes holding at least that much are the ones reserving it
+    pub fn idle_reservation_mb(&self, gpu: u32) -> Option<u64> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(r, rule)| {
+                rule.metric == Metric::IdleProcessVramMb
+                    && rule.op == Op::Above
+                    && self.states.iter().any(|((rule, _, _), s)| {
+                        rule == r && s.gpu == gpu && s.firing_since.is_some()
+                    })
+            })
+            .map(|(_, rule)| rule.threshold.max(0.0) as u64)
+            .min()
+    }
+
+    /// `Hot: 86°C (> 83°C)`
+    pub fn describe(&self, alert: &Alert) -> String {
+        let rule = &self.rules[alert.rule];
+        let op = match rule.op {
+            Op::Above => '>',
+            Op::Below => '<',
+        };
+        format!(
+            "{}: {} ({} {})",
+            rule.name,
+            rule.metric.fmt(alert.value),
+            op,
+            rule.metric.fmt(rule.threshold)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::{ProcessInfo, ProcessKind};
+
+    fn gpu(index: u32, temperature: u32) -> GpuSnapshot {
+        GpuSnapshot {
+            temperature,
+            fan_speed: Some(50),
+            ..GpuSnapshot::placeholder(index)
+        }
+    }
+
+    fn hot() -> Rule {
+        Rule {
+            name: "Hot".into(),
+            metric: Metric::Temperature,
+            op: Op::Above,
+            threshold: 83.0,
+            hysteresis: 3.0,
+            for_s: 30,
+            gpus: Vec::new(),
+        }
+    }
+
+    /// Feed one GPU's temperatures, one per `(seconds, °C)`
+    fn feed(engine: &mut AlertEngine, readings: &[(i64, u32)]) -> Vec<Transition> {
+        readings
+            .iter()
+            .flat_map(|&(s, temp)| engine.update(s * 1000, &[gpu(0, temp)]))
+            .collect()
+    }
+
+    #[test]
+    fn fires_only_after_holding_for_s() {
+        let mut engine = AlertEngine::new(vec![hot()]);
+        assert!(feed(&mut engine, &[(0, 85), (10, 86), (29, 85)]).is_empty());
+        assert!(engine.firing().is_empty());
+
+        let fired = feed(&mut engine, &[(30, 87)]);
+        let alert = Alert {
+            rule: 0,
+            gpu: 0,
+            since_ms: 30_000,
+            value: 87.0,
+        };
+        assert_eq!(fired, [Transition::Fired(alert.clone())]);
+        assert_eq!(engine.firing(), [alert]);
+        assert_eq!(engine.describe(&engine.firing()[0]), "Hot: 87°C (> 83°C)");
+
+        // Still firing: no second transition
+        assert!(feed(&mut engine, &[(40, 90)]).is_empty());
+    }
+
+    #[test]
+    fn dipping_below_restarts_the_wait() {
+        let mut engine = AlertEngine::new(vec![hot()]);
+        assert!(feed(&mut engine, &[(0, 85), (20, 83), (25, 85), (50, 85)]).is_empty());
+        assert_eq!(feed(&mut engine, &[(55, 85)]).len(), 1);
+    }
+
+    #[test]
+    fn clears_past_the_hysteresis_band() {
+        let mut engine = AlertEngine::new(vec![hot()]);
+        feed(&mut engine, &[(0, 85), (30, 85)]);
+        assert_eq!(engine.firing().len(), 1);
+
+        // Below the threshold but inside the band
+        assert!(feed(&mut engine, &[(40, 83), (50, 81)]).is_empty());
+        assert_eq!(engine.firing()[0].value, 81.0);
+
+        let cleared = feed(&mut engine, &[(60, 80)]);
+        assert_eq!(cleared, [Transition::Cleared { rule: 0, gpu: 0 }]);
+        assert!(engine.firing().is_empty());
+
+        // And has to hold for `for_s` again to come back
+        assert!(feed(&mut engine, &[(70, 90), (99, 90)]).is_empty());
+        assert_eq!(feed(&mut engine, &[(100, 90)]).len(), 1);
+    }
+
+    #[test]
+    fn clears_when_the_metric_goes_missing() {
+        let mut engine = AlertEngine::new(vec![Rule {
+            name: "Fan".into(),
+            metric: Metric::FanSpeed,
+            op: Op::Above,
+            threshold: 90.0,
+            hysteresis: 0.0,
+            for_s: 0,
+            gpus: Vec::new(),
+        }]);
+        let fan = |speed| GpuSnapshot {
+            fan_speed: speed,
+            ..gpu(0, 50)
+        };
+        assert_eq!(engine.update(0, &[fan(Some(95))]).len(), 1);
+        assert_eq!(
+            engine.update(1000, &[fan(None)]),
+            [Transition::Cleared { rule: 0, gpu: 0 }]
+        );
+        assert!(engine.firing().is_empty());
+        assert!(engine.update(2000, &[fan(None)]).is_empty());
+    }
+
+    #[test]
+    fn idle_reservations_need_known_utilization() {
+        let held = |gpu_util: Option<u32>| GpuSnapshot {
+            gpu_util: gpu_util.unwrap_or(0),
+            util_unavailable: gpu_util.is_none(),
+            processes: vec![ProcessInfo {
+                pid: 4242,
+                name: "python".into(),
+                vram_mb: 4096,
+                start_time: 0,
+                cmdline: String::new(),
+                user: String::new(),
+                cpu_pct: 0.0,
+                rss_mb: 0,
+                cwd: String::new(),
+                kind: ProcessKind::Compute,
+                utilization: None,
+                encoder_sessions: Vec::new(),
+            }],
+            ..gpu(0, 50)
+        };
+        let metric = Metric::IdleProcessVramMb;
+        assert_eq!(metric.value(&held(Some(0))), Some(4096.0));
+        assert_eq!(metric.value(&held(Some(80))), Some(0.0));
+        // A MIG-enabled parent can't report utilization; that isn't idle
+        assert_eq!(metric.value(&held(None)), None);
+    }
+
+    #[test]
+    fn tracks_gpus_separately() {
+        let mut engine = AlertEngine::new(vec![Rule {
+            gpus: vec![GpuRef::Index(1), GpuRef::Index(2)],
+            for_s: 0,
+            ..hot()
+        }]);
+        engine.update(0, &[gpu(0, 90), gpu(1, 90), gpu(2, 70)]);
+        let gpus: Vec<u32> = engine.firing().iter().map(|a| a.gpu).collect();
+        assert_eq!(gpus, [1]);
+
+        engine.update(1000, &[gpu(0, 90), gpu(1, 70), gpu(2, 90)]);
+        let gpus: Vec<u32> = engine.firing().iter().map(|a| a.gpu).collect();
+        assert_eq!(gpus, [2]);
+    }
+
+    #[test]
+    fn below_rules_clear_above_the_band() {
+        let mut engine = AlertEngine::new(vec![Rule {
+            name: "Slow clock".into(),
+            metric: Metric::ClockGraphicsMhz,
+            op: Op::Below,
+            threshold: 1000.0,
+            hysteresis: 100.0,
+            for_s: 0,
+            gpus: Vec::new(),
+        }]);
+        let clock = |mhz| GpuSnapshot {
+            clock_graphics_mhz: mhz,
+            ..gpu(0, 50)
+        };
+        assert_eq!(engine.update(0, &[clock(900)]).len(), 1);
+        assert!(engine.update(1000, &[clock(1050)]).is_empty());
+        assert_eq!(engine.update(2000, &[clock(1100)]).len(), 1);
+        assert!(engine.firing().is_empty());
+    }
+
+    #[test]
+    fn changing_rules_starts_over() {
+        let mut engine = AlertEngine::new(vec![Rule { for_s: 0, ..hot() }]);
+        engine.update(0, &[gpu(0, 90)]);
+        engine.set_rules(&[Rule { for_s: 0, ..hot() }]);
+        assert_eq!(engine.firing().len(), 1);
+        engine.set_rules(&[hot()]);
+        assert!(engine.firing().is_empty());
+    }
+
+    /// GPU `uuid` on agent `host`, listed at `index`
+    fn remote(host: &str, uuid: &str, index: u32, temperature: u32) -> GpuSnapshot {
+        GpuSnapshot {
+            host: host.into(),
+            uuid: uuid.into(),
+            ..gpu(index, temperature)
+        }
+    }
+
+    #[test]
+    fn holds_follow_a_gpu_that_is_renumbered() {
+        let mut engine = AlertEngine::new(vec![hot()]);
+        let local = remote("", "GPU-a", 0, 50);
+        engine.update(0, &[local.clone(), remote("box:9836", "GPU-b", 1, 90)]);
+
+        // A GPU shows up on the agent ahead of the hot one, which moves down
+        let shifted = [
+            local.clone(),
+            remote("box:9836", "GPU-x", 1, 50),
+            remote("box:9836", "GPU-b", 2, 90),
+        ];
+        assert!(engine.update(20_000, &shifted).is_empty());
+        let fired = engine.update(30_000, &shifted);
+        assert!(matches!(&fired[..], [Transition::Fired(a)] if a.gpu == 2 && a.since_ms == 30_000));
+
+        // The same UUID on another host is another GPU
+        let elsewhere = remote("other:9836", "GPU-b", 3, 90);
+        let mut all = shifted.to_vec();
+        all.push(elsewhere);
+        engine.update(40_000, &all);
+        let gpus: Vec<u32> = engine.firing().iter().map(|a| a.gpu).collect();
+        assert_eq!(gpus, [2]);
+
+        // And when the agent goes away, so do its alerts
+        assert_eq!(
+            engine.update(50_000, &[local]),
+            [Transition::Cleared { rule: 0, gpu: 2 }]
+        );
+        assert!(engine.firing().is_empty());
+    }
+
+    #[test]
+    fn rules_name_gpus_by_local_index_or_uuid() {
+        let mut engine = AlertEngine::new(vec![Rule {
+            gpus: vec![GpuRef::Index(1), GpuRef::Uuid("GPU-b".into())],
+            for_s: 0,
+            ..hot()
+        }]);
+        // Index 1 is a local GPU's; the agent's GPU listed there isn't it
+        engine.update(
+            0,
+            &[
+                remote("", "GPU-a", 0, 90),
+                remote("box:9836", "GPU-c", 1, 90),
+                remote("box:9836", "GPU-b", 2, 90),
+            ],
+        );
+        let gpus: Vec<u32> = engine.firing().iter().map(|a| a.gpu).collect();
+        assert_eq!(gpus, [2]);
+
+        let rules: Vec<GpuRef> = toml::from_str::<Rule>(
+            "name = \"Hot\"\nmetric = \"temperature\"\nop = \"above\"\n\
+             threshold = 83.0\ngpus = [0, \"GPU-b\"]\n",
+        )
+        .unwrap()
+        .gpus;
+        assert_eq!(rules, [GpuRef::Index(0), GpuRef::Uuid("GPU-b".into())]);
+    }
+}
@@ -0,0 +1,375 @@
+//! Expanded charts: any collected metric against a real time axis, for
+//! every GPU at once.
+//!
+//! Scroll (or pinch) to zoom around the pointer, drag to pan back in time,
+//! double-click to return to the live window. Hovering reads out each
+//! GPU's value at that moment. The compact text sparklines stay the main
+//! view; this is the window for looking closer.
+
+use crate::gpu::GpuSnapshot;
+use crate::history::{GpuHistory, Sample, Series, MAX_WINDOW_MS};
+use crate::text;
+use crate::ui;
+use egui::{Align2, Color32, FontId, Pos2, Rect, RichText, Sense, Shape, Stroke};
+
+/// Narrowest span the chart zooms into
+const MIN_SPAN_MS: i64 = 10 * 1000;
+/// Space left of the plot for the value axis, below it for the time axis
+const AXIS_LEFT: f32 = 52.0;
+const AXIS_BOTTOM: f32 = 16.0;
+/// Tick spacings tried for the time axis, finest first
+const TIME_STEPS_MS: [i64; 13] = [
+    1000,
+    5 * 1000,
+    10 * 1000,
+    30 * 1000,
+    60 * 1000,
+    5 * 60 * 1000,
+    10 * 60 * 1000,
+    30 * 60 * 1000,
+    60 * 60 * 1000,
+    3 * 60 * 60 * 1000,
+    6 * 60 * 60 * 1000,
+    12 * 60 * 60 * 1000,
+    24 * 60 * 60 * 1000,
+];
+/// One line color per GPU, cycling
+const PALETTE: [Color32; 6] = [
+    ui::NVIDIA_GREEN,
+    ui::ACCENT_CYAN,
+    ui::ACCENT_AMBER,
+    Color32::from_rgb(190, 120, 220),
+    Color32::from_rgb(240, 120, 150),
+    Color32::from_rgb(220, 220, 120),
+];
+
+/// A metric `GpuHistory` keeps a series for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartMetric {
+    GpuUtil,
+    Vram,
+    Temperature,
+    Power,
+    PcieTx,
+    PcieRx,
+    Encoder,
+    Decoder,
+}
+
+impl ChartMetric {
+    const ALL: [ChartMetric; 8] = [
+        ChartMetric::GpuUtil,
+        ChartMetric::Vram,
+        ChartMetric::Temperature,
+        ChartMetric::Power,
+        ChartMetric::PcieTx,
+        ChartMetric::PcieRx,
+        ChartMetric::Encoder,
+        ChartMetric::Decoder,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ChartMetric::GpuUtil => "GPU utilization",
+            ChartMetric::Vram => "VRAM used",
+            ChartMetric::Temperature => "Temperature",
+            ChartMetric::Power => "Power draw",
+            ChartMetric::PcieTx => "PCIe TX",
+            ChartMetric::PcieRx => "PCIe RX",
+            ChartMetric::Encoder => "Encoder",
+            ChartMetric::Decoder => "Decoder",
+        }
+    }
+
+    fn series(self, history: &GpuHistory) -> &Series {
+        match self {
+            ChartMetric::GpuUtil => &history.gpu_util,
+            ChartMetric::Vram => &history.vram_used,
+            ChartMetric::Temperature => &history.temperature,
+            ChartMetric::Power => &history.power_draw,
+            ChartMetric::PcieTx => &history.pcie_tx,
+            ChartMetric::PcieRx => &history.pcie_rx,
+            ChartMetric::Encoder => &history.encoder,
+            ChartMetric::Decoder => &history.decoder,
+        }
+    }
+
+    /// Top of the value axis before looking at the data: the device's
+    /// capacity where there is one
+    fn ceiling(self, snapshot: &GpuSnapshot) -> f64 {
+        match self {
+            ChartMetric::GpuUtil | ChartMetric::Encoder | ChartMetric::Decoder => 100.0,
+            ChartMetric::Vram => snapshot.vram_total_mb as f64,
+            ChartMetric::Temperature => snapshot.temp_slowdown_c.unwrap_or(90) as f64,
+            ChartMetric::Power => snapshot.power_limit_w,
+            ChartMetric::PcieTx | ChartMetric::PcieRx => 0.0,
+        }
+    }
+
+    fn fmt(self, value: f64) -> String {
+        match self {
+            ChartMetric::GpuUtil | ChartMetric::Encoder | ChartMetric::Decoder => {
+                format!("{:.0}%", value)
+            }
+            ChartMetric::Vram => text::fmt_mb(value.max(0.0) as u64),
+            ChartMetric::Temperature => format!("{:.0}°C", value),
+            ChartMetric::Power => format!("{:.0}W", value),
+            ChartMetric::PcieTx | ChartMetric::PcieRx => format!("{}/s", text::fmt_kb_s(value)),
+        }
+    }
+}
+
+/// State of the chart window
+pub struct ChartView {
+    pub open: bool,
+    metric: ChartMetric,
+    /// Width of the visible time range
+    span_ms: i64,
+    /// Right edge of the visible range; `None` follows the newest sample
+    end_ms: Option<i64>,
+    /// GPUs left off the chart
+    hidden: Vec<u32>,
+}
+
+impl Default for ChartView {
+    fn default() -> Self {
+        Self {
+            open: false,
+            metric: ChartMetric::GpuUtil,
+            span_ms: 10 * 60 * 1000,
+            end_ms: None,
+            hidden: Vec::new(),
+        }
+    }
+}
+
+/// Nearest sample at or before `t_ms`
+fn value_at(samples: &[Sample], t_ms: i64) -> Option<f64> {
+    let i = samples.partition_point(|s| s.t_ms <= t_ms);
+    samples.get(i.checked_sub(1)?).map(|s| s.avg)
+}
+
+impl ChartView {
+    /// Metric picker, GPU toggles and the chart itself
+    pub fn show(&mut self, ui: &mut egui::Ui, snapshots: &[GpuSnapshot], histories: &[GpuHistory]) {
+        let latest = histories
+            .iter()
+            .map(GpuHistory::latest_ms)
+            .max()
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("chart_metric")
+                .selected_text(self.metric.label())
+                .show_ui(ui, |ui| {
+                    for metric in ChartMetric::ALL {
+                        ui.selectable_value(&mut self.metric, metric, metric.label());
+                    }
+                });
+            for (n, snapshot) in snapshots.iter().enumerate() {
+                let shown = !self.hidden.contains(&snapshot.index);
+                let color = if shown {
+                    PALETTE[n % PALETTE.len()]
+                } else {
+                    ui::TEXT_DIM
+                };
+                let label = RichText::new(format!("GPU {}", snapshot.index))
+                    .size(10.0)
+                    .color(color);
+                if ui
+                    .selectable_label(shown, label)
+                    .on_hover_text(&snapshot.name)
+                    .clicked()
+                {
+                    if shown {
+                        self.hidden.push(snapshot.index);
+                    } else {
+                        self.hidden.retain(|&i| i != snapshot.index);
+                    }
+                }
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let label = if self.end_ms.is_some() {
+                    "live"
+                } else {
+                    "● live"
+                };
+                if ui
+                    .selectable_label(self.end_ms.is_none(), RichText::new(label).size(10.0))
+                    .on_hover_text("Follow the newest samples (or double-click the chart)")
+                    .clicked()
+                {
+                    self.end_ms = None;
+                }
+                ui.label(
+                    RichText::new(text::fmt_window(self.span_ms))
+                        .size(10.0)
+                        .color(ui::TEXT_SECONDARY),
+                );
+            });
+        });
+
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        let plot = Rect::from_min_max(
+            rect.min + egui::vec2(AXIS_LEFT, 4.0),
+            rect.max - egui::vec2(4.0, AXIS_BOTTOM),
+        );
+        if plot.width() < 20.0 || plot.height() < 20.0 {
+            return;
+        }
+
+        self.handle_input(ui, &response, plot, latest);
+        let end_ms = self.end_ms.unwrap_or(latest);
+        let start_ms = end_ms - self.span_ms;
+        let x_of =
+            |t_ms: i64| plot.left() + (t_ms - start_ms) as f32 / self.span_ms as f32 * plot.width();
+
+        // Data and value range
+        let lines: Vec<(Color32, u32, Vec<Sample>)> = snapshots
+            .iter()
+            .zip(histories)
+            .enumerate()
+            .filter(|(_, (s, _))| !self.hidden.contains(&s.index))
+            .map(|(n, (s, h))| {
+                let samples = self.metric.series(h).samples_between(start_ms, end_ms);
+                (PALETTE[n % PALETTE.len()], s.index, samples)
+            })
+            .collect();
+        let ceiling = snapshots
+            .iter()
+            .map(|s| self.metric.ceiling(s))
+            .fold(0.0, f64::max);
+        let data_max = lines
+            .iter()
+            .flat_map(|(_, _, samples)| samples.iter().map(|s| s.max))
+            .fold(0.0, f64::max);
+        let top = ceiling.max(data_max).max(1.0);
+        let y_of = |v: f64| plot.bottom() - (v / top).clamp(0.0, 1.05) as f32 * plot.height();
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(plot, 2.0, ui::BG_PANEL);
+        let font = FontId::monospace(9.0);
+
+        // Value axis: quarters of the range
+        for q in 0..=4 {
+            let v = top * q as f64 / 4.0;
+            let y = y_of(v);
+            painter.line_segment(
+                [Pos2::new(plot.left(), y), Pos2::new(plot.right(), y)],
+                Stroke::new(1.0, ui::BG_ELEVATED),
+            );
+            painter.text(
+                Pos2::new(plot.left() - 4.0, y),
+                Align2::RIGHT_CENTER,
+                self.metric.fmt(v),
+                font.clone(),
+                ui::TEXT_DIM,
+            );
+        }
+
+        // Time axis: about one tick per 90 points
+        let wanted = (plot.width() / 90.0).max(1.0) as i64;
+        let step = TIME_STEPS_MS
+            .into_iter()
+            .find(|&s| self.span_ms / s <= wanted)
+            .unwrap_or(TIME_STEPS_MS[TIME_STEPS_MS.len() - 1]);
+        let offset = chrono::Local::now().offset().local_minus_utc() as i64 * 1000;
+        // Ticks fall on round local times
+        let mut tick = start_ms - (start_ms + offset).rem_euclid(step) + step;
+        while tick <= end_ms {
+            let x = x_of(tick);
+            painter.line_segment(
+                [Pos2::new(x, plot.top()), Pos2::new(x, plot.bottom())],
+                Stroke::new(1.0, ui::BG_ELEVATED),
+            );
+            let label = text::fmt_clock(tick);
+            let label = if step >= 60 * 1000 {
+                label[..5].to_string()
+            } else {
+                label
+            };
+            painter.text(
+                Pos2::new(x, plot.bottom() + 2.0),
+                Align2::CENTER_TOP,
+                label,
+                font.clone(),
+                ui::TEXT_DIM,
+            );
+            tick += step;
+        }
+
+        // Min/max band under each average line, where samples are buckets
+        for (color, _, samples) in &lines {
+            for s in samples.iter().filter(|s| s.max > s.min) {
+                let x = x_of(s.t_ms);
+                painter.line_segment(
+                    [Pos2::new(x, y_of(s.min)), Pos2::new(x, y_of(s.max))],
+                    Stroke::new(1.0, color.gamma_multiply(0.25)),
+                );
+            }
+            let points: Vec<Pos2> = samples
+                .iter()
+                .map(|s| Pos2::new(x_of(s.t_ms), y_of(s.avg)))
+                .collect();
+            if points.len() > 1 {
+                painter.add(Shape::line(points, Stroke::new(1.5, *color)));
+            }
+        }
+
+        // Hover readout
+        if let Some(pos) = response.hover_pos().filter(|p| plot.contains(*p)) {
+            let t_ms =
+                start_ms + ((pos.x - plot.left()) / plot.width() * self.span_ms as f32) as i64;
+            painter.line_segment(
+                [
+                    Pos2::new(pos.x, plot.top()),
+                    Pos2::new(pos.x, plot.bottom()),
+                ],
+                Stroke::new(1.0, ui::TEXT_DIM),
+            );
+            let mut readout = vec![text::fmt_clock(t_ms)];
+            for (_, index, samples) in &lines {
+                if let Some(v) = value_at(samples, t_ms) {
+                    readout.push(format!("GPU {}  {}", index, self.metric.fmt(v)));
+                }
+            }
+            response.on_hover_text_at_pointer(readout.join("\n"));
+        }
+    }
+
+    /// Zoom with the scroll wheel or pinch around the pointer, pan by
+    /// dragging, double-click to go back to live
+    fn handle_input(&mut self, ui: &egui::Ui, response: &egui::Response, plot: Rect, latest: i64) {
+        if response.double_clicked() {
+            self.end_ms = None;
+            return;
+        }
+        let end_ms = self.end_ms.unwrap_or(latest);
+        let ms_per_point = self.span_ms as f64 / plot.width() as f64;
+
+        let drag = response.drag_delta().x as f64;
+        if drag != 0.0 {
+            self.end_ms = Some(end_ms - (drag * ms_per_point) as i64);
+        }
+
+        if let Some(pos) = response.hover_pos() {
+            let (scroll, pinch) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let factor = (-scroll as f64 * 0.003).exp() / pinch as f64;
+            if factor != 1.0 {
+                // Keep the time under the pointer where it is
+                let anchor_frac = ((pos.x - plot.left()) / plot.width()).clamp(0.0, 1.0) as f64;
+                let anchor = end_ms as f64 - (1.0 - anchor_frac) * self.span_ms as f64;
+                let span =
+                    (self.span_ms as f64 * factor).clamp(MIN_SPAN_MS as f64, MAX_WINDOW_MS as f64);
+                self.span_ms = span as i64;
+                let end = anchor + (1.0 - anchor_frac) * span;
+                self.end_ms = Some(end as i64);
+            }
+        }
+
+        // Past the newest sample means live again
+        if self.end_ms.is_some_and(|end| end >= latest) {
+            self.end_ms = None;
+        }
+    }
+}
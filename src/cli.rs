@@ -0,0 +1,201 @@
+//! Command-line parsing. Kept dependency-free; the surface is small.
+
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage: nvdash [command] [options]
+       nvdash session [NAME] [options] [-- COMMAND...]
+
+commands:
+  (none)             open the dashboard window
+  snapshot           print every GPU's current snapshot once and exit
+  watch              stream one JSON frame per line at the poll interval
+  exporter           serve Prometheus metrics over HTTP
+  tui                full-screen terminal dashboard
+  session            summarize GPU use until COMMAND exits (or Enter is
+                     pressed) as a Markdown or JSON report
+  agent              serve this machine's GPUs to nvdash windows elsewhere
+
+options:
+  --sim[=N]          use N simulated GPUs (default 1) instead of NVML
+  --record [FILE]    record every poll to FILE (default nvdash-<time>.jsonl)
+  --replay FILE      play back a recording instead of polling
+  --connect ADDR     also show the GPUs of the agent at ADDR (host:port);
+                     repeat for more agents
+  --config FILE      settings file (default <config dir>/nvdash/config.toml)
+  --format FMT       snapshot output: json (default) or table;
+                     session report: markdown (default) or json
+  --interval MS      watch/tui/session/agent poll interval in milliseconds
+                     (default 500)
+  --listen ADDR      exporter address (default 0.0.0.0:9835) or agent
+                     address (default 127.0.0.1:9836)
+  --share-cmdline    agent: also serve process command lines and working
+                     directories, which can hold secrets
+  -h, --help         show this help";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Gui,
+    Snapshot,
+    Watch,
+    Exporter,
+    Tui,
+    Session,
+    Agent,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Json,
+    Table,
+    Markdown,
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    pub help: bool,
+    /// Number of simulated GPUs, if the simulator was requested
+    pub sim: Option<u32>,
+    /// `Some(None)` means record to the default file name
+    pub record: Option<Option<PathBuf>>,
+    pub replay: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    /// `None` leaves the choice to the command
+    pub format: Option<Format>,
+    pub interval_ms: Option<u64>,
+    pub listen: Option<String>,
+    /// Whether the agent serves process command lines and working directories
+    pub share_cmdline: bool,
+    /// Agents to read GPUs from, besides the local ones
+    pub connect: Vec<String>,
+    /// Session name; defaults to the command being run
+    pub session_name: Option<String>,
+    /// What `session` runs: everything after `--`
+    pub session_command: Vec<String>,
+}
+
+pub fn parse() -> Result<Options, String> {
+    parse_from(std::env::args().skip(1))
+}
+
+pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.into_iter().peekable();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        match flag.as_str() {
+            "-h" | "--help" => opts.help = true,
+            "--sim" => {
+                let n = match inline {
+                    Some(v) => v
+                        .parse()
+                        .map_err(|_| format!("--sim: expected a GPU count, got '{}'", v))?,
+                    None => 1,
+                };
+                opts.sim = Some(n);
+            }
+            "--record" => {
+                let path = inline.or_else(|| args.next_if(|a| !a.starts_with('-')));
+                opts.record = Some(path.map(PathBuf::from));
+            }
+            "--format" => {
+                let value = inline
+                    .or_else(|| args.next())
+                    .ok_or("--format: missing FMT")?;
+                opts.format = Some(match value.as_str() {
+                    "json" => Format::Json,
+                    "table" => Format::Table,
+                    "markdown" | "md" => Format::Markdown,
+                    _ => {
+                        return Err(format!(
+                            "--format: expected json, table or markdown, got '{}'",
+                            value
+                        ))
+                    }
+                });
+            }
+            "--interval" => {
+                let value = inline
+                    .or_else(|| args.next())
+                    .ok_or("--interval: missing MS")?;
+                let ms: u64 = value
+                    .parse()
+                    .map_err(|_| format!("--interval: expected milliseconds, got '{}'", value))?;
+                opts.interval_ms = Some(ms.max(50));
+            }
+            "--listen" => {
+                let addr = inline
+                    .or_else(|| args.next())
+                    .ok_or("--listen: missing ADDR")?;
+                opts.listen = Some(addr);
+            }
+            "--share-cmdline" => opts.share_cmdline = true,
+            "tui" if opts.command == Command::Gui => opts.command = Command::Tui,
+            "exporter" if opts.command == Command::Gui => opts.command = Command::Exporter,
+            "snapshot" if opts.command == Command::Gui => opts.command = Command::Snapshot,
+            "watch" if opts.command == Command::Gui => opts.command = Command::Watch,
+            "session" if opts.command == Command::Gui => opts.command = Command::Session,
+            "agent" if opts.command == Command::Gui => opts.command = Command::Agent,
+            "--connect" => {
+                let addr = inline
+                    .or_else(|| args.next())
+                    .ok_or("--connect: missing ADDR")?;
+                opts.connect.push(addr);
+            }
+            "--" if opts.command == Command::Session => {
+                opts.session_command = args.by_ref().collect();
+            }
+            name if opts.command == Command::Session
+                && opts.session_name.is_none()
+                && !name.starts_with('-') =>
+            {
+                opts.session_name = Some(name.to_string());
+            }
+            "--replay" => {
+                let path = inline
+                    .or_else(|| args.next())
+                    .ok_or("--replay: missing FILE")?;
+                opts.replay = Some(PathBuf::from(path));
+            }
+            "--config" => {
+                let path = inline
+                    .or_else(|| args.next())
+                    .ok_or("--config: missing FILE")?;
+                opts.config = Some(PathBuf::from(path));
+            }
+            _ => return Err(format!("unknown argument '{}'\n\n{}", arg, USAGE)),
+        }
+    }
+
+    if opts.replay.is_some() && (opts.record.is_some() || opts.sim.is_some()) {
+        return Err("--replay cannot be combined with --record or --sim".into());
+    }
+    if opts.command != Command::Gui && (opts.record.is_some() || opts.replay.is_some()) {
+        return Err("--record and --replay only apply to the dashboard window".into());
+    }
+    if !opts.connect.is_empty() && (opts.command != Command::Gui || opts.replay.is_some()) {
+        return Err("--connect only applies to the live dashboard window".into());
+    }
+    if opts.share_cmdline && opts.command != Command::Agent {
+        return Err("--share-cmdline only applies to agent".into());
+    }
+    match (opts.command, opts.format) {
+        (_, None) | (Command::Snapshot, Some(Format::Json | Format::Table)) => {}
+        (Command::Session, Some(Format::Json | Format::Markdown)) => {}
+        (Command::Snapshot, Some(_)) => {
+            return Err("snapshot --format: expected json or table".into())
+        }
+        (Command::Session, Some(_)) => {
+            return Err("session --format: expected markdown or json".into())
+        }
+        _ => return Err("--format only applies to snapshot and session".into()),
+    }
+    Ok(opts)
+}
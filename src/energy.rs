@@ -0,0 +1,252 @@
+//! Energy accounting: how much a GPU has used since nvdash started and
+//! since the user last reset the meter, what that cost and what it emitted.
+//!
+//! Energy comes from NVML's cumulative counter where the device has one
+//! (Volta and later) and from integrating the power draw otherwise. Each
+//! poll's energy is split between the processes by their share of SM
+//! utilization, which `history` accumulates per process.
+
+use crate::gpu::GpuSnapshot;
+use serde::{Deserialize, Serialize};
+
+pub const J_PER_KWH: f64 = 3.6e6;
+
+/// Longest gap the power draw is integrated over; beyond it the device was
+/// not being watched and the gap counts for nothing
+const MAX_GAP_MS: i64 = 60 * 1000;
+
+/// Turns energy into cost and emissions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyRates {
+    /// Electricity price per kWh, in `currency`
+    pub price_per_kwh: f64,
+    /// Prefix for prices: `$`, `€`, ...
+    pub currency: String,
+    /// Grid carbon intensity, g CO₂ per kWh
+    pub co2_g_per_kwh: f64,
+}
+
+impl Default for EnergyRates {
+    fn default() -> Self {
+        Self {
+            price_per_kwh: 0.15,
+            currency: "$".into(),
+            co2_g_per_kwh: 400.0,
+        }
+    }
+}
+
+impl EnergyRates {
+    pub fn cost(&self, joules: f64) -> f64 {
+        joules / J_PER_KWH * self.price_per_kwh
+    }
+
+    pub fn co2_g(&self, joules: f64) -> f64 {
+        joules / J_PER_KWH * self.co2_g_per_kwh
+    }
+}
+
+/// Previous reading, for the next poll's delta
+#[derive(Clone, Copy, Debug)]
+struct Reading {
+    t_ms: i64,
+    counter_mj: Option<u64>,
+    power_w: f64,
+}
+
+/// Energy used by one GPU
+#[derive(Clone, Debug, Default)]
+pub struct EnergyMeter {
+    last: Option<Reading>,
+    /// First reading (Unix ms)
+    pub start_ms: Option<i64>,
+    pub total_j: f64,
+    /// When the meter was last reset; `None` if never
+    pub reset_ms: Option<i64>,
+    pub since_reset_j: f64,
+}
+
+impl EnergyMeter {
+    /// Account for a snapshot taken at `t_ms`; returns the joules used
+    /// since the previous one
+    pub fn push_at(&mut self, t_ms: i64, snapshot: &GpuSnapshot) -> f64 {
+        let reading = Reading {
+            t_ms,
+            counter_mj: snapshot.energy_mj,
+            power_w: snapshot.power_draw_w,
+        };
+        let joules = match self.last.replace(reading) {
+            None => {
+                self.start_ms = Some(t_ms);
+                0.0
+            }
+            Some(then) => match (then.counter_mj, reading.counter_mj) {
+                // The counter restarts when the driver reloads
+                (Some(a), Some(b)) if b >= a => (b - a) as f64 / 1000.0,
+                _ => {
+                    let dt_ms = t_ms - then.t_ms;
+                    if (0..=MAX_GAP_MS).contains(&dt_ms) {
+                        (then.power_w + reading.power_w) / 2.0 * dt_ms as f64 / 1000.0
+                    } else {
+                        0.0
+                    }
+                }
+            },
+        };
+        self.total_j += joules;
+        self.since_reset_j += joules;
+        joules
+    }
+
+    pub fn reset(&mut self, t_ms: i64) {
+        self.reset_ms = Some(t_ms);
+        self.since_reset_j = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::{ProcessInfo, ProcessUtil};
+    use crate::history::{ProcessHistory, ProcessKey};
+
+    fn reading(power_w: f64, energy_mj: Option<u64>) -> GpuSnapshot {
+        GpuSnapshot {
+            power_draw_w: power_w,
+            energy_mj,
+            ..GpuSnapshot::placeholder(0)
+        }
+    }
+
+    /// Feed `(t_ms, watts, counter mJ)` readings; returns each poll's joules
+    fn feed(meter: &mut EnergyMeter, readings: &[(i64, f64, Option<u64>)]) -> Vec<f64> {
+        readings
+            .iter()
+            .map(|&(t, w, mj)| meter.push_at(t, &reading(w, mj)))
+            .collect()
+    }
+
+    #[test]
+    fn the_counter_wins_over_power_draw() {
+        let mut meter = EnergyMeter::default();
+        let joules = feed(
+            &mut meter,
+            &[
+                (1000, 100.0, Some(5_000_000)),
+                (2000, 100.0, Some(5_250_000)),
+                (3000, 100.0, Some(5_500_000)),
+            ],
+        );
+        assert_eq!(joules, [0.0, 250.0, 250.0]);
+        assert_eq!(meter.start_ms, Some(1000));
+        assert_eq!(meter.total_j, 500.0);
+    }
+
+    #[test]
+    fn a_counter_going_backwards_falls_back_to_power() {
+        let mut meter = EnergyMeter::default();
+        // The driver reloaded and the counter started over
+        let joules = feed(
+            &mut meter,
+            &[
+                (0, 100.0, Some(9_000_000)),
+                (2000, 300.0, Some(1_000)),
+                (3000, 300.0, Some(301_000)),
+            ],
+        );
+        assert_eq!(joules, [0.0, 400.0, 300.0]);
+    }
+
+    #[test]
+    fn without_a_counter_power_draw_is_integrated() {
+        let mut meter = EnergyMeter::default();
+        // Trapezoids: (100 + 200) / 2 W for 2 s, then 200 W for 0.5 s
+        let joules = feed(
+            &mut meter,
+            &[(0, 100.0, None), (2000, 200.0, None), (2500, 200.0, None)],
+        );
+        assert_eq!(joules, [0.0, 300.0, 100.0]);
+        assert_eq!(meter.total_j, 400.0);
+    }
+
+    #[test]
+    fn long_gaps_count_for_nothing() {
+        let mut meter = EnergyMeter::default();
+        let joules = feed(
+            &mut meter,
+            &[
+                (0, 100.0, None),
+                (MAX_GAP_MS, 100.0, None),
+                (2 * MAX_GAP_MS + 1, 100.0, None),
+                // A clock stepping back doesn't count either
+                (MAX_GAP_MS, 100.0, None),
+            ],
+        );
+        assert_eq!(joules, [0.0, 6000.0, 0.0, 0.0]);
+        // The counter doesn't depend on the gap
+        let mut meter = EnergyMeter::default();
+        let joules = feed(
+            &mut meter,
+            &[(0, 0.0, Some(0)), (10 * MAX_GAP_MS, 0.0, Some(7_000_000))],
+        );
+        assert_eq!(joules, [0.0, 7000.0]);
+    }
+
+    #[test]
+    fn resetting_keeps_the_total() {
+        let mut meter = EnergyMeter::default();
+        feed(&mut meter, &[(0, 100.0, None), (1000, 100.0, None)]);
+        meter.reset(1000);
+        feed(&mut meter, &[(3000, 100.0, None)]);
+        assert_eq!((meter.total_j, meter.since_reset_j), (300.0, 200.0));
+        assert_eq!(meter.reset_ms, Some(1000));
+    }
+
+    fn proc(pid: u32, sm: Option<u32>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            vram_mb: 100,
+            utilization: sm.map(|sm| ProcessUtil {
+                sm,
+                mem: 0,
+                enc: 0,
+                dec: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn energy_j(history: &ProcessHistory, pid: u32) -> f64 {
+        let key = ProcessKey { pid, start_time: 0 };
+        history.get(&key).unwrap().energy_j
+    }
+
+    #[test]
+    fn processes_share_energy_by_sm_utilization() {
+        let mut history = ProcessHistory::default();
+        let snapshot = GpuSnapshot {
+            processes: vec![proc(1, Some(60)), proc(2, Some(20)), proc(3, None)],
+            ..GpuSnapshot::placeholder(0)
+        };
+        history.push_at(0, &snapshot, 400.0);
+        history.push_at(1000, &snapshot, 200.0);
+        assert_eq!(energy_j(&history, 1), 450.0);
+        assert_eq!(energy_j(&history, 2), 150.0);
+        // No utilization figure, no share
+        assert_eq!(energy_j(&history, 3), 0.0);
+    }
+
+    #[test]
+    fn energy_without_sm_figures_goes_unattributed() {
+        // Nothing to split by: no share rather than 0/0
+        let mut history = ProcessHistory::default();
+        let snapshot = GpuSnapshot {
+            processes: vec![proc(1, Some(0)), proc(2, None)],
+            ..GpuSnapshot::placeholder(0)
+        };
+        history.push_at(0, &snapshot, 400.0);
+        assert_eq!(energy_j(&history, 1), 0.0);
+        assert_eq!(energy_j(&history, 2), 0.0);
+    }
+}
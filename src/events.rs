@@ -0,0 +1,139 @@
+//! Background listener for NVML device events: critical XID errors, ECC
+//! errors, and clock, P-state, power source and MIG changes.
+//!
+//! Events arrive on their own schedule, so a thread per device blocks in
+//! NVML waiting for them and forwards each one over a channel; the backend
+//! drains the channel into the next snapshot. NVML only delivers events on
+//! Linux.
+
+use crate::gpu::GpuEvent;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Events received for a device, tagged with its index
+pub type EventReceiver = Receiver<(u32, GpuEvent)>;
+
+/// Start listening. The channel closes if no device can deliver events.
+pub fn spawn() -> EventReceiver {
+    let (tx, rx) = mpsc::channel();
+    let _ = std::thread::Builder::new()
+        .name("nvml-events".into())
+        .spawn(move || listen(tx));
+    rx
+}
+
+#[cfg(target_os = "linux")]
+fn listen(tx: Sender<(u32, GpuEvent)>) {
+    use crate::gpu::EventKind;
+    use nvml_wrapper::bitmasks::event::EventTypes;
+    use nvml_wrapper::enums::event::XidError;
+    use nvml_wrapper::error::NvmlError;
+    use nvml_wrapper::Nvml;
+
+    const KINDS: [(EventTypes, EventKind); 7] = [
+        (EventTypes::CRITICAL_XID_ERROR, EventKind::Xid),
+        (EventTypes::SINGLE_BIT_ECC_ERROR, EventKind::EccSingleBit),
+        (EventTypes::DOUBLE_BIT_ECC_ERROR, EventKind::EccDoubleBit),
+        (EventTypes::PSTATE_CHANGE, EventKind::PstateChange),
+        (EventTypes::CLOCK_CHANGE, EventKind::ClockChange),
+        (
+            EventTypes::POWER_SOURCE_CHANGE,
+            EventKind::PowerSourceChange,
+        ),
+        (EventTypes::MIG_CONFIG_CHANGE, EventKind::MigConfigChange),
+    ];
+    /// How long one wait blocks; only bounds how long a dead set lingers
+    const WAIT_MS: u32 = 1000;
+
+    // A handle of our own, so the backend's stays free for polling
+    let Ok(nvml) = Nvml::init() else {
+        return;
+    };
+    let Ok(count) = nvml.device_count() else {
+        return;
+    };
+    let now = || chrono::Utc::now().timestamp_millis();
+
+    // One set per device: registering a device the driver refuses frees
+    // the whole set, which would silence the devices already in it
+    std::thread::scope(|scope| {
+        for index in 0..count {
+            let nvml = &nvml;
+            let tx = tx.clone();
+            scope.spawn(move || {
+                let Ok(device) = nvml.device_by_index(index) else {
+                    return;
+                };
+                let Ok(supported) = device.supported_event_types() else {
+                    return;
+                };
+                let Ok(set) = nvml.create_event_set() else {
+                    return;
+                };
+                let Ok(set) = device.register_events(supported, set) else {
+                    return;
+                };
+                loop {
+                    let data = match set.wait(WAIT_MS) {
+                        Ok(data) => data,
+                        Err(NvmlError::Timeout) => continue,
+                        Err(NvmlError::GpuLost) => {
+                            let lost = GpuEvent {
+                                t_ms: now(),
+                                kind: EventKind::GpuLost,
+                                xid: None,
+                            };
+                            let _ = tx.send((index, lost));
+                            return;
+                        }
+                        Err(_) => return,
+                    };
+                    for (flag, kind) in KINDS {
+                        if !data.event_type.contains(flag) {
+                            continue;
+                        }
+                        let xid = match data.event_data {
+                            Some(XidError::Value(code)) if kind == EventKind::Xid => Some(code),
+                            _ => None,
+                        };
+                        let event = GpuEvent {
+                            t_ms: now(),
+                            kind,
+                            xid,
+                        };
+                        // The backend went away
+                        if tx.send((index, event)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listen(_tx: Sender<(u32, GpuEvent)>) {}
+
+/// What an XID code means, for the ones worth recognizing at a glance
+pub fn xid_description(code: u64) -> Option<&'static str> {
+    let text = match code {
+        13 => "Graphics engine exception",
+        31 => "GPU memory page fault",
+        32 => "Invalid or corrupted push buffer stream",
+        43 => "GPU stopped processing",
+        45 => "Preemptive cleanup, due to previous errors",
+        48 => "Double-bit ECC error",
+        61 | 62 => "Internal micro-controller error",
+        63 => "ECC page retirement or row remapping recorded",
+        64 => "ECC page retirement or row remapping failed",
+        68 => "Video processor exception",
+        74 => "NVLink error",
+        79 => "GPU has fallen off the bus",
+        92 => "High single-bit ECC error rate",
+        94 => "Contained ECC error",
+        95 => "Uncontained ECC error",
+        119 | 120 => "GSP error",
+        _ => return None,
+    };
+    Some(text)
+}
@@ -0,0 +1,230 @@
+//! Trend forecasts: when a metric will reach a limit if it keeps moving the
+//! way it has lately.
+//!
+//! The trend is a Theil–Sen fit, the median of the slopes between every
+//! pair of samples, so a dataloader stall or a one-off allocation spike
+//! doesn't swing it the way it would a least-squares line. The fitting is
+//! pure over `(t_ms, value)` points; the last two functions only pick the
+//! series and limits out of a GPU's history.
+
+use crate::gpu::GpuSnapshot;
+use crate::history::{GpuHistory, Series};
+
+/// How far back the trend looks
+pub const LOOKBACK_MS: i64 = 15 * 60 * 1000;
+/// Least history a trend is fitted to; shorter runs are mostly noise
+const MIN_SPAN_MS: i64 = 60 * 1000;
+const MIN_POINTS: usize = 8;
+/// Points kept for the fit; pairs grow with the square
+const MAX_POINTS: usize = 64;
+
+/// A straight line through the recent samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trend {
+    /// Change per millisecond
+    pub slope: f64,
+    /// Fitted value at `t_ms`
+    pub level: f64,
+    /// Time of the last point
+    pub t_ms: i64,
+}
+
+impl Trend {
+    /// Theil–Sen fit of `points` (oldest first); `None` if they span too
+    /// little time or are too few to trust
+    pub fn fit(points: &[(i64, f64)]) -> Option<Self> {
+        let (&(first_ms, _), &(t_ms, _)) = (points.first()?, points.last()?);
+        if points.len() < MIN_POINTS || t_ms - first_ms < MIN_SPAN_MS {
+            return None;
+        }
+        let stride = points.len().div_ceil(MAX_POINTS);
+        let points: Vec<(i64, f64)> = points.iter().copied().step_by(stride).collect();
+
+        let mut slopes = Vec::with_capacity(points.len() * (points.len() - 1) / 2);
+        for (i, &(ta, va)) in points.iter().enumerate() {
+            for &(tb, vb) in &points[i + 1..] {
+                if tb != ta {
+                    slopes.push((vb - va) / (tb - ta) as f64);
+                }
+            }
+        }
+        let slope = median(&mut slopes)?;
+        let mut levels: Vec<f64> = points
+            .iter()
+            .map(|&(t, v)| v + slope * (t_ms - t) as f64)
+            .collect();
+        let level = median(&mut levels)?;
+        Some(Self { slope, level, t_ms })
+    }
+
+    /// Milliseconds from the last point until the line climbs to `limit`;
+    /// `None` if it is flat or falling, or already there
+    pub fn time_to(&self, limit: f64) -> Option<i64> {
+        if self.slope <= 0.0 || self.level >= limit {
+            return None;
+        }
+        Some(((limit - self.level) / self.slope) as i64)
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+fn recent(series: &Series, end_ms: i64) -> Vec<(i64, f64)> {
+    series
+        .samples(LOOKBACK_MS, end_ms)
+        .iter()
+        .map(|s| (s.t_ms, s.avg))
+        .collect()
+}
+
+/// Time until VRAM is full
+pub fn vram_exhaustion(history: &GpuHistory, snapshot: &GpuSnapshot) -> Option<i64> {
+    if snapshot.vram_total_mb == 0 {
+        return None;
+    }
+    let trend = Trend::fit(&recent(&history.vram_used, history.latest_ms()))?;
+    trend.time_to(snapshot.vram_total_mb as f64)
+}
+
+/// Time until the temperature reaches the hardware slowdown threshold
+pub fn thermal_throttle(history: &GpuHistory, snapshot: &GpuSnapshot) -> Option<i64> {
+    let limit = snapshot.temp_slowdown_c?;
+    let trend = Trend::fit(&recent(&history.temperature, history.latest_ms()))?;
+    trend.time_to(limit as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every 5 s for 10 minutes
+    fn series(value: impl Fn(i64) -> f64) -> Vec<(i64, f64)> {
+        (0..=120)
+            .map(|i| i * 5_000)
+            .map(|t| (t, value(t)))
+            .collect()
+    }
+
+    /// 10 MB/s from 10 GB, reaching 16 GB at the last point
+    fn leak(t_ms: i64) -> f64 {
+        10_000.0 + t_ms as f64 / 100.0
+    }
+
+    #[test]
+    fn linear_leak_hits_the_limit_on_time() {
+        let trend = Trend::fit(&series(leak)).unwrap();
+        assert_eq!(trend.t_ms, 600_000);
+        assert!((trend.slope - 0.01).abs() < 1e-9);
+        assert!((trend.level - 16_000.0).abs() < 1e-6);
+        // 8 GB left at 10 MB/s
+        let ms = trend.time_to(24_000.0).unwrap();
+        assert!((ms - 800_000).abs() <= 1, "{} ms", ms);
+    }
+
+    #[test]
+    fn flat_and_falling_series_have_no_forecast() {
+        let flat = Trend::fit(&series(|_| 5_000.0)).unwrap();
+        assert_eq!(flat.slope, 0.0);
+        assert_eq!(flat.time_to(24_000.0), None);
+
+        let falling = Trend::fit(&series(|t| 20_000.0 - t as f64 / 100.0)).unwrap();
+        assert!(falling.slope < 0.0);
+        assert_eq!(falling.time_to(24_000.0), None);
+    }
+
+    #[test]
+    fn nothing_left_to_forecast_at_the_limit() {
+        let trend = Trend::fit(&series(leak)).unwrap();
+        assert_eq!(trend.time_to(16_000.0), None);
+        assert_eq!(trend.time_to(12_000.0), None);
+    }
+
+    #[test]
+    fn outliers_do_not_swing_the_trend() {
+        // Allocation spikes on every tenth sample, dataloader stalls
+        // dropping to a third on every seventh
+        let noisy = series(|t| {
+            let i = t / 5_000;
+            if i % 10 == 3 {
+                leak(t) + 6_000.0
+            } else if i % 7 == 5 {
+                leak(t) / 3.0
+            } else {
+                leak(t)
+            }
+        });
+        let trend = Trend::fit(&noisy).unwrap();
+        let ms = trend.time_to(24_000.0).unwrap();
+        assert!((ms - 800_000).abs() < 16_000, "{} ms", ms);
+
+        // A least-squares line through the same points is thrown well off
+        let n = noisy.len() as f64;
+        let (mt, mv) = noisy
+            .iter()
+            .fold((0.0, 0.0), |(a, b), &(t, v)| (a + t as f64 / n, b + v / n));
+        let (cov, var) = noisy.iter().fold((0.0, 0.0), |(c, w), &(t, v)| {
+            (c + (t as f64 - mt) * (v - mv), w + (t as f64 - mt).powi(2))
+        });
+        assert!((cov / var - 0.01).abs() > 0.0005);
+    }
+
+    #[test]
+    fn too_little_history_is_not_fitted() {
+        // Long enough but too few points
+        let sparse: Vec<(i64, f64)> = (0..MIN_POINTS as i64 - 1)
+            .map(|i| (i * 60_000, leak(i * 60_000)))
+            .collect();
+        assert_eq!(Trend::fit(&sparse), None);
+
+        // Plenty of points over too short a span
+        let brief: Vec<(i64, f64)> = (0..50).map(|i| (i * 1_000, leak(i * 1_000))).collect();
+        assert_eq!(Trend::fit(&brief), None);
+
+        assert_eq!(Trend::fit(&[]), None);
+    }
+
+    #[test]
+    fn large_series_are_subsampled() {
+        let dense: Vec<(i64, f64)> = (0..=6_000).map(|i| (i * 100, leak(i * 100))).collect();
+        let trend = Trend::fit(&dense).unwrap();
+        assert_eq!(trend.t_ms, 600_000);
+        assert!((trend.slope - 0.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn vram_exhaustion_from_history() {
+        let mut history = GpuHistory::new();
+        let t0 = 1_700_000_000_000;
+        let snapshot = |t_ms: i64| GpuSnapshot {
+            vram_used_mb: leak(t_ms) as u64,
+            vram_total_mb: 24_000,
+            temperature: 60,
+            temp_slowdown_c: Some(88),
+            ..GpuSnapshot::placeholder(0)
+        };
+        for (t, _) in series(leak) {
+            history.push_at(t0 + t, &snapshot(t));
+        }
+        let ms = vram_exhaustion(&history, &snapshot(600_000)).unwrap();
+        assert!((ms - 800_000).abs() < 16_000, "{} ms", ms);
+        // Steady temperature: no thermal forecast
+        assert_eq!(thermal_throttle(&history, &snapshot(600_000)), None);
+        // Unknown total: nothing to run out of
+        let unknown = GpuSnapshot {
+            vram_total_mb: 0,
+            ..snapshot(600_000)
+        };
+        assert_eq!(vram_exhaustion(&history, &unknown), None);
+    }
+}
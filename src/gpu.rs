@@ -1,27 +1,313 @@
-use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
-use nvml_wrapper::enums::device::UsedGpuMemory;
-use nvml_wrapper::error::NvmlError;
-use nvml_wrapper::Nvml;
-use std::collections::VecDeque;
+use crate::nvml::NvmlBackend;
+use crate::sim::{SimBackend, SimConfig};
+use serde::{Deserialize, Serialize};
 
-/// Maximum number of history samples to keep (at 500ms poll = ~60s of history)
-const MAX_HISTORY: usize = 120;
+// `ProcessInfo` and `GpuSnapshot` are the JSON schema for `snapshot`/`watch`
+// output and recordings: fields serialize in declaration order under these
+// names. Only ever append fields; never rename or reorder them.
 
-#[derive(Clone, Debug)]
+/// Which NVML process list a process came from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessKind {
+    Compute,
+    Graphics,
+    /// In both the compute and the graphics list
+    Both,
+    /// Recorded before the kind was tracked
+    #[default]
+    Unknown,
+}
+
+impl ProcessKind {
+    /// nvidia-smi's type column: `C`, `G`, `C+G`
+    pub fn label(self) -> &'static str {
+        match self {
+            ProcessKind::Compute => "C",
+            ProcessKind::Graphics => "G",
+            ProcessKind::Both => "C+G",
+            ProcessKind::Unknown => "-",
+        }
+    }
+}
+
+/// A process's share of the GPU's engines over the last poll, in percent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessUtil {
+    /// SM (3D / compute)
+    pub sm: u32,
+    /// Frame buffer memory
+    pub mem: u32,
+    /// Video encoder
+    pub enc: u32,
+    /// Video decoder
+    pub dec: u32,
+}
+
+/// Codec of a hardware encoder session
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+}
+
+impl VideoCodec {
+    pub fn label(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "H.264",
+            VideoCodec::Hevc => "HEVC",
+        }
+    }
+}
+
+/// One NVENC session; rates are the driver's moving averages
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncoderSession {
+    pub session_id: u32,
+    pub codec: VideoCodec,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub latency_us: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ProcessInfo {
     pub pid: u32,
     pub name: String,
     pub vram_mb: u64,
+    /// Unix seconds; 0 if unknown. Tells a process apart from a later one
+    /// that reuses its PID.
+    #[serde(default)]
+    pub start_time: u64,
+    /// Full command line, arguments joined by spaces; empty if unknown
+    #[serde(default)]
+    pub cmdline: String,
+    /// Owning user name; empty if unknown
+    #[serde(default)]
+    pub user: String,
+    /// Host CPU usage since the previous poll (or since nvdash started, on
+    /// the first); 100 = one core
+    #[serde(default)]
+    pub cpu_pct: f32,
+    /// Host resident memory
+    #[serde(default)]
+    pub rss_mb: u64,
+    /// Working directory; empty if unknown
+    #[serde(default)]
+    pub cwd: String,
+    #[serde(default)]
+    pub kind: ProcessKind,
+    /// `None` if the device doesn't report per-process utilization
+    #[serde(default)]
+    pub utilization: Option<ProcessUtil>,
+    /// Hardware encoder sessions the process has open
+    #[serde(default)]
+    pub encoder_sessions: Vec<EncoderSession>,
+}
+
+/// One MIG slice: a compute instance inside a GPU instance
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MigInstance {
+    pub gpu_instance_id: u32,
+    pub compute_instance_id: u32,
+    /// Profile name as `nvidia-smi` prints it: `1g.10gb`
+    pub profile: String,
+    pub uuid: String,
+    pub vram_used_mb: u64,
+    pub vram_total_mb: u64,
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// What an NVML device event reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Critical XID error; the code is in `GpuEvent::xid`
+    Xid,
+    /// Corrected (single-bit) ECC error
+    EccSingleBit,
+    /// Uncorrected (double-bit) ECC error
+    EccDoubleBit,
+    PstateChange,
+    ClockChange,
+    PowerSourceChange,
+    MigConfigChange,
+    /// The driver lost the device (fell off the bus, ...)
+    GpuLost,
+}
+
+impl EventKind {
+    /// Errors that can take a job down, as opposed to state changes
+    pub fn critical(self) -> bool {
+        matches!(self, EventKind::Xid | EventKind::EccDoubleBit | EventKind::GpuLost)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EventKind::Xid => "XID",
+            EventKind::EccSingleBit => "ECC corrected",
+            EventKind::EccDoubleBit => "ECC uncorrected",
+            EventKind::PstateChange => "P-state change",
+            EventKind::ClockChange => "Clock change",
+            EventKind::PowerSourceChange => "Power source change",
+            EventKind::MigConfigChange => "MIG config change",
+            EventKind::GpuLost => "GPU lost",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GpuEvent {
+    /// When the event arrived (Unix ms)
+    pub t_ms: i64,
+    pub kind: EventKind,
+    /// XID code, for `EventKind::Xid`
+    #[serde(default)]
+    pub xid: Option<u64>,
+}
+
+/// ECC error counts. Volatile counts reset when the driver loads;
+/// aggregate counts cover the board's lifetime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EccCounts {
+    pub volatile_corrected: u64,
+    pub volatile_uncorrected: u64,
+    pub aggregate_corrected: u64,
+    pub aggregate_uncorrected: u64,
+}
+
+/// GPU utilization below which the device counts as idle
+pub const IDLE_UTIL: u32 = 5;
+
+/// GPU utilization above which a link below its maximum generation is a
+/// problem rather than power saving
+const LINK_BUSY_UTIL: u32 = 20;
+
+/// PCIe link state and throughput
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PcieStatus {
+    /// Host-bound (TX) and device-bound (RX) throughput, KB/s
+    pub tx_kb_s: u32,
+    pub rx_kb_s: u32,
+    pub link_gen: u32,
+    pub max_link_gen: u32,
+    pub link_width: u32,
+    pub max_link_width: u32,
 }
 
-#[derive(Clone, Debug)]
+impl PcieStatus {
+    /// Per-direction bandwidth of the link at its maximum, KB/s
+    pub fn max_kb_s(&self) -> f64 {
+        // Per lane after encoding overhead, Gen1..Gen6
+        const LANE_MB_S: [f64; 6] = [250.0, 500.0, 985.0, 1969.0, 3938.0, 7563.0];
+        let gen = (self.max_link_gen.clamp(1, 6) - 1) as usize;
+        LANE_MB_S[gen] * 1024.0 * self.max_link_width as f64
+    }
+
+    /// Trained below its maximum: fewer lanes at any time, or a lower
+    /// generation while the GPU is busy (idle links downshift to save power)
+    pub fn degraded(&self, gpu_util: Option<u32>) -> bool {
+        self.link_width < self.max_link_width
+            || (gpu_util.is_some_and(|u| u >= LINK_BUSY_UTIL) && self.link_gen < self.max_link_gen)
+    }
+}
+
+/// One NVLink and its counters
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NvLinkStatus {
+    pub link: u32,
+    pub active: bool,
+    pub version: u32,
+    /// PCI bus ID of the device at the other end
+    pub remote_bus_id: String,
+    /// Index of that device when it is a local GPU rather than an NVSwitch
+    pub remote_gpu: Option<u32>,
+    /// Data throughput per direction, KB/s; `None` where the driver doesn't
+    /// count it (before R510)
+    pub tx_kb_s: Option<u64>,
+    pub rx_kb_s: Option<u64>,
+    /// Error counters since the driver loaded. CRC counts both flit and
+    /// data CRC errors.
+    pub crc_errors: u64,
+    pub replay_errors: u64,
+    pub recovery_errors: u64,
+}
+
+impl NvLinkStatus {
+    pub fn errors(&self) -> u64 {
+        self.crc_errors + self.replay_errors + self.recovery_errors
+    }
+}
+
+/// Why the clocks are being held down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleReason {
+    /// Nothing to run, so clocks idle
+    GpuIdle,
+    /// Capped by application or user-set clocks
+    ApplicationsClocks,
+    SwPowerCap,
+    /// Hardware slowdown: high temperature, external power brake, ...
+    HwSlowdown,
+    /// Held back to match the other GPUs in a sync-boost group
+    SyncBoost,
+    SwThermal,
+    HwThermal,
+    HwPowerBrake,
+    /// Held by the display's clock requirements
+    DisplayClocks,
+}
+
+impl ThrottleReason {
+    pub const ALL: [ThrottleReason; 9] = [
+        ThrottleReason::GpuIdle,
+        ThrottleReason::ApplicationsClocks,
+        ThrottleReason::SwPowerCap,
+        ThrottleReason::HwSlowdown,
+        ThrottleReason::SyncBoost,
+        ThrottleReason::SwThermal,
+        ThrottleReason::HwThermal,
+        ThrottleReason::HwPowerBrake,
+        ThrottleReason::DisplayClocks,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ThrottleReason::GpuIdle => "Idle",
+            ThrottleReason::ApplicationsClocks => "App clocks",
+            ThrottleReason::SwPowerCap => "SW power cap",
+            ThrottleReason::HwSlowdown => "HW slowdown",
+            ThrottleReason::SyncBoost => "Sync boost",
+            ThrottleReason::SwThermal => "SW thermal",
+            ThrottleReason::HwThermal => "HW thermal",
+            ThrottleReason::HwPowerBrake => "Power brake",
+            ThrottleReason::DisplayClocks => "Display clocks",
+        }
+    }
+
+    /// Costs performance the work asked for; idling and clocks someone
+    /// chose to set don't count
+    pub fn limits_performance(self) -> bool {
+        !matches!(
+            self,
+            ThrottleReason::GpuIdle
+                | ThrottleReason::ApplicationsClocks
+                | ThrottleReason::DisplayClocks
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GpuSnapshot {
     pub name: String,
     pub index: u32,
     pub driver_version: String,
     pub cuda_version: String,
 
-    // Utilization
+    // Utilization; 0 where NVML can't read it (see `util_unavailable`)
     pub gpu_util: u32,       // 0-100%
     pub memory_util: u32,    // 0-100%
 
@@ -42,172 +328,305 @@ pub struct GpuSnapshot {
 
     // Processes
     pub processes: Vec<ProcessInfo>,
-}
 
-#[derive(Clone, Debug)]
-pub struct GpuHistory {
-    pub gpu_util: VecDeque<f64>,
-    pub vram_used: VecDeque<f64>,
-    pub temperature: VecDeque<f64>,
-    pub power_draw: VecDeque<f64>,
+    // Identity (stable across reboots, unlike `index`)
+    #[serde(default)]
+    pub uuid: String,
+
+    // MIG slices, when the device is partitioned. Processes running in a
+    // slice are listed under it rather than in `processes`.
+    #[serde(default)]
+    pub mig_instances: Vec<MigInstance>,
+
+    // Reliability: ECC counters (`None` when ECC is off or unsupported) and
+    // the device events received since the previous snapshot
+    #[serde(default)]
+    pub ecc: Option<EccCounts>,
+    #[serde(default)]
+    pub events: Vec<GpuEvent>,
+
+    // Why the clocks are below their maximum right now
+    #[serde(default)]
+    pub throttle_reasons: Vec<ThrottleReason>,
+
+    // PCIe (`None` for devices not on PCIe or that don't report it)
+    #[serde(default)]
+    pub pcie: Option<PcieStatus>,
+
+    // NVLink (empty for devices without it)
+    #[serde(default)]
+    pub nvlinks: Vec<NvLinkStatus>,
+
+    // Video engines, percent busy (`None` where not reported)
+    #[serde(default)]
+    pub encoder_util: Option<u32>,
+    #[serde(default)]
+    pub decoder_util: Option<u32>,
+
+    /// Energy counter since the driver loaded, mJ (`None` before Volta)
+    #[serde(default)]
+    pub energy_mj: Option<u64>,
+
+    /// Temperature at which hardware slowdown starts, °C; `None` if the
+    /// device doesn't report it
+    #[serde(default)]
+    pub temp_slowdown_c: Option<u32>,
+
+    /// Agent the snapshot came from (`host:port`); empty for local GPUs
+    #[serde(default)]
+    pub host: String,
+
+    /// NVML can't read utilization here (MIG-enabled GPUs, some vGPUs), so
+    /// `gpu_util` and `memory_util` are 0 without meaning idle
+    #[serde(default)]
+    pub util_unavailable: bool,
 }
 
-impl GpuHistory {
-    pub fn new() -> Self {
+impl GpuSnapshot {
+    /// `gpu_util`, or `None` where it couldn't be read
+    pub fn gpu_util(&self) -> Option<u32> {
+        (!self.util_unavailable).then_some(self.gpu_util)
+    }
+
+    /// `memory_util`, or `None` where it couldn't be read
+    pub fn memory_util(&self) -> Option<u32> {
+        (!self.util_unavailable).then_some(self.memory_util)
+    }
+
+    /// Every process on the device, MIG slices included, with the slice it
+    /// runs in
+    pub fn all_processes(&self) -> impl Iterator<Item = (&ProcessInfo, Option<&MigInstance>)> {
+        self.processes.iter().map(|p| (p, None)).chain(
+            self.mig_instances
+                .iter()
+                .flat_map(|m| m.processes.iter().map(move |p| (p, Some(m)))),
+        )
+    }
+
+    /// Active throttle reasons that cost performance
+    pub fn throttled(&self) -> impl Iterator<Item = ThrottleReason> + '_ {
+        self.throttle_reasons
+            .iter()
+            .copied()
+            .filter(|r| r.limits_performance())
+    }
+
+    /// Stand-in for a device that could not be read
+    pub fn placeholder(index: u32) -> Self {
         Self {
-            gpu_util: VecDeque::with_capacity(MAX_HISTORY),
-            vram_used: VecDeque::with_capacity(MAX_HISTORY),
-            temperature: VecDeque::with_capacity(MAX_HISTORY),
-            power_draw: VecDeque::with_capacity(MAX_HISTORY),
+            name: format!("GPU {} (error)", index),
+            index,
+            driver_version: String::new(),
+            cuda_version: String::new(),
+            gpu_util: 0,
+            memory_util: 0,
+            vram_used_mb: 0,
+            vram_total_mb: 0,
+            temperature: 0,
+            fan_speed: None,
+            power_draw_w: 0.0,
+            power_limit_w: 0.0,
+            clock_graphics_mhz: 0,
+            clock_memory_mhz: 0,
+            clock_sm_mhz: 0,
+            processes: vec![],
+            uuid: String::new(),
+            mig_instances: vec![],
+            ecc: None,
+            events: vec![],
+            throttle_reasons: vec![],
+            pcie: None,
+            nvlinks: vec![],
+            encoder_util: None,
+            decoder_util: None,
+            energy_mj: None,
+            temp_slowdown_c: None,
+            host: String::new(),
+            util_unavailable: true,
         }
     }
+}
+
+/// A GPU as the config file names it. A number is the index of a GPU on
+/// this machine; a string is a GPU's UUID, which also picks out GPUs on
+/// agents, whose display indices shift as agents come and go.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GpuRef {
+    Index(u32),
+    Uuid(String),
+}
 
-    pub fn push(&mut self, snapshot: &GpuSnapshot) {
-        Self::push_val(&mut self.gpu_util, snapshot.gpu_util as f64);
-        Self::push_val(&mut self.vram_used, snapshot.vram_used_mb as f64);
-        Self::push_val(&mut self.temperature, snapshot.temperature as f64);
-        Self::push_val(&mut self.power_draw, snapshot.power_draw_w);
+impl GpuRef {
+    /// `snapshot` by UUID, or by index where the device has none
+    pub fn of(snapshot: &GpuSnapshot) -> Self {
+        if snapshot.uuid.is_empty() {
+            GpuRef::Index(snapshot.index)
+        } else {
+            GpuRef::Uuid(snapshot.uuid.clone())
+        }
     }
 
-    fn push_val(buf: &mut VecDeque<f64>, val: f64) {
-        if buf.len() >= MAX_HISTORY {
-            buf.pop_front();
+    pub fn matches(&self, snapshot: &GpuSnapshot) -> bool {
+        match self {
+            GpuRef::Index(i) => snapshot.host.is_empty() && snapshot.index == *i,
+            GpuRef::Uuid(uuid) => snapshot.uuid == *uuid,
         }
-        buf.push_back(val);
     }
 }
 
+/// Error returned by a backend when a device cannot be read
+pub type GpuError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A source of GPU snapshots (real hardware, simulation, ...)
+pub trait GpuBackend: Send {
+    fn device_count(&self) -> u32;
+
+    fn snapshot(&mut self, index: u32) -> Result<GpuSnapshot, GpuError>;
+
+    /// Whether the PIDs in snapshots are made up rather than real processes
+    fn simulated(&self) -> bool {
+        false
+    }
+}
+
+/// Front end over whichever backend is feeding the dashboard
 pub struct GpuMonitor {
-    nvml: Nvml,
-    device_count: u32,
+    backend: Box<dyn GpuBackend>,
 }
 
 impl GpuMonitor {
-    pub fn init() -> Result<Self, NvmlError> {
-        let nvml = Nvml::init()?;
-        let device_count = nvml.device_count()?;
-        Ok(Self { nvml, device_count })
+    /// Open the NVIDIA driver via NVML
+    pub fn init() -> Result<Self, GpuError> {
+        Ok(Self {
+            backend: Box::new(NvmlBackend::init()?),
+        })
     }
 
-    pub fn device_count(&self) -> u32 {
-        self.device_count
+    /// Generate synthetic data instead of talking to a GPU
+    pub fn simulated(config: SimConfig) -> Self {
+        Self {
+            backend: Box::new(SimBackend::new(config)),
+        }
     }
 
-    pub fn driver_version(&self) -> String {
-        self.nvml.sys_driver_version().unwrap_or_else(|_| "N/A".into())
+    pub fn device_count(&self) -> u32 {
+        self.backend.device_count()
     }
 
-    pub fn cuda_version(&self) -> String {
-        match self.nvml.sys_cuda_driver_version() {
-            Ok(v) => {
-                let major = v / 1000;
-                let minor = (v % 1000) / 10;
-                format!("{}.{}", major, minor)
-            }
-            Err(_) => "N/A".into(),
-        }
+    pub fn snapshot(&mut self, index: u32) -> Result<GpuSnapshot, GpuError> {
+        self.backend.snapshot(index)
     }
 
-    pub fn snapshot(&self, index: u32) -> Result<GpuSnapshot, NvmlError> {
-        let device = self.nvml.device_by_index(index)?;
+    pub fn is_simulated(&self) -> bool {
+        self.backend.simulated()
+    }
 
-        let name = device.name().unwrap_or_else(|_| "Unknown GPU".into());
+    /// Read every device. One that fails is reported and stands in as a
+    /// placeholder, so each GPU keeps its position from frame to frame.
+    pub fn snapshot_all(&mut self) -> Vec<GpuSnapshot> {
+        (0..self.device_count())
+            .map(|i| self.read(i).unwrap_or_else(|| GpuSnapshot::placeholder(i)))
+            .collect()
+    }
 
-        let utilization = device.utilization_rates().unwrap_or(
-            nvml_wrapper::struct_wrappers::device::Utilization { gpu: 0, memory: 0 },
-        );
+    /// Read every device, reporting (but skipping) the ones that fail; for
+    /// consumers that would take a placeholder's zeros for real readings
+    pub fn snapshot_readable(&mut self) -> Vec<GpuSnapshot> {
+        (0..self.device_count())
+            .filter_map(|i| self.read(i))
+            .collect()
+    }
 
-        let mem_info = device.memory_info()?;
+    fn read(&mut self, index: u32) -> Option<GpuSnapshot> {
+        self.snapshot(index)
+            .map_err(|e| eprintln!("Warning: failed to read GPU {}: {}", index, e))
+            .ok()
+    }
+}
 
-        let temperature = device
-            .temperature(TemperatureSensor::Gpu)
-            .unwrap_or(0);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let fan_speed = device.fan_speed(0).ok();
+    #[test]
+    fn monitor_reads_every_simulated_gpu() {
+        let mut monitor = GpuMonitor::simulated(SimConfig {
+            gpus: 3,
+            ..Default::default()
+        });
+        assert!(monitor.is_simulated());
+        assert_eq!(monitor.device_count(), 3);
 
-        let power_draw_mw = device.power_usage().unwrap_or(0) as f64;
-        let power_limit_mw = device.enforced_power_limit().unwrap_or(0) as f64;
+        let snapshots = monitor.snapshot_all();
+        let indices: Vec<u32> = snapshots.iter().map(|s| s.index).collect();
+        assert_eq!(indices, [0, 1, 2]);
+        let mut uuids: Vec<&str> = snapshots.iter().map(|s| s.uuid.as_str()).collect();
+        uuids.dedup();
+        assert_eq!(uuids.len(), 3);
 
-        let clock_graphics = device
-            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Graphics)
-            .unwrap_or(0);
-        let clock_memory = device
-            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Memory)
-            .unwrap_or(0);
-        let clock_sm = device
-            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::SM)
-            .unwrap_or(0);
+        assert!(monitor.snapshot(3).is_err());
+    }
 
-        // Collect GPU processes from both compute and graphics lists
-        let mut processes = Vec::new();
+    /// Three GPUs, the middle one unreadable
+    struct Flaky;
 
-        if let Ok(compute_procs) = device.running_compute_processes() {
-            for proc in compute_procs {
-                let vram_bytes = match proc.used_gpu_memory {
-                    UsedGpuMemory::Used(bytes) => bytes,
-                    UsedGpuMemory::Unavailable => 0,
-                };
-                processes.push(ProcessInfo {
-                    pid: proc.pid,
-                    name: String::new(),
-                    vram_mb: vram_bytes / (1024 * 1024),
-                });
-            }
+    impl GpuBackend for Flaky {
+        fn device_count(&self) -> u32 {
+            3
         }
-        if let Ok(gfx_procs) = device.running_graphics_processes() {
-            for proc in gfx_procs {
-                if processes.iter().any(|p| p.pid == proc.pid) {
-                    continue;
-                }
-                let vram_bytes = match proc.used_gpu_memory {
-                    UsedGpuMemory::Used(bytes) => bytes,
-                    UsedGpuMemory::Unavailable => 0,
-                };
-                processes.push(ProcessInfo {
-                    pid: proc.pid,
-                    name: String::new(),
-                    vram_mb: vram_bytes / (1024 * 1024),
-                });
+
+        fn snapshot(&mut self, index: u32) -> Result<GpuSnapshot, GpuError> {
+            if index == 1 {
+                return Err("GPU is lost".into());
             }
+            Ok(GpuSnapshot {
+                uuid: format!("GPU-{}", index),
+                ..GpuSnapshot::placeholder(index)
+            })
         }
+    }
 
-        // Batch resolve process names
-        resolve_process_names(&mut processes);
-
-        // Sort by VRAM usage descending
-        processes.sort_by(|a, b| b.vram_mb.cmp(&a.vram_mb));
+    #[test]
+    fn failed_reads_keep_their_place() {
+        let mut monitor = GpuMonitor {
+            backend: Box::new(Flaky),
+        };
+        let snapshots = monitor.snapshot_all();
+        let read: Vec<(u32, &str)> = snapshots
+            .iter()
+            .map(|s| (s.index, s.uuid.as_str()))
+            .collect();
+        assert_eq!(read, [(0, "GPU-0"), (1, ""), (2, "GPU-2")]);
 
-        Ok(GpuSnapshot {
-            name,
-            index,
-            driver_version: self.driver_version(),
-            cuda_version: self.cuda_version(),
-            gpu_util: utilization.gpu,
-            memory_util: utilization.memory,
-            vram_used_mb: mem_info.used / (1024 * 1024),
-            vram_total_mb: mem_info.total / (1024 * 1024),
-            temperature,
-            fan_speed,
-            power_draw_w: power_draw_mw / 1000.0,
-            power_limit_w: power_limit_mw / 1000.0,
-            clock_graphics_mhz: clock_graphics,
-            clock_memory_mhz: clock_memory,
-            clock_sm_mhz: clock_sm,
-            processes,
-        })
+        let readable: Vec<u32> = monitor
+            .snapshot_readable()
+            .iter()
+            .map(|s| s.index)
+            .collect();
+        assert_eq!(readable, [0, 2]);
     }
-}
 
-fn resolve_process_names(processes: &mut [ProcessInfo]) {
-    use sysinfo::{Pid, System};
-    let pids: Vec<Pid> = processes.iter().map(|p| Pid::from_u32(p.pid)).collect();
-    let mut sys = System::new();
-    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&pids), true);
-    for proc in processes.iter_mut() {
-        let pid = Pid::from_u32(proc.pid);
-        proc.name = sys
-            .process(pid)
-            .map(|p| p.name().to_string_lossy().to_string())
-            .unwrap_or_else(|| format!("PID {}", proc.pid));
+    #[test]
+    fn unknown_utilization_keeps_the_schema() {
+        let snap = GpuSnapshot {
+            gpu_util: 0,
+            util_unavailable: true,
+            ..GpuSnapshot::placeholder(0)
+        };
+        assert_eq!(snap.gpu_util(), None);
+        // Appended, not inserted
+        let text = serde_json::to_string(&snap).unwrap();
+        assert!(text.ends_with(",\"util_unavailable\":true}"), "{}", text);
+        let json = serde_json::to_value(&snap).unwrap();
+        assert_eq!(json["gpu_util"], 0);
+        assert_eq!(json["memory_util"], 0);
+
+        // Snapshots written before the flag existed read as known
+        let mut old = json;
+        old["gpu_util"] = 42.into();
+        old.as_object_mut().unwrap().remove("util_unavailable");
+        let old: GpuSnapshot = serde_json::from_value(old).unwrap();
+        assert_eq!(old.gpu_util(), Some(42));
     }
 }
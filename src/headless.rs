@@ -0,0 +1,166 @@
+//! Headless output for `nvdash snapshot` and `nvdash watch`, for SSH
+//! sessions and scripts where there is no display for the window.
+//!
+//! JSON output uses the same [`Frame`] layout as recordings, so
+//! `nvdash watch > run.jsonl` can later be opened with `--replay`.
+
+use crate::cli::Format;
+use crate::gpu::{GpuMonitor, GpuSnapshot, ProcessInfo};
+use crate::record::Frame;
+use crate::text::{
+    ecc_summary, encoder_session, event_summary, fmt_clock, fmt_kb_s, fmt_mb, fmt_mig_vram,
+    fmt_pcie_link, fmt_pct, mig_label, nvlink_summary, process_label, throttle_summary, truncate,
+    video_summary,
+};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+pub fn snapshot(monitor: &mut GpuMonitor, format: Format) -> io::Result<()> {
+    let frame = Frame::now(monitor.snapshot_all());
+    let mut out = io::stdout().lock();
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &frame)?;
+            writeln!(out)?;
+        }
+        Format::Table => out.write_all(format_table(&frame.gpus).as_bytes())?,
+        Format::Markdown => unreachable!("rejected by the parser"),
+    }
+    out.flush()
+}
+
+/// Print one JSON frame per line until stdout closes
+pub fn watch(monitor: &mut GpuMonitor, interval: Duration) -> io::Result<()> {
+    let stdout = io::stdout();
+    loop {
+        let started = Instant::now();
+        let frame = Frame::now(monitor.snapshot_all());
+
+        let mut out = stdout.lock();
+        let written = serde_json::to_writer(&mut out, &frame)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+            .and_then(|_| out.flush());
+        match written {
+            Ok(()) => {}
+            // `nvdash watch | head` — the reader went away, not an error
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        drop(out);
+
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// One process row, indented by `indent` spaces, then its encoder sessions
+fn process_row(proc: &ProcessInfo, indent: usize) -> String {
+    let user = if proc.user.is_empty() { "-" } else { &proc.user };
+    let sm = proc
+        .utilization
+        .map(|u| format!("{}%", u.sm))
+        .unwrap_or_else(|| "-".into());
+    let mut row = format!(
+        "{:indent$}{:<8}{:<10}{:>8}  SM {:>4}  CPU {:>4.0}%  RSS {:>6}  {}\n",
+        "",
+        proc.pid,
+        truncate(user, 9),
+        fmt_mb(proc.vram_mb),
+        sm,
+        proc.cpu_pct,
+        fmt_mb(proc.rss_mb),
+        process_label(proc),
+        indent = indent
+    );
+    for session in &proc.encoder_sessions {
+        row.push_str(&format!(
+            "{:indent$}NVENC {}\n",
+            "",
+            encoder_session(session),
+            indent = indent + 8
+        ));
+    }
+    row
+}
+
+/// Fixed-width summary: one row per GPU, its processes indented beneath
+/// and, on a MIG-partitioned GPU, one row per slice with its own processes
+pub fn format_table(snapshots: &[GpuSnapshot]) -> String {
+    let mut out = format!(
+        "{:<4}{:<30}{:>5}{:>6}{:>14}{:>7}{:>11}{:>6}  {}\n",
+        "GPU", "NAME", "UTIL", "MEM", "VRAM", "TEMP", "POWER", "FAN", "GFX/MEM/SM MHz"
+    );
+    for snap in snapshots {
+        let vram = format!(
+            "{:.1}/{:.1}G",
+            snap.vram_used_mb as f64 / 1024.0,
+            snap.vram_total_mb as f64 / 1024.0
+        );
+        let power = format!("{:.0}/{:.0}W", snap.power_draw_w, snap.power_limit_w);
+        let fan = snap
+            .fan_speed
+            .map(|f| format!("{}%", f))
+            .unwrap_or_else(|| "-".into());
+        out.push_str(&format!(
+            "{:<4}{:<30}{:>5}{:>6}{:>14}{:>5}°C{:>11}{:>6}  {}/{}/{}\n",
+            snap.index,
+            truncate(&snap.name, 28),
+            fmt_pct(snap.gpu_util()),
+            fmt_pct(snap.memory_util()),
+            vram,
+            snap.temperature,
+            power,
+            fan,
+            snap.clock_graphics_mhz,
+            snap.clock_memory_mhz,
+            snap.clock_sm_mhz,
+        ));
+        if let Some(pcie) = &snap.pcie {
+            out.push_str(&format!(
+                "    PCIe {}  TX {}/s  RX {}/s{}\n",
+                fmt_pcie_link(pcie),
+                fmt_kb_s(pcie.tx_kb_s as f64),
+                fmt_kb_s(pcie.rx_kb_s as f64),
+                if pcie.degraded(snap.gpu_util()) {
+                    "  (degraded)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if let Some(nvlink) = nvlink_summary(snap) {
+            out.push_str(&format!("    {}\n", nvlink));
+        }
+        if let Some(video) = video_summary(snap) {
+            out.push_str(&format!("    video {}\n", video));
+        }
+        if let Some(throttled) = throttle_summary(snap) {
+            out.push_str(&format!("    throttled: {}\n", throttled));
+        }
+        if let Some(ecc) = ecc_summary(snap) {
+            out.push_str(&format!("    {}\n", ecc));
+        }
+        for event in &snap.events {
+            out.push_str(&format!(
+                "    ! {}  {}\n",
+                fmt_clock(event.t_ms),
+                event_summary(event)
+            ));
+        }
+        for proc in &snap.processes {
+            out.push_str(&process_row(proc, 4));
+        }
+        for mig in &snap.mig_instances {
+            out.push_str(&format!(
+                "    MIG {:<26}{:>14}  {}\n",
+                mig_label(mig),
+                fmt_mig_vram(mig),
+                mig.uuid
+            ));
+            for proc in &mig.processes {
+                out.push_str(&process_row(proc, 8));
+            }
+        }
+    }
+    out
+}
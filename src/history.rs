@@ -0,0 +1,757 @@
+//! Timestamped, multi-resolution metric history.
+//!
+//! Every metric keeps raw samples for a couple of minutes plus coarser
+//! tiers (10 s averages for an hour, 1 min min/avg/max for a day), all
+//! bounded by time rather than sample count so the window shown doesn't
+//! depend on the poll rate. Queries pick the finest tier that covers them.
+
+use crate::energy::EnergyMeter;
+use crate::gpu::{GpuEvent, GpuSnapshot, ProcessInfo, ThrottleReason, IDLE_UTIL};
+use crate::text;
+use std::collections::{HashMap, VecDeque};
+
+/// How long raw samples are kept
+const RAW_RETENTION_MS: i64 = 2 * 60 * 1000;
+/// (bucket width, retention) of each downsampled tier, finest first
+const TIERS: [(i64, i64); 2] = [
+    (10 * 1000, 60 * 60 * 1000),
+    (60 * 1000, 24 * 60 * 60 * 1000),
+];
+
+/// Longest window any tier can answer
+pub const MAX_WINDOW_MS: i64 = TIERS[TIERS.len() - 1].1;
+
+/// Most processes remembered per GPU; the oldest exited ones go first
+const MAX_PROCESSES: usize = 256;
+
+/// Most device events remembered per GPU; state changes go before errors
+const MAX_EVENTS: usize = 500;
+
+/// Time windows offered by the window selector, in ms
+pub const WINDOWS: [i64; 5] = [
+    60 * 1000,
+    10 * 60 * 1000,
+    60 * 60 * 1000,
+    6 * 60 * 60 * 1000,
+    24 * 60 * 60 * 1000,
+];
+
+/// One point of history. Raw samples have `min == avg == max`; tier
+/// buckets are stamped with the start of the bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub t_ms: i64,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl Sample {
+    fn raw(t_ms: i64, value: f64) -> Self {
+        Self {
+            t_ms,
+            min: value,
+            avg: value,
+            max: value,
+        }
+    }
+}
+
+/// Running aggregate of the bucket currently being filled
+#[derive(Clone, Debug)]
+struct Pending {
+    start_ms: i64,
+    min: f64,
+    sum: f64,
+    max: f64,
+    count: u32,
+}
+
+impl Pending {
+    fn sample(&self) -> Sample {
+        Sample {
+            t_ms: self.start_ms,
+            min: self.min,
+            avg: self.sum / self.count as f64,
+            max: self.max,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Tier {
+    bucket_ms: i64,
+    retention_ms: i64,
+    buckets: VecDeque<Sample>,
+    pending: Option<Pending>,
+}
+
+impl Tier {
+    fn new(bucket_ms: i64, retention_ms: i64) -> Self {
+        Self {
+            bucket_ms,
+            retention_ms,
+            buckets: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    fn push(&mut self, t_ms: i64, value: f64) {
+        let start_ms = t_ms - t_ms.rem_euclid(self.bucket_ms);
+        match &mut self.pending {
+            Some(p) if p.start_ms == start_ms => {
+                p.min = p.min.min(value);
+                p.max = p.max.max(value);
+                p.sum += value;
+                p.count += 1;
+            }
+            pending => {
+                if let Some(done) = pending.take() {
+                    self.buckets.push_back(done.sample());
+                }
+                *pending = Some(Pending {
+                    start_ms,
+                    min: value,
+                    sum: value,
+                    max: value,
+                    count: 1,
+                });
+            }
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|b| b.t_ms < t_ms - self.retention_ms)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn samples(&self) -> impl Iterator<Item = Sample> + '_ {
+        self.buckets
+            .iter()
+            .copied()
+            .chain(self.pending.as_ref().map(Pending::sample))
+    }
+}
+
+/// History of one metric
+#[derive(Clone, Debug)]
+pub struct Series {
+    raw: VecDeque<Sample>,
+    tiers: Vec<Tier>,
+}
+
+impl Default for Series {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Series {
+    pub fn new() -> Self {
+        Self {
+            raw: VecDeque::new(),
+            tiers: TIERS.iter().map(|&(b, r)| Tier::new(b, r)).collect(),
+        }
+    }
+
+    pub fn push(&mut self, t_ms: i64, value: f64) {
+        // Samples must arrive in order; drop anything from the past
+        if self.latest_ms().is_some_and(|last| t_ms < last) {
+            return;
+        }
+        self.raw.push_back(Sample::raw(t_ms, value));
+        while self
+            .raw
+            .front()
+            .is_some_and(|s| s.t_ms < t_ms - RAW_RETENTION_MS)
+        {
+            self.raw.pop_front();
+        }
+        for tier in &mut self.tiers {
+            tier.push(t_ms, value);
+        }
+    }
+
+    pub fn latest_ms(&self) -> Option<i64> {
+        self.raw.back().map(|s| s.t_ms)
+    }
+
+    /// Samples in `(end_ms - window_ms, end_ms]` from the finest tier that
+    /// reaches back that far
+    pub fn samples(&self, window_ms: i64, end_ms: i64) -> Vec<Sample> {
+        let start_ms = end_ms - window_ms;
+        let in_window = |s: &Sample| s.t_ms > start_ms && s.t_ms <= end_ms;
+        if window_ms <= RAW_RETENTION_MS {
+            return self.raw.iter().copied().filter(in_window).collect();
+        }
+        let tier = self
+            .tiers
+            .iter()
+            .find(|t| t.retention_ms >= window_ms)
+            .unwrap_or(&self.tiers[self.tiers.len() - 1]);
+        // Buckets are stamped with their start; keep the one straddling the edge
+        let start_ms = start_ms - tier.bucket_ms;
+        tier.samples()
+            .filter(|s| s.t_ms > start_ms && s.t_ms <= end_ms)
+            .collect()
+    }
+
+    /// Samples in `(start_ms, end_ms]` from the finest tier that still
+    /// holds `start_ms`, for windows that end in the past
+    pub fn samples_between(&self, start_ms: i64, end_ms: i64) -> Vec<Sample> {
+        let latest = self.latest_ms().unwrap_or(end_ms);
+        let reach = latest - start_ms;
+        if reach <= RAW_RETENTION_MS {
+            return self
+                .raw
+                .iter()
+                .copied()
+                .filter(|s| s.t_ms > start_ms && s.t_ms <= end_ms)
+                .collect();
+        }
+        let tier = self
+            .tiers
+            .iter()
+            .find(|t| t.retention_ms >= reach)
+            .unwrap_or(&self.tiers[self.tiers.len() - 1]);
+        let start_ms = start_ms - tier.bucket_ms;
+        tier.samples()
+            .filter(|s| s.t_ms > start_ms && s.t_ms <= end_ms)
+            .collect()
+    }
+
+    /// Average value per cell over `cells` equal slices of the window ending
+    /// at `end_ms`. Gaps after the first sample are filled forward; cells
+    /// before any data are `None`.
+    pub fn resample(&self, window_ms: i64, end_ms: i64, cells: usize) -> Vec<Option<f64>> {
+        let mut sums = vec![(0.0, 0u32); cells];
+        let start_ms = end_ms - window_ms;
+        for s in self.samples(window_ms, end_ms) {
+            let offset = (s.t_ms - start_ms).max(0) as f64 / window_ms.max(1) as f64;
+            let cell = ((offset * cells as f64) as usize).min(cells.saturating_sub(1));
+            sums[cell].0 += s.avg;
+            sums[cell].1 += 1;
+        }
+
+        let mut last = None;
+        sums.iter()
+            .map(|&(sum, n)| {
+                if n > 0 {
+                    last = Some(sum / n as f64);
+                }
+                last
+            })
+            .collect()
+    }
+
+    /// (min, avg, max) over the window
+    pub fn stats(&self, window_ms: i64, end_ms: i64) -> Option<(f64, f64, f64)> {
+        let samples = self.samples(window_ms, end_ms);
+        if samples.is_empty() {
+            return None;
+        }
+        let min = samples.iter().map(|s| s.min).fold(f64::INFINITY, f64::min);
+        let max = samples
+            .iter()
+            .map(|s| s.max)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let avg = samples.iter().map(|s| s.avg).sum::<f64>() / samples.len() as f64;
+        Some((min, avg, max))
+    }
+}
+
+/// Identifies one process run: PIDs get reused, start times tell them apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProcessKey {
+    pub pid: u32,
+    /// Unix seconds; 0 if unknown
+    pub start_time: u64,
+}
+
+/// Lifetime and memory of one process on one GPU
+#[derive(Clone, Debug)]
+pub struct ProcessRecord {
+    pub key: ProcessKey,
+    pub name: String,
+    /// First and latest poll the process was seen in (Unix ms)
+    pub first_seen_ms: i64,
+    pub last_seen_ms: i64,
+    pub peak_vram_mb: u64,
+    pub vram: Series,
+    /// Device energy attributed to the process over its lifetime (see
+    /// `ProcessHistory::push_at`)
+    pub energy_j: f64,
+    /// Missing from the latest poll
+    pub exited: bool,
+}
+
+/// Every process seen on a GPU, kept for `MAX_WINDOW_MS` after it exits
+#[derive(Clone, Debug, Default)]
+pub struct ProcessHistory {
+    records: HashMap<ProcessKey, ProcessRecord>,
+}
+
+impl ProcessHistory {
+    /// Record the processes in a snapshot, splitting the `joules` the device
+    /// used since the previous one by each process's share of SM
+    /// utilization. Without utilization figures the energy goes unattributed.
+    pub fn push_at(&mut self, t_ms: i64, snapshot: &GpuSnapshot, joules: f64) {
+        for r in self.records.values_mut() {
+            r.exited = true;
+        }
+        let sm = |p: &ProcessInfo| p.utilization.map_or(0, |u| u.sm) as f64;
+        let total_sm: f64 = snapshot.all_processes().map(|(p, _)| sm(p)).sum();
+        for (p, _) in snapshot.all_processes() {
+            let key = ProcessKey {
+                pid: p.pid,
+                start_time: p.start_time,
+            };
+            let r = self.records.entry(key).or_insert_with(|| ProcessRecord {
+                key,
+                name: text::process_label(p),
+                first_seen_ms: t_ms,
+                last_seen_ms: t_ms,
+                peak_vram_mb: 0,
+                vram: Series::new(),
+                energy_j: 0.0,
+                exited: false,
+            });
+            r.last_seen_ms = t_ms;
+            r.peak_vram_mb = r.peak_vram_mb.max(p.vram_mb);
+            r.vram.push(t_ms, p.vram_mb as f64);
+            if total_sm > 0.0 {
+                r.energy_j += joules * sm(p) / total_sm;
+            }
+            r.exited = false;
+        }
+
+        self.records
+            .retain(|_, r| !r.exited || r.last_seen_ms >= t_ms - MAX_WINDOW_MS);
+        if self.records.len() > MAX_PROCESSES {
+            let mut exited: Vec<(i64, ProcessKey)> = self
+                .records
+                .values()
+                .filter(|r| r.exited)
+                .map(|r| (r.last_seen_ms, r.key))
+                .collect();
+            exited.sort_unstable_by_key(|&(t, _)| t);
+            let excess = self.records.len() - MAX_PROCESSES;
+            for (_, key) in exited.into_iter().take(excess) {
+                self.records.remove(&key);
+            }
+        }
+    }
+
+    pub fn get(&self, key: &ProcessKey) -> Option<&ProcessRecord> {
+        self.records.get(key)
+    }
+
+    /// Every remembered process, running or exited, in no particular order
+    pub fn records(&self) -> impl Iterator<Item = &ProcessRecord> {
+        self.records.values()
+    }
+
+    /// Processes alive at any point in `(end_ms - window_ms, end_ms]`,
+    /// oldest first
+    pub fn timeline(&self, window_ms: i64, end_ms: i64) -> Vec<&ProcessRecord> {
+        let start_ms = end_ms - window_ms;
+        let mut records: Vec<&ProcessRecord> = self
+            .records
+            .values()
+            .filter(|r| r.last_seen_ms > start_ms && r.first_seen_ms <= end_ms)
+            .collect();
+        records.sort_by_key(|r| (r.first_seen_ms, r.key.pid));
+        records
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GpuHistory {
+    pub gpu_util: Series,
+    pub vram_used: Series,
+    pub temperature: Series,
+    pub power_draw: Series,
+    /// PCIe throughput, KB/s
+    pub pcie_tx: Series,
+    pub pcie_rx: Series,
+    /// Video encoder and decoder, percent busy
+    pub encoder: Series,
+    pub decoder: Series,
+    pub processes: ProcessHistory,
+    /// Device events, oldest first
+    pub events: VecDeque<GpuEvent>,
+    /// Per `ThrottleReason::ALL` entry: 1 while the reason is active, else 0
+    pub throttle: [Series; ThrottleReason::ALL.len()],
+    pub energy: EnergyMeter,
+    /// When utilization last dropped below `IDLE_UTIL`; `None` while busy
+    pub idle_since_ms: Option<i64>,
+}
+
+impl GpuHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a snapshot taken now
+    pub fn push(&mut self, snapshot: &GpuSnapshot) {
+        self.push_at(chrono::Utc::now().timestamp_millis(), snapshot);
+    }
+
+    /// Record a snapshot taken at `t_ms` (Unix ms), e.g. from a recording
+    pub fn push_at(&mut self, t_ms: i64, snapshot: &GpuSnapshot) {
+        if let Some(util) = snapshot.gpu_util() {
+            self.gpu_util.push(t_ms, util as f64);
+        }
+        self.vram_used.push(t_ms, snapshot.vram_used_mb as f64);
+        self.temperature.push(t_ms, snapshot.temperature as f64);
+        self.power_draw.push(t_ms, snapshot.power_draw_w);
+        if let Some(pcie) = snapshot.pcie {
+            self.pcie_tx.push(t_ms, pcie.tx_kb_s as f64);
+            self.pcie_rx.push(t_ms, pcie.rx_kb_s as f64);
+        }
+        if let Some(util) = snapshot.encoder_util {
+            self.encoder.push(t_ms, util as f64);
+        }
+        if let Some(util) = snapshot.decoder_util {
+            self.decoder.push(t_ms, util as f64);
+        }
+        match snapshot.gpu_util() {
+            Some(util) if util < IDLE_UTIL => {
+                self.idle_since_ms.get_or_insert(t_ms);
+            }
+            _ => self.idle_since_ms = None,
+        }
+        let joules = self.energy.push_at(t_ms, snapshot);
+        self.processes.push_at(t_ms, snapshot, joules);
+        for (series, reason) in self.throttle.iter_mut().zip(ThrottleReason::ALL) {
+            let active = snapshot.throttle_reasons.contains(&reason);
+            series.push(t_ms, if active { 1.0 } else { 0.0 });
+        }
+
+        self.events.extend(snapshot.events.iter().cloned());
+        while self.events.len() > MAX_EVENTS {
+            let evict = self
+                .events
+                .iter()
+                .position(|e| !e.kind.critical())
+                .unwrap_or(0);
+            self.events.remove(evict);
+        }
+    }
+
+    /// How long a process started at `start_time` (Unix seconds) has held
+    /// memory on the idle GPU; `None` while the GPU is busy
+    pub fn idle_for_ms(&self, start_time: u64) -> Option<i64> {
+        let since = self.idle_since_ms?.max(start_time as i64 * 1000);
+        Some((self.latest_ms() - since).max(0))
+    }
+
+    /// Share of the window (0-1) each throttle reason was active, for the
+    /// reasons that were active at some point
+    pub fn throttle_shares(&self, window_ms: i64, end_ms: i64) -> Vec<(ThrottleReason, f64)> {
+        ThrottleReason::ALL
+            .into_iter()
+            .zip(&self.throttle)
+            .filter_map(|(reason, series)| {
+                let (_, avg, max) = series.stats(window_ms, end_ms)?;
+                (max > 0.0).then_some((reason, avg))
+            })
+            .collect()
+    }
+
+    /// Whether the video engines did any work in the window; GPUs that
+    /// never encode or decode don't need a row for them
+    pub fn video_busy(&self, window_ms: i64, end_ms: i64) -> bool {
+        [&self.encoder, &self.decoder].into_iter().any(|series| {
+            series
+                .stats(window_ms, end_ms)
+                .is_some_and(|(_, _, max)| max > 0.0)
+        })
+    }
+
+    /// XID, uncorrected ECC and lost-GPU events in `(end_ms - window_ms,
+    /// end_ms]`, oldest first
+    pub fn critical_events(&self, window_ms: i64, end_ms: i64) -> Vec<&GpuEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.kind.critical() && e.t_ms > end_ms - window_ms && e.t_ms <= end_ms)
+            .collect()
+    }
+
+    /// Time of the newest sample; windows are drawn ending here so replayed
+    /// history lines up with the frame being shown
+    pub fn latest_ms(&self) -> i64 {
+        // Every snapshot has VRAM; not every one has utilization
+        self.vram_used
+            .latest_ms()
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: i64 = 60 * 1000;
+    const HOUR: i64 = 60 * MIN;
+
+    /// A series fed `value(t)` every `step_ms` from 0 through `end_ms`
+    fn series(end_ms: i64, step_ms: i64, value: impl Fn(i64) -> f64) -> Series {
+        let mut series = Series::new();
+        for t in (0..=end_ms).step_by(step_ms as usize) {
+            series.push(t, value(t));
+        }
+        series
+    }
+
+    fn sample(t_ms: i64, min: f64, avg: f64, max: f64) -> Sample {
+        Sample {
+            t_ms,
+            min,
+            avg,
+            max,
+        }
+    }
+
+    fn times(samples: &[Sample]) -> Vec<i64> {
+        samples.iter().map(|s| s.t_ms).collect()
+    }
+
+    #[test]
+    fn buckets_hold_min_avg_max() {
+        let mut s = series(19_000, 1000, |t| (t / 1000) as f64);
+        // 10 s buckets, the second still filling
+        assert_eq!(
+            s.samples(10 * MIN, 19_000),
+            [sample(0, 0.0, 4.5, 9.0), sample(10_000, 10.0, 14.5, 19.0)]
+        );
+        // One 1 min bucket
+        assert_eq!(s.samples(2 * HOUR, 19_000), [sample(0, 0.0, 9.5, 19.0)]);
+
+        // The next bucket closes the pending one; empty buckets are skipped
+        s.push(35_000, 100.0);
+        assert_eq!(
+            s.samples(10 * MIN, 35_000),
+            [
+                sample(0, 0.0, 4.5, 9.0),
+                sample(10_000, 10.0, 14.5, 19.0),
+                sample(30_000, 100.0, 100.0, 100.0)
+            ]
+        );
+        assert_eq!(
+            s.samples(2 * HOUR, 35_000),
+            [sample(0, 0.0, 290.0 / 21.0, 100.0)]
+        );
+    }
+
+    #[test]
+    fn each_tier_keeps_its_retention() {
+        let end = 25 * HOUR;
+        let s = series(end, 10_000, |t| t as f64);
+        // Anything exactly at the boundary is still kept
+        assert_eq!(s.raw.front().unwrap().t_ms, end - RAW_RETENTION_MS);
+        assert_eq!(s.raw.len(), 13);
+        assert_eq!(s.tiers[0].buckets.front().unwrap().t_ms, end - HOUR);
+        assert_eq!(s.tiers[1].buckets.front().unwrap().t_ms, end - 24 * HOUR);
+
+        // One step later the oldest of each goes
+        let mut s = s;
+        s.push(end + 10_000, 0.0);
+        assert_eq!(s.raw.front().unwrap().t_ms, end + 10_000 - RAW_RETENTION_MS);
+        assert_eq!(
+            s.tiers[0].buckets.front().unwrap().t_ms,
+            end + 10_000 - HOUR
+        );
+    }
+
+    #[test]
+    fn queries_pick_the_finest_tier_that_covers_them() {
+        let end = 25 * HOUR;
+        let s = series(end, 10_000, |t| t as f64);
+
+        // Raw samples, one per poll
+        let raw = s.samples(MIN, end);
+        assert_eq!(
+            times(&raw),
+            (0..6)
+                .map(|i| end - 50_000 + i * 10_000)
+                .collect::<Vec<_>>()
+        );
+
+        // 10 s buckets for up to an hour, 1 min buckets beyond
+        let tier = |samples: &[Sample]| samples[1].t_ms - samples[0].t_ms;
+        assert_eq!(tier(&s.samples(10 * MIN, end)), 10_000);
+        assert_eq!(tier(&s.samples(HOUR, end)), 10_000);
+        assert_eq!(tier(&s.samples(6 * HOUR, end)), MIN);
+        // Past the longest tier, the longest tier is all there is
+        assert_eq!(s.samples(48 * HOUR, end)[0].t_ms, end - 24 * HOUR);
+
+        // The bucket straddling the window's start is kept
+        let off = end - 5_000;
+        let buckets = s.samples(10 * MIN, off);
+        assert_eq!(buckets[0].t_ms, off - 10 * MIN - 5_000);
+        assert_eq!(buckets.last().unwrap().t_ms, end - 10_000);
+
+        // Past windows go by how far back they start
+        let between = s.samples_between(end - 90_000, end - 30_000);
+        assert_eq!(
+            times(&between),
+            (0..6)
+                .map(|i| end - 80_000 + i * 10_000)
+                .collect::<Vec<_>>()
+        );
+        assert!(between.iter().all(|b| b.min == b.max));
+        let between = s.samples_between(end - 30 * MIN, end - 20 * MIN);
+        assert_eq!(tier(&between), 10_000);
+        let between = s.samples_between(end - 5 * HOUR - 30_000, end - 4 * HOUR);
+        assert_eq!(tier(&between), MIN);
+        assert_eq!(between[0].t_ms, end - 5 * HOUR - MIN);
+        assert_eq!(between.last().unwrap().t_ms, end - 4 * HOUR);
+    }
+
+    #[test]
+    fn resample_fills_forward_from_the_first_sample() {
+        let mut s = Series::new();
+        s.push(10_000, 1.0);
+        s.push(30_000, 3.0);
+        s.push(35_000, 5.0);
+        assert_eq!(
+            s.resample(MIN, MIN, 6),
+            [None, Some(1.0), Some(1.0), Some(4.0), Some(4.0), Some(4.0)]
+        );
+        assert_eq!(Series::new().resample(MIN, MIN, 3), [None, None, None]);
+    }
+
+    #[test]
+    fn samples_from_the_past_are_dropped() {
+        let mut s = Series::new();
+        s.push(10_000, 1.0);
+        s.push(5_000, 2.0);
+        s.push(10_000, 3.0);
+        assert_eq!(s.latest_ms(), Some(10_000));
+        assert_eq!(
+            s.samples(MIN, 10_000),
+            [sample(10_000, 1.0, 1.0, 1.0), sample(10_000, 3.0, 3.0, 3.0)]
+        );
+        assert_eq!(s.samples(10 * MIN, 10_000), [sample(10_000, 1.0, 2.0, 3.0)]);
+    }
+
+    /// A process `pid` started at `start_time` holding `vram_mb`
+    fn proc(pid: u32, start_time: u64, vram_mb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: format!("job{}", pid),
+            vram_mb,
+            start_time,
+            ..Default::default()
+        }
+    }
+
+    fn running(procs: Vec<ProcessInfo>) -> GpuSnapshot {
+        GpuSnapshot {
+            processes: procs,
+            ..GpuSnapshot::placeholder(0)
+        }
+    }
+
+    fn key(pid: u32, start_time: u64) -> ProcessKey {
+        ProcessKey { pid, start_time }
+    }
+
+    #[test]
+    fn a_reused_pid_is_a_new_process() {
+        let mut h = ProcessHistory::default();
+        h.push_at(0, &running(vec![proc(7, 100, 512)]), 0.0);
+        h.push_at(1000, &running(vec![proc(7, 100, 2048)]), 0.0);
+        h.push_at(2000, &running(vec![proc(7, 200, 256)]), 0.0);
+
+        let first = h.get(&key(7, 100)).unwrap();
+        assert!(first.exited);
+        assert_eq!((first.first_seen_ms, first.last_seen_ms), (0, 1000));
+        assert_eq!(first.peak_vram_mb, 2048);
+        assert_eq!(first.name, "job7");
+
+        let second = h.get(&key(7, 200)).unwrap();
+        assert!(!second.exited);
+        assert_eq!((second.first_seen_ms, second.peak_vram_mb), (2000, 256));
+        assert_eq!(h.records().count(), 2);
+    }
+
+    #[test]
+    fn processes_exit_when_missing_and_return_when_seen() {
+        let mut h = ProcessHistory::default();
+        h.push_at(0, &running(vec![proc(1, 0, 100), proc(2, 0, 100)]), 0.0);
+        h.push_at(1000, &running(vec![proc(2, 0, 100)]), 0.0);
+        assert!(h.get(&key(1, 0)).unwrap().exited);
+        assert!(!h.get(&key(2, 0)).unwrap().exited);
+
+        // A missed poll doesn't make it a different process
+        h.push_at(2000, &running(vec![proc(1, 0, 100)]), 0.0);
+        let back = h.get(&key(1, 0)).unwrap();
+        assert!(!back.exited);
+        assert_eq!((back.first_seen_ms, back.last_seen_ms), (0, 2000));
+    }
+
+    #[test]
+    fn exited_processes_are_forgotten_after_the_longest_window() {
+        let mut h = ProcessHistory::default();
+        h.push_at(0, &running(vec![proc(1, 0, 100), proc(2, 0, 100)]), 0.0);
+        h.push_at(1000, &running(vec![proc(2, 0, 100)]), 0.0);
+
+        h.push_at(MAX_WINDOW_MS, &running(vec![proc(2, 0, 100)]), 0.0);
+        assert!(h.get(&key(1, 0)).is_some());
+        h.push_at(MAX_WINDOW_MS + 1, &running(vec![proc(2, 0, 100)]), 0.0);
+        assert!(h.get(&key(1, 0)).is_none());
+        // Running ones stay however long ago they started
+        assert_eq!(h.get(&key(2, 0)).unwrap().first_seen_ms, 0);
+    }
+
+    #[test]
+    fn only_the_oldest_exited_processes_are_evicted() {
+        let mut h = ProcessHistory::default();
+        // A long job alongside a new short one each poll
+        for pid in 1..=300 {
+            let t = pid as i64 * 1000;
+            h.push_at(t, &running(vec![proc(0, 0, 100), proc(pid, 0, 100)]), 0.0);
+        }
+        assert_eq!(h.records().count(), MAX_PROCESSES);
+        assert!(!h.get(&key(0, 0)).unwrap().exited);
+        assert!(!h.get(&key(300, 0)).unwrap().exited);
+        let oldest = h.records().filter(|r| r.exited).map(|r| r.key.pid).min();
+        assert_eq!(oldest, Some(300 - (MAX_PROCESSES as u32 - 2)));
+
+        // Running processes are never evicted, even past the limit
+        let crowd: Vec<ProcessInfo> = (1000..1300).map(|pid| proc(pid, 0, 1)).collect();
+        h.push_at(301_000, &running(crowd), 0.0);
+        assert_eq!(h.records().filter(|r| !r.exited).count(), 300);
+        assert_eq!(h.records().count(), 300);
+    }
+
+    #[test]
+    fn timeline_lists_what_ran_in_the_window_oldest_first() {
+        let mut h = ProcessHistory::default();
+        let polls = [
+            (0, vec![3]),
+            (10_000, vec![3]),
+            (20_000, vec![5, 4]),
+            (30_000, vec![5, 4]),
+            (40_000, vec![6]),
+        ];
+        for (t, pids) in polls {
+            let procs = pids.into_iter().map(|pid| proc(pid, 0, 100)).collect();
+            h.push_at(t, &running(procs), 0.0);
+        }
+        let pids = |window_ms, end_ms| -> Vec<u32> {
+            h.timeline(window_ms, end_ms)
+                .iter()
+                .map(|r| r.key.pid)
+                .collect()
+        };
+        assert_eq!(pids(15_000, 35_000), [4, 5]);
+        assert_eq!(pids(MIN, 40_000), [3, 4, 5, 6]);
+        // Last seen exactly at the window's start is outside it
+        assert_eq!(pids(10_000, 20_000), [4, 5]);
+    }
+}
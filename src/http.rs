@@ -0,0 +1,204 @@
+//! Just enough HTTP/1.1 to answer GET requests from scrapers and curl, and
+//! to make them to another nvdash. Each connection gets its own thread, so a
+//! stalled client holds up nobody else; `Connection: close` — no
+//! keep-alive, no request bodies.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Request heads larger than this are rejected
+const MAX_HEAD: usize = 8 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections served at once; more are closed straight away
+const MAX_CONNECTIONS: usize = 32;
+
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// What follows `?`, undecoded; empty if none
+    pub query: String,
+}
+
+impl Request {
+    /// Value of `name` in the query string
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self {
+            status: 404,
+            content_type: "text/plain; charset=utf-8",
+            body: "not found\n".into(),
+        }
+    }
+
+    /// Nothing to serve yet
+    pub fn unavailable() -> Self {
+        Self {
+            status: 503,
+            content_type: "text/plain; charset=utf-8",
+            body: "not ready\n".into(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            503 => "Service Unavailable",
+            _ => "Error",
+        }
+    }
+}
+
+/// Accept connections forever, answering each with `handler` on a thread
+/// of its own
+pub fn serve(
+    listener: TcpListener,
+    handler: impl Fn(&Request) -> Response + Sync,
+) -> io::Result<()> {
+    let open = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Warning: accept failed: {}", e);
+                    continue;
+                }
+            };
+            if open.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::Relaxed);
+                eprintln!("Warning: too many HTTP connections, dropping one");
+                continue;
+            }
+            let (open, handler) = (&open, &handler);
+            scope.spawn(move || {
+                if let Err(e) = handle(stream, handler) {
+                    eprintln!("Warning: HTTP connection error: {}", e);
+                }
+                open.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+        Ok(())
+    })
+}
+
+fn handle(mut stream: TcpStream, handler: &impl Fn(&Request) -> Response) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let request = read_request(&mut stream)?;
+    let head_only = request.as_ref().is_some_and(|r| r.method == "HEAD");
+    let response = match request {
+        Some(req) if req.method == "GET" || req.method == "HEAD" => handler(&req),
+        Some(_) => Response {
+            status: 405,
+            content_type: "text/plain; charset=utf-8",
+            body: "method not allowed\n".into(),
+        },
+        None => Response {
+            status: 400,
+            content_type: "text/plain; charset=utf-8",
+            body: "bad request\n".into(),
+        },
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    )?;
+    if !head_only {
+        stream.write_all(response.body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+/// Read the request head; `None` if it is malformed or too large
+fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_HEAD {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.lines().next().unwrap_or("").split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+    }))
+}
+
+/// GET `path` from `addr` (`host:port`) and return the body of a 200
+/// response
+pub fn get(addr: &str, path: &str) -> io::Result<String> {
+    let socket = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address has no IPs"))?;
+    let mut stream = TcpStream::connect_timeout(&socket, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, addr
+    )?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated response"))?;
+    let status = head.split_whitespace().nth(1).unwrap_or("");
+    if status != "200" {
+        let line = head.lines().next().unwrap_or("");
+        return Err(io::Error::other(format!("{}: {}", path, line)));
+    }
+    Ok(body.to_string())
+}
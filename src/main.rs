@@ -1,20 +1,57 @@
 //! nvdash — A lightweight, native NVIDIA GPU monitor for ML workloads.
 //!
 //! Built with egui + nvml-wrapper. No web views, no Electron.
-//! Polls NVML at a fixed 500ms interval and renders a compact
+//! Polls NVML (every 500ms by default) and renders a compact
 //! GPU peek widget with metric bars, sparklines, and process summary.
 //! Lives in the system tray; click to toggle, right-click to quit.
+//! Run with `--sim[=N]` to feed it simulated GPUs instead of NVML,
+//! `--record` to save every poll and `--replay FILE` to play one back.
+//! Settings and window geometry persist in `config.toml` (see `settings`).
 
 #![cfg_attr(
     all(target_os = "windows", not(debug_assertions)),
     windows_subsystem = "windows"
 )]
 
+mod agent;
+mod alerts;
+mod chart;
+mod cli;
+mod energy;
+mod events;
+mod forecast;
 mod gpu;
+mod headless;
+mod history;
+mod http;
+mod mig;
+mod nvlink;
+mod nvml;
+mod process;
+mod process_table;
+mod prometheus;
+mod record;
+mod remote;
+mod session;
+mod settings;
+mod sim;
+mod text;
+mod tui;
 mod ui;
 
+use alerts::{AlertEngine, Transition};
+use chart::ChartView;
 use eframe::egui;
-use gpu::{GpuHistory, GpuMonitor, GpuSnapshot};
+use gpu::{GpuMonitor, GpuRef, GpuSnapshot};
+use history::GpuHistory;
+use process_table::ProcessTable;
+use record::{Player, Recorder};
+use remote::Remote;
+use session::{Session, SessionReport};
+use settings::{Layout, Settings, SettingsFile, WindowGeometry};
+use sim::SimConfig;
+use std::path::PathBuf;
+#[cfg(windows)]
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -26,6 +63,9 @@ use winapi::shared::windef::HWND;
 struct Shared {
     hwnd: HWND,
     visible: bool,
+    tray: &'static tray_icon::TrayIcon,
+    /// "Start session" / "Stop session" tray menu entry
+    session_item: tray_icon::menu::MenuItem,
 }
 
 #[cfg(windows)]
@@ -33,147 +73,203 @@ unsafe impl Send for Shared {}
 #[cfg(windows)]
 unsafe impl Sync for Shared {}
 
+/// Where snapshots come from
+enum Source {
+    Live(GpuMonitor),
+    Replay(Player),
+}
+
+/// The settings file (if any), the settings read from it and any error
+/// reading them
+type Config = (Option<SettingsFile>, Settings, Option<String>);
+
+/// How long a failed process action stays on screen
+const ACTION_ERROR_TTL: Duration = Duration::from_secs(5);
+
 /// Application state
 struct NvDash {
-    monitor: GpuMonitor,
+    source: Source,
+    /// Agents whose GPUs follow the local ones in `snapshots`
+    remotes: Vec<Remote>,
+    recorder: Option<Recorder>,
     snapshots: Vec<GpuSnapshot>,
     histories: Vec<GpuHistory>,
     last_poll: Instant,
-    poll_interval: Duration,
-    poll_ms: u64,
-    always_on_top: bool,
-    decorations: bool,
-    opacity_pct: u8,
+    settings: Settings,
+    /// What the config file holds, to tell when `settings` needs saving
+    saved: Settings,
+    /// `None` when the platform has no config directory
+    config: Option<SettingsFile>,
+    /// Last problem reading or writing the config; cleared by a good reload
+    config_error: Option<String>,
+    /// GPU shown in the tabs layout
+    selected_tab: usize,
+    /// Process timeline window open
+    show_timeline: bool,
+    /// Event log window open
+    show_events: bool,
+    /// NVLink window open
+    show_nvlink: bool,
+    /// Energy window open
+    show_energy: bool,
+    /// Session being tracked, if any
+    session: Option<Session>,
+    /// Name the next session starts with
+    session_name: String,
+    /// Summary of the last session stopped; its window is open while set
+    session_report: Option<SessionReport>,
+    /// Where the report was last saved
+    session_saved: Option<PathBuf>,
+    process_table: ProcessTable,
+    charts: ChartView,
+    /// Signal waiting for the user to confirm it
+    pending_action: Option<ui::ProcessRequest>,
+    /// Why the last signal failed, and when
+    action_error: Option<(String, Instant)>,
+    alerts: AlertEngine,
     error_msg: Option<String>,
+    /// Persistent note about the data source (e.g. simulated data)
+    notice: Option<String>,
     #[cfg(windows)]
     shared: Arc<Mutex<Shared>>,
     #[cfg(windows)]
     hwnd_captured: bool,
+    /// Alert tooltip the tray icon is showing; `None` in the normal state
+    #[cfg(windows)]
+    tray_alerts: Option<String>,
 }
 
 impl NvDash {
     #[cfg(windows)]
-    fn new(_cc: &eframe::CreationContext<'_>, shared: Arc<Mutex<Shared>>) -> Self {
-        let monitor =
-            GpuMonitor::init().expect("Failed to initialize NVML. Is an NVIDIA GPU present?");
-        let count = monitor.device_count() as usize;
+    fn new(
+        _cc: &eframe::CreationContext<'_>,
+        source: Source,
+        remotes: Vec<Remote>,
+        recorder: Option<Recorder>,
+        notice: Option<String>,
+        config: Config,
+        shared: Arc<Mutex<Shared>>,
+    ) -> Self {
+        Self::with_source(source, remotes, recorder, notice, config, shared)
+    }
 
-        let mut snapshots = Vec::with_capacity(count);
-        let mut histories = Vec::with_capacity(count);
+    #[cfg(not(windows))]
+    fn new(
+        _cc: &eframe::CreationContext<'_>,
+        source: Source,
+        remotes: Vec<Remote>,
+        recorder: Option<Recorder>,
+        notice: Option<String>,
+        config: Config,
+    ) -> Self {
+        Self::with_source(source, remotes, recorder, notice, config)
+    }
 
-        for i in 0..count as u32 {
-            match monitor.snapshot(i) {
-                Ok(snap) => {
-                    let mut h = GpuHistory::new();
-                    h.push(&snap);
-                    histories.push(h);
-                    snapshots.push(snap);
-                }
-                Err(e) => {
-                    eprintln!("Warning: failed to read GPU {}: {}", i, e);
-                    histories.push(GpuHistory::new());
-                    snapshots.push(GpuSnapshot {
-                        name: format!("GPU {} (error)", i),
-                        index: i,
-                        driver_version: String::new(),
-                        cuda_version: String::new(),
-                        gpu_util: 0,
-                        memory_util: 0,
-                        vram_used_mb: 0,
-                        vram_total_mb: 0,
-                        temperature: 0,
-                        fan_speed: None,
-                        power_draw_w: 0.0,
-                        power_limit_w: 0.0,
-                        clock_graphics_mhz: 0,
-                        clock_memory_mhz: 0,
-                        clock_sm_mhz: 0,
-                        processes: vec![],
-                    });
+    fn with_source(
+        mut source: Source,
+        remotes: Vec<Remote>,
+        recorder: Option<Recorder>,
+        notice: Option<String>,
+        (config, settings, config_error): Config,
+        #[cfg(windows)] shared: Arc<Mutex<Shared>>,
+    ) -> Self {
+        let (snapshots, histories) = match &mut source {
+            Source::Live(monitor) => {
+                let count = monitor.device_count() as usize;
+
+                let mut snapshots = Vec::with_capacity(count);
+                let mut histories = Vec::with_capacity(count);
+
+                for i in 0..count as u32 {
+                    match monitor.snapshot(i) {
+                        Ok(snap) => {
+                            let mut h = GpuHistory::new();
+                            h.push(&snap);
+                            histories.push(h);
+                            snapshots.push(snap);
+                        }
+                        Err(e) => {
+                            eprintln!("Warning: failed to read GPU {}: {}", i, e);
+                            histories.push(GpuHistory::new());
+                            snapshots.push(GpuSnapshot::placeholder(i));
+                        }
+                    }
                 }
+                (snapshots, histories)
             }
-        }
+            Source::Replay(player) => (player.snapshots(), player.histories()),
+        };
 
         Self {
-            monitor,
+            source,
+            remotes,
+            recorder,
             snapshots,
             histories,
             last_poll: Instant::now(),
-            poll_interval: Duration::from_millis(500),
-            poll_ms: 500,
-            always_on_top: false,
-            decorations: true,
-            opacity_pct: 100,
+            alerts: AlertEngine::new(settings.alerts.clone()),
+            saved: settings.clone(),
+            settings,
+            config,
+            config_error,
+            selected_tab: 0,
+            show_timeline: false,
+            show_events: false,
+            show_nvlink: false,
+            show_energy: false,
+            session: None,
+            session_name: String::new(),
+            session_report: None,
+            session_saved: None,
+            process_table: ProcessTable::default(),
+            charts: ChartView::default(),
+            pending_action: None,
+            action_error: None,
             error_msg: None,
+            notice,
+            #[cfg(windows)]
             shared,
+            #[cfg(windows)]
             hwnd_captured: false,
+            #[cfg(windows)]
+            tray_alerts: None,
         }
     }
 
-    #[cfg(not(windows))]
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let monitor =
-            GpuMonitor::init().expect("Failed to initialize NVML. Is an NVIDIA GPU present?");
-        let count = monitor.device_count() as usize;
-
-        let mut snapshots = Vec::with_capacity(count);
-        let mut histories = Vec::with_capacity(count);
+    fn poll(&mut self) {
+        self.alerts.set_rules(&self.settings.alerts);
 
-        for i in 0..count as u32 {
-            match monitor.snapshot(i) {
-                Ok(snap) => {
-                    let mut h = GpuHistory::new();
-                    h.push(&snap);
-                    histories.push(h);
-                    snapshots.push(snap);
+        if let Source::Replay(player) = &mut self.source {
+            let devices = player.device_count();
+            let passed = player.tick();
+            for frame in passed {
+                for snap in &frame.gpus {
+                    if let Some(h) = self.histories.get_mut(snap.index as usize) {
+                        h.push_at(frame.timestamp_ms, snap);
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Warning: failed to read GPU {}: {}", i, e);
-                    histories.push(GpuHistory::new());
-                    snapshots.push(GpuSnapshot {
-                        name: format!("GPU {} (error)", i),
-                        index: i,
-                        driver_version: String::new(),
-                        cuda_version: String::new(),
-                        gpu_util: 0,
-                        memory_util: 0,
-                        vram_used_mb: 0,
-                        vram_total_mb: 0,
-                        temperature: 0,
-                        fan_speed: None,
-                        power_draw_w: 0.0,
-                        power_limit_w: 0.0,
-                        clock_graphics_mhz: 0,
-                        clock_memory_mhz: 0,
-                        clock_sm_mhz: 0,
-                        processes: vec![],
-                    });
+                let transitions = self.alerts.update(frame.timestamp_ms, &frame.gpus);
+                log_alerts(&self.alerts, &transitions);
+                if let Some(session) = &mut self.session {
+                    session.push_at(frame.timestamp_ms, &frame.gpus);
                 }
             }
+            if let Some(frame) = passed.last() {
+                self.snapshots = frame.gpus_by_index(devices);
+            }
+            return;
         }
 
-        Self {
-            monitor,
-            snapshots,
-            histories,
-            last_poll: Instant::now(),
-            poll_interval: Duration::from_millis(500),
-            poll_ms: 500,
-            always_on_top: false,
-            decorations: true,
-            opacity_pct: 100,
-            error_msg: None,
-        }
-    }
-
-    fn poll(&mut self) {
-        if self.last_poll.elapsed() < self.poll_interval {
+        if self.last_poll.elapsed() < self.poll_interval() {
             return;
         }
         self.last_poll = Instant::now();
 
-        for i in 0..self.monitor.device_count() {
-            match self.monitor.snapshot(i) {
+        let Source::Live(monitor) = &mut self.source else {
+            return;
+        };
+        for i in 0..monitor.device_count() {
+            match monitor.snapshot(i) {
                 Ok(snap) => {
                     let idx = i as usize;
                     if idx < self.histories.len() {
@@ -189,6 +285,656 @@ impl NvDash {
                 }
             }
         }
+
+        self.poll_remotes();
+
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let transitions = self.alerts.update(now_ms, &self.snapshots);
+        log_alerts(&self.alerts, &transitions);
+        if let Some(session) = &mut self.session {
+            session.push_at(now_ms, &self.snapshots);
+        }
+
+        if let Some(rec) = &mut self.recorder {
+            if let Err(e) = rec.record(&self.snapshots) {
+                self.error_msg = Some(format!("Recording stopped: {}", e));
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Take in what the agents sent since the last poll
+    fn poll_remotes(&mut self) {
+        let Source::Live(monitor) = &self.source else {
+            return;
+        };
+        let local = monitor.device_count() as usize;
+        let error = remote::merge(
+            &mut self.remotes,
+            local,
+            &mut self.snapshots,
+            &mut self.histories,
+        );
+        if error.is_some() {
+            self.error_msg = error;
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.settings.poll_ms)
+    }
+
+    /// Remember where the window is so the next launch puts it back
+    fn track_geometry(&mut self, ctx: &egui::Context) {
+        let (outer, inner) = ctx.input(|i| (i.viewport().outer_rect, i.viewport().inner_rect));
+        if let (Some(outer), Some(inner)) = (outer, inner) {
+            self.settings.window = Some(WindowGeometry {
+                x: outer.min.x.round(),
+                y: outer.min.y.round(),
+                width: inner.width().round(),
+                height: inner.height().round(),
+            });
+        }
+    }
+
+    /// Save changed settings and pick up external edits to the config file
+    fn sync_settings(&mut self, ctx: &egui::Context) {
+        let Some(config) = &mut self.config else {
+            return;
+        };
+        match config.sync(&self.settings, &mut self.saved) {
+            Some(Ok(reloaded)) => {
+                self.config_error = None;
+                self.apply_settings(ctx, reloaded);
+            }
+            Some(Err(e)) => self.config_error = Some(e),
+            None => {}
+        }
+    }
+
+    /// Switch to `new`, pushing whatever changed out to the window
+    fn apply_settings(&mut self, ctx: &egui::Context, new: Settings) {
+        let old = std::mem::replace(&mut self.settings, new);
+        let new = &self.settings;
+        if new.always_on_top != old.always_on_top {
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(window_level(
+                new.always_on_top,
+            )));
+        }
+        if new.decorations != old.decorations {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(new.decorations));
+        }
+        if new.opacity_pct != old.opacity_pct {
+            set_window_opacity(new.opacity_pct);
+        }
+        if let Some(g) = new.window.filter(|&g| Some(g) != old.window) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(g.x, g.y)));
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
+                g.width, g.height,
+            )));
+        }
+    }
+
+    /// Show or hide the GPU listed at `index`, keeping "every GPU" as an
+    /// empty list
+    fn toggle_gpu(&mut self, index: usize) {
+        let snapshots = &self.snapshots;
+        let gpus = &mut self.settings.gpus;
+        if gpus.is_empty() {
+            *gpus = snapshots.iter().map(GpuRef::of).collect();
+        }
+        let toggled = &snapshots[index];
+        let before = gpus.len();
+        gpus.retain(|g| !g.matches(toggled));
+        if gpus.len() == before {
+            gpus.push(GpuRef::of(toggled));
+        }
+        if snapshots.iter().all(|s| gpus.iter().any(|g| g.matches(s))) {
+            gpus.clear();
+        }
+    }
+
+    /// Layout and GPU selection
+    fn draw_view_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(
+            egui::RichText::new("view")
+                .size(10.0)
+                .color(ui::TEXT_SECONDARY),
+            |ui| {
+                ui.radio_value(&mut self.settings.layout, Layout::Stacked, "Stacked");
+                ui.radio_value(&mut self.settings.layout, Layout::Tabs, "Tabs");
+                ui.separator();
+                let mut toggled = None;
+                for (i, snap) in self.snapshots.iter().enumerate() {
+                    let mut shown = self.settings.shows_gpu(snap);
+                    if ui
+                        .checkbox(&mut shown, format!("GPU {} · {}", i, snap.name))
+                        .changed()
+                    {
+                        toggled = Some(i);
+                    }
+                }
+                if let Some(i) = toggled {
+                    self.toggle_gpu(i);
+                }
+                ui.separator();
+                ui.checkbox(&mut self.process_table.open, "Process table");
+                ui.checkbox(&mut self.charts.open, "Charts");
+                ui.checkbox(&mut self.show_timeline, "Process timeline");
+                ui.checkbox(&mut self.show_events, "Event log");
+                ui.checkbox(&mut self.show_energy, "Energy");
+                if self.snapshots.iter().any(|s| !s.nvlinks.is_empty()) {
+                    ui.checkbox(&mut self.show_nvlink, "NVLink");
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Forecast within");
+                    ui.add(
+                        egui::DragValue::new(&mut self.settings.forecast_horizon_min)
+                            .range(1..=24 * 60)
+                            .suffix(" min"),
+                    )
+                    .on_hover_text(
+                        "Show when VRAM or temperature will hit its limit, if that's sooner",
+                    );
+                });
+            },
+        );
+    }
+
+    /// Turn the tray icon red while any alert is firing, listing them in the
+    /// tooltip
+    #[cfg(windows)]
+    fn update_tray(&mut self) {
+        let firing = self.alerts.firing();
+        let tooltip = (!firing.is_empty()).then(|| {
+            let mut tooltip = String::from("nvdash");
+            for alert in &firing {
+                tooltip.push_str(&format!("\nGPU {} {}", alert.gpu, self.alerts.describe(alert)));
+            }
+            tooltip
+        });
+        if tooltip == self.tray_alerts {
+            return;
+        }
+        let Ok(s) = self.shared.lock() else {
+            return;
+        };
+        if self.tray_alerts.is_none() || tooltip.is_none() {
+            let color = if tooltip.is_some() { TRAY_RED } else { TRAY_GREEN };
+            let _ = s.tray.set_icon(Some(tray_icon_image(color)));
+        }
+        let _ = s.tray.set_tooltip(Some(tooltip.as_deref().unwrap_or("nvdash")));
+        self.tray_alerts = tooltip;
+    }
+
+    /// Separate window listing when each process started and exited
+    fn draw_timeline_window(&mut self, ctx: &egui::Context) {
+        let snapshots = &self.snapshots;
+        let histories = &self.histories;
+        let settings = &self.settings;
+        secondary_window(
+            ctx,
+            "process_timeline",
+            "nvdash — process timeline",
+            [520.0, 300.0],
+            &mut self.show_timeline,
+            |panel_ui| {
+                egui::ScrollArea::vertical().show(panel_ui, |ui| {
+                    for (snapshot, history) in snapshots.iter().zip(histories) {
+                        if !settings.shows_gpu(snapshot) {
+                            continue;
+                        }
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "GPU {} · {} · last {}",
+                                snapshot.index,
+                                snapshot.name,
+                                text::fmt_window(settings.window_ms)
+                            ))
+                            .size(10.0)
+                            .color(ui::TEXT_SECONDARY),
+                        );
+                        ui::draw_process_timeline(ui, history, settings.window_ms);
+                        ui.separator();
+                    }
+                });
+            },
+        );
+    }
+
+    /// Separate window with each GPU's ECC counters and device events
+    fn draw_events_window(&mut self, ctx: &egui::Context) {
+        let snapshots = &self.snapshots;
+        let histories = &self.histories;
+        let settings = &self.settings;
+        secondary_window(
+            ctx,
+            "event_log",
+            "nvdash — event log",
+            [460.0, 300.0],
+            &mut self.show_events,
+            |panel_ui| {
+                egui::ScrollArea::vertical().show(panel_ui, |ui| {
+                    for (snapshot, history) in snapshots.iter().zip(histories) {
+                        if !settings.shows_gpu(snapshot) {
+                            continue;
+                        }
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "GPU {} · {}",
+                                snapshot.index, snapshot.name
+                            ))
+                            .size(10.0)
+                            .color(ui::TEXT_SECONDARY),
+                        );
+                        ui::draw_event_log(ui, snapshot, history);
+                        ui.separator();
+                    }
+                });
+            },
+        );
+    }
+
+    /// Separate window with a panel per GPU listing its NVLinks
+    fn draw_nvlink_window(&mut self, ctx: &egui::Context) {
+        let snapshots = &self.snapshots;
+        let settings = &self.settings;
+        secondary_window(
+            ctx,
+            "nvlink",
+            "nvdash — NVLink",
+            [560.0, 320.0],
+            &mut self.show_nvlink,
+            |panel_ui| {
+                egui::ScrollArea::vertical().show(panel_ui, |ui| {
+                    for snapshot in snapshots {
+                        if !settings.shows_gpu(snapshot) {
+                            continue;
+                        }
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "GPU {} · {}",
+                                snapshot.index, snapshot.name
+                            ))
+                            .size(10.0)
+                            .color(ui::TEXT_SECONDARY),
+                        );
+                        ui::draw_nvlinks(ui, snapshot);
+                        ui.separator();
+                    }
+                });
+            },
+        );
+    }
+
+    /// Separate window with each GPU's energy use, cost and emissions, the
+    /// rates behind them and a button to reset the meters
+    fn draw_energy_window(&mut self, ctx: &egui::Context) {
+        let snapshots = &self.snapshots;
+        let histories = &mut self.histories;
+        let settings = &mut self.settings;
+        secondary_window(
+            ctx,
+            "energy",
+            "nvdash — energy",
+            [520.0, 340.0],
+            &mut self.show_energy,
+            |panel_ui| {
+                let rates = &mut settings.energy;
+                panel_ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(" price").size(10.0).color(ui::TEXT_DIM));
+                    ui.add(
+                        egui::DragValue::new(&mut rates.price_per_kwh)
+                            .range(0.0..=10.0)
+                            .speed(0.01)
+                            .prefix(rates.currency.clone())
+                            .suffix("/kWh"),
+                    );
+                    ui.label(egui::RichText::new("carbon").size(10.0).color(ui::TEXT_DIM));
+                    ui.add(
+                        egui::DragValue::new(&mut rates.co2_g_per_kwh)
+                            .range(0.0..=2000.0)
+                            .speed(5.0)
+                            .suffix(" g/kWh"),
+                    );
+                    if ui
+                        .button("Reset")
+                        .on_hover_text("Start counting energy again from now")
+                        .clicked()
+                    {
+                        for history in histories.iter_mut() {
+                            history.energy.reset(history.latest_ms());
+                        }
+                    }
+                });
+                panel_ui.separator();
+                egui::ScrollArea::vertical().show(panel_ui, |ui| {
+                    for (snapshot, history) in snapshots.iter().zip(histories.iter()) {
+                        if !settings.shows_gpu(snapshot) {
+                            continue;
+                        }
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "GPU {} · {}",
+                                snapshot.index, snapshot.name
+                            ))
+                            .size(10.0)
+                            .color(ui::TEXT_SECONDARY),
+                        );
+                        ui::draw_energy(ui, snapshot.index, history, &settings.energy);
+                        ui.separator();
+                    }
+                });
+            },
+        );
+    }
+
+    /// Separate window with every process in a sortable, filterable table
+    fn draw_process_table(&mut self, ctx: &egui::Context) {
+        let live = self.signals_allowed();
+        let idle_mb: Vec<Option<u64>> = self
+            .snapshots
+            .iter()
+            .map(|s| self.alerts.idle_reservation_mb(s.index))
+            .collect();
+        let table = &mut self.process_table;
+        let snapshots = &self.snapshots;
+        let histories = &self.histories;
+        let mut request = None;
+        let mut open = table.open;
+        secondary_window(
+            ctx,
+            "process_table",
+            "nvdash — processes",
+            [640.0, 360.0],
+            &mut open,
+            |ui| request = table.show(ui, snapshots, histories, &idle_mb, live),
+        );
+        table.open = open;
+        if request.is_some() {
+            self.pending_action = request;
+        }
+    }
+
+    /// Zoomable charts of every collected metric
+    fn draw_charts(&mut self, ctx: &egui::Context) {
+        let charts = &mut self.charts;
+        let snapshots = &self.snapshots;
+        let histories = &self.histories;
+        let mut open = charts.open;
+        secondary_window(
+            ctx,
+            "charts",
+            "nvdash — charts",
+            [720.0, 380.0],
+            &mut open,
+            |ui| charts.show(ui, snapshots, histories),
+        );
+        charts.open = open;
+    }
+
+    /// Simulated and replayed PIDs don't belong to processes on this machine
+    fn signals_allowed(&self) -> bool {
+        matches!(&self.source, Source::Live(m) if !m.is_simulated())
+    }
+
+    /// Ask before signalling a process, then send the signal
+    fn draw_action_confirm(&mut self, ctx: &egui::Context) {
+        let Some(req) = &self.pending_action else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("confirm_action")).show(ctx, |ui| {
+            ui.label(
+                egui::RichText::new(format!("{}?", req.action.label()))
+                    .color(ui::TEXT_PRIMARY),
+            );
+            ui.label(
+                egui::RichText::new(format!("{} (PID {})", req.name, req.pid))
+                    .size(10.0)
+                    .color(ui::TEXT_SECONDARY),
+            );
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    cancelled = true;
+                }
+                let verb = egui::RichText::new(req.action.verb()).color(ui::ACCENT_RED);
+                if ui.button(verb).clicked() {
+                    confirmed = true;
+                }
+            });
+        });
+
+        if confirmed {
+            if let Err(e) = process::send(req.pid, req.start_time, req.action) {
+                self.action_error = Some((e, Instant::now()));
+            }
+        }
+        if confirmed || cancelled || modal.should_close() {
+            self.pending_action = None;
+        }
+    }
+
+    /// Start recording to a fresh file, or stop the current recording
+    fn toggle_recording(&mut self) {
+        if self.recorder.take().is_some() {
+            return;
+        }
+        match Recorder::create(Recorder::default_path()) {
+            Ok(rec) => self.recorder = Some(rec),
+            Err(e) => self.error_msg = Some(format!("Cannot start recording: {}", e)),
+        }
+    }
+
+    /// Start a session named after `session_name`, or stop the current one
+    /// and open its report
+    fn toggle_session(&mut self) {
+        match self.session.take() {
+            Some(session) => {
+                self.session_report = Some(session.finish(&self.settings.energy));
+                self.session_saved = None;
+            }
+            None => {
+                let name = match self.session_name.trim() {
+                    "" => "session",
+                    name => name,
+                };
+                let start_ms = match &self.source {
+                    Source::Live(_) => chrono::Utc::now().timestamp_millis(),
+                    Source::Replay(player) => player.current().timestamp_ms,
+                };
+                self.session = Some(Session::start(name.to_string(), start_ms));
+            }
+        }
+        #[cfg(windows)]
+        if let Ok(s) = self.shared.lock() {
+            let label = if self.session.is_some() {
+                "Stop session"
+            } else {
+                "Start session"
+            };
+            s.session_item.set_text(label);
+        }
+    }
+
+    /// Name field and start/stop button for sessions
+    fn draw_session_menu(&mut self, ui: &mut egui::Ui) {
+        let color = if self.session.is_some() {
+            ui::NVIDIA_GREEN
+        } else {
+            ui::TEXT_SECONDARY
+        };
+        let label = egui::RichText::new("session").size(10.0).color(color);
+        let menu = ui.menu_button(label, |ui| match &self.session {
+            Some(session) => {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} · since {}",
+                        session.name,
+                        text::fmt_clock(session.start_ms)
+                    ))
+                    .size(10.0)
+                    .color(ui::TEXT_SECONDARY),
+                );
+                if ui.button("Stop and report").clicked() {
+                    self.toggle_session();
+                    ui.close_menu();
+                }
+            }
+            None => {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.session_name)
+                        .hint_text("session name")
+                        .desired_width(140.0),
+                );
+                if ui.button("Start").clicked() {
+                    self.toggle_session();
+                    ui.close_menu();
+                }
+            }
+        });
+        menu.response.on_hover_text(match &self.session {
+            Some(session) => format!("Tracking session \"{}\"", session.name),
+            None => "Summarize GPU use over a named stretch of time".into(),
+        });
+    }
+
+    /// Separate window with the last session's report, to copy or save
+    fn draw_session_report_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.session_report.is_some();
+        let Some(report) = &self.session_report else {
+            return;
+        };
+        let saved = &mut self.session_saved;
+        let error_msg = &mut self.error_msg;
+        secondary_window(
+            ctx,
+            "session_report",
+            &format!("nvdash — session {}", report.name),
+            [640.0, 260.0],
+            &mut open,
+            |panel_ui| {
+                let markdown = report.to_markdown();
+                panel_ui.horizontal(|ui| {
+                    if ui.button("Copy Markdown").clicked() {
+                        ui.ctx().copy_text(markdown.clone());
+                    }
+                    if ui.button("Copy JSON").clicked() {
+                        ui.ctx().copy_text(report.to_json());
+                    }
+                    if ui.button("Save").clicked() {
+                        let path = report.default_path("md");
+                        match std::fs::write(&path, &markdown) {
+                            Ok(()) => *saved = Some(path),
+                            Err(e) => {
+                                *error_msg = Some(format!("Cannot save {}: {}", path.display(), e))
+                            }
+                        }
+                    }
+                    if let Some(path) = saved {
+                        ui.label(
+                            egui::RichText::new(format!("saved to {}", path.display()))
+                                .size(10.0)
+                                .color(ui::TEXT_DIM),
+                        );
+                    }
+                });
+                egui::ScrollArea::both().show(panel_ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(&markdown)
+                            .font(egui::FontId::monospace(11.0))
+                            .color(ui::TEXT_PRIMARY),
+                    );
+                });
+            },
+        );
+        if !open {
+            self.session_report = None;
+        }
+    }
+
+    /// Play/pause, seek slider and speed selector for replay mode
+    fn draw_replay_bar(&mut self, ctx: &egui::Context) {
+        let Source::Replay(player) = &mut self.source else {
+            return;
+        };
+        let mut seek_to = None;
+
+        egui::TopBottomPanel::bottom("replay_bar")
+            .show_separator_line(false)
+            .frame(
+                egui::Frame::new()
+                    .fill(ui::BG_DARK)
+                    .inner_margin(egui::Margin::symmetric(4, 0)),
+            )
+            .show(ctx, |bar_ui| {
+                bar_ui.horizontal(|ui| {
+                    let play_label = if player.playing { "pause" } else { "play" };
+                    if ui
+                        .selectable_label(
+                            player.playing,
+                            egui::RichText::new(play_label)
+                                .size(10.0)
+                                .color(ui::TEXT_SECONDARY),
+                        )
+                        .clicked()
+                    {
+                        if !player.playing && player.at_end() {
+                            seek_to = Some(0.0);
+                        }
+                        player.playing = !player.playing;
+                    }
+
+                    ui.with_layout(
+                        egui::Layout::right_to_left(egui::Align::Center),
+                        |ui| {
+                            egui::ComboBox::from_id_salt("replay_speed")
+                                .selected_text(
+                                    egui::RichText::new(format!("{}x", player.speed))
+                                        .size(10.0)
+                                        .font(egui::FontId::monospace(10.0))
+                                        .color(ui::TEXT_SECONDARY),
+                                )
+                                .width(42.0)
+                                .show_ui(ui, |ui| {
+                                    for &speed in &record::SPEEDS {
+                                        ui.selectable_value(
+                                            &mut player.speed,
+                                            speed,
+                                            format!("{}x", speed),
+                                        );
+                                    }
+                                });
+
+                            let time = text::fmt_clock(player.current().timestamp_ms);
+                            ui.label(
+                                egui::RichText::new(time)
+                                    .size(10.0)
+                                    .font(egui::FontId::monospace(10.0))
+                                    .color(ui::TEXT_SECONDARY),
+                            );
+
+                            let mut pos = player.position_ms();
+                            ui.spacing_mut().slider_width = ui.available_width();
+                            let slider = egui::Slider::new(&mut pos, 0.0..=player.duration_ms())
+                                .show_value(false);
+                            if ui.add(slider).changed() {
+                                seek_to = Some(pos);
+                            }
+                        },
+                    );
+                });
+            });
+
+        if let Some(ms) = seek_to {
+            player.seek(ms);
+            self.snapshots = player.snapshots();
+            self.histories = player.histories();
+            self.alerts = AlertEngine::new(self.settings.alerts.clone());
+        }
     }
 }
 
@@ -213,6 +959,9 @@ impl eframe::App for NvDash {
                     let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE);
                     SetWindowLongW(hwnd, GWL_EXSTYLE, ex_style | WS_EX_TOOLWINDOW as i32);
                     self.hwnd_captured = true;
+                    if self.settings.opacity_pct < 100 {
+                        set_window_opacity(self.settings.opacity_pct);
+                    }
                 }
             }
         }
@@ -281,16 +1030,36 @@ impl eframe::App for NvDash {
                 }
             }
 
-            // Handle menu events (Quit)
+            // Handle menu events (session toggle, Quit)
             use tray_icon::menu::MenuEvent;
-            if let Ok(_event) = MenuEvent::receiver().try_recv() {
-                std::process::exit(0);
+            if let Ok(event) = MenuEvent::receiver().try_recv() {
+                let session = self
+                    .shared
+                    .lock()
+                    .map_or(false, |s| event.id == *s.session_item.id());
+                if !session {
+                    std::process::exit(0);
+                }
+                self.toggle_session();
             }
         }
 
         ui::setup_style(ctx);
         self.poll();
-        ctx.request_repaint_after(self.poll_interval);
+        ctx.request_repaint_after(self.poll_interval());
+        self.track_geometry(ctx);
+        self.sync_settings(ctx);
+        #[cfg(windows)]
+        self.update_tray();
+
+        self.draw_replay_bar(ctx);
+        self.draw_timeline_window(ctx);
+        self.draw_events_window(ctx);
+        self.draw_nvlink_window(ctx);
+        self.draw_energy_window(ctx);
+        self.draw_session_report_window(ctx);
+        self.draw_process_table(ctx);
+        self.draw_charts(ctx);
 
         egui::TopBottomPanel::bottom("poll_bar")
             .show_separator_line(false)
@@ -302,50 +1071,98 @@ impl eframe::App for NvDash {
             .show(ctx, |bar_ui| {
                 bar_ui.horizontal(|ui| {
                     // Left side: toggles
-                    let pin_label = if self.always_on_top { "pinned" } else { "pin" };
+                    let pin_label = if self.settings.always_on_top {
+                        "pinned"
+                    } else {
+                        "pin"
+                    };
                     if ui
                         .selectable_label(
-                            self.always_on_top,
+                            self.settings.always_on_top,
                             egui::RichText::new(pin_label)
                                 .size(10.0)
                                 .color(ui::TEXT_SECONDARY),
                         )
                         .clicked()
                     {
-                        self.always_on_top = !self.always_on_top;
-                        let level = if self.always_on_top {
-                            egui::viewport::WindowLevel::AlwaysOnTop
-                        } else {
-                            egui::viewport::WindowLevel::Normal
-                        };
-                        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+                        self.settings.always_on_top = !self.settings.always_on_top;
+                        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                            window_level(self.settings.always_on_top),
+                        ));
                     }
 
-                    let frame_label = if self.decorations {
+                    let frame_label = if self.settings.decorations {
                         "frame"
                     } else {
                         "frameless"
                     };
                     if ui
                         .selectable_label(
-                            !self.decorations,
+                            !self.settings.decorations,
                             egui::RichText::new(frame_label)
                                 .size(10.0)
                                 .color(ui::TEXT_SECONDARY),
                         )
                         .clicked()
                     {
-                        self.decorations = !self.decorations;
+                        self.settings.decorations = !self.settings.decorations;
                         ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(
-                            self.decorations,
+                            self.settings.decorations,
                         ));
                     }
 
+                    if matches!(self.source, Source::Live(_)) {
+                        let recording = self.recorder.is_some();
+                        let rec_color = if recording {
+                            ui::ACCENT_RED
+                        } else {
+                            ui::TEXT_SECONDARY
+                        };
+                        let resp = ui.selectable_label(
+                            recording,
+                            egui::RichText::new("rec").size(10.0).color(rec_color),
+                        );
+                        let resp = match &self.recorder {
+                            Some(rec) => resp.on_hover_text(format!(
+                                "Recording to {} ({} frames)",
+                                rec.path().display(),
+                                rec.frames()
+                            )),
+                            None => resp.on_hover_text("Record snapshots to a file"),
+                        };
+                        if resp.clicked() {
+                            self.toggle_recording();
+                        }
+                    }
+                    self.draw_session_menu(ui);
+
+                    egui::ComboBox::from_id_salt("window")
+                        .selected_text(
+                            egui::RichText::new(text::fmt_window(self.settings.window_ms))
+                                .size(10.0)
+                                .font(egui::FontId::monospace(10.0))
+                                .color(ui::TEXT_SECONDARY),
+                        )
+                        .width(36.0)
+                        .show_ui(ui, |ui| {
+                            for &ms in &history::WINDOWS {
+                                ui.selectable_value(
+                                    &mut self.settings.window_ms,
+                                    ms,
+                                    text::fmt_window(ms),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("Sparkline time window");
+
+                    self.draw_view_menu(ui);
+
                     // Right side: poll rate + opacity
                     ui.with_layout(
                         egui::Layout::right_to_left(egui::Align::Center),
                         |ui| {
-                            let poll_label = format!("{}ms", self.poll_ms);
+                            let poll_label = format!("{}ms", self.settings.poll_ms);
                             egui::ComboBox::from_id_salt("poll_rate")
                                 .selected_text(
                                     egui::RichText::new(&poll_label)
@@ -357,12 +1174,7 @@ impl eframe::App for NvDash {
                                 .show_ui(ui, |ui| {
                                     for &ms in &[250u64, 500, 1000, 2000] {
                                         let text = format!("{}ms", ms);
-                                        if ui
-                                            .selectable_value(&mut self.poll_ms, ms, &text)
-                                            .changed()
-                                        {
-                                            self.poll_interval = Duration::from_millis(ms);
-                                        }
+                                        ui.selectable_value(&mut self.settings.poll_ms, ms, text);
                                     }
                                 });
                             ui.label(
@@ -373,7 +1185,7 @@ impl eframe::App for NvDash {
 
                             ui.add_space(4.0);
 
-                            let opacity_label = format!("{}%", self.opacity_pct);
+                            let opacity_label = format!("{}%", self.settings.opacity_pct);
                             egui::ComboBox::from_id_salt("opacity")
                                 .selected_text(
                                     egui::RichText::new(&opacity_label)
@@ -386,7 +1198,11 @@ impl eframe::App for NvDash {
                                     for pct in (10..=100).step_by(10) {
                                         let text = format!("{}%", pct);
                                         if ui
-                                            .selectable_value(&mut self.opacity_pct, pct, &text)
+                                            .selectable_value(
+                                                &mut self.settings.opacity_pct,
+                                                pct,
+                                                &text,
+                                            )
                                             .changed()
                                         {
                                             set_window_opacity(pct);
@@ -412,7 +1228,27 @@ impl eframe::App for NvDash {
             )
             .show(ctx, |main_ui| {
                 main_ui.style_mut().spacing.item_spacing.y = 2.0;
-                if let Some(ref err) = self.error_msg {
+                if let Some(ref notice) = self.notice {
+                    main_ui.label(
+                        egui::RichText::new(notice)
+                            .color(ui::TEXT_DIM)
+                            .size(10.0),
+                    );
+                }
+                if self
+                    .action_error
+                    .as_ref()
+                    .is_some_and(|(_, at)| at.elapsed() > ACTION_ERROR_TTL)
+                {
+                    self.action_error = None;
+                }
+                let action_error = self.action_error.as_ref().map(|(e, _)| e);
+                for err in self
+                    .config_error
+                    .iter()
+                    .chain(&self.error_msg)
+                    .chain(action_error)
+                {
                     main_ui.label(
                         egui::RichText::new(err)
                             .color(ui::ACCENT_RED)
@@ -420,25 +1256,173 @@ impl eframe::App for NvDash {
                     );
                 }
 
-                for (i, snapshot) in self.snapshots.iter().enumerate() {
-                    let history = &self.histories[i];
+                let mut visible: Vec<usize> = (0..self.snapshots.len())
+                    .filter(|&i| self.settings.shows_gpu(&self.snapshots[i]))
+                    .collect();
+                if visible.is_empty() {
+                    main_ui.label(
+                        egui::RichText::new("No GPUs selected")
+                            .color(ui::TEXT_DIM)
+                            .size(10.0),
+                    );
+                }
+                if self.settings.layout == Layout::Tabs && !visible.is_empty() {
+                    if !visible.contains(&self.selected_tab) {
+                        self.selected_tab = visible[0];
+                    }
+                    if visible.len() > 1 {
+                        main_ui.horizontal(|ui| {
+                            for &i in &visible {
+                                let label = egui::RichText::new(format!("GPU {}", i))
+                                    .size(10.0)
+                                    .color(ui::TEXT_SECONDARY);
+                                if ui.selectable_label(self.selected_tab == i, label).clicked() {
+                                    self.selected_tab = i;
+                                }
+                            }
+                        });
+                        main_ui.separator();
+                    }
+                    visible = vec![self.selected_tab];
+                }
 
-                    ui::draw_header(main_ui, snapshot);
+                let signals = self.signals_allowed();
+                let firing = self.alerts.firing();
+                for (n, &i) in visible.iter().enumerate() {
+                    let snapshot = &self.snapshots[i];
+                    let Some(history) = self.histories.get(i) else {
+                        continue;
+                    };
+                    // Processes on an agent's machine can't be signalled from here
+                    let live = signals && snapshot.host.is_empty();
+                    let alerts: Vec<String> = firing
+                        .iter()
+                        .filter(|a| a.gpu == snapshot.index)
+                        .map(|a| self.alerts.describe(a))
+                        .collect();
+
+                    if ui::draw_header(
+                        main_ui,
+                        snapshot,
+                        history,
+                        self.settings.window_ms,
+                        &alerts,
+                    ) {
+                        self.show_events = true;
+                    }
                     main_ui.separator();
-                    ui::draw_text_sparklines(main_ui, snapshot, history);
+                    ui::draw_text_sparklines(main_ui, snapshot, history, self.settings.window_ms);
                     ui::draw_temp_bar(main_ui, snapshot);
+                    ui::draw_forecast(
+                        main_ui,
+                        snapshot,
+                        history,
+                        self.settings.forecast_horizon_min as i64 * 60 * 1000,
+                    );
                     main_ui.separator();
-                    ui::draw_process_list(main_ui, snapshot);
+                    let idle_mb = self.alerts.idle_reservation_mb(snapshot.index);
+                    let listed = ui::draw_process_list(
+                        main_ui,
+                        snapshot,
+                        history,
+                        self.settings.window_ms,
+                        live,
+                        idle_mb,
+                    );
+                    let sliced = ui::draw_mig_instances(
+                        main_ui,
+                        snapshot,
+                        history,
+                        self.settings.window_ms,
+                        live,
+                        idle_mb,
+                    );
+                    match listed.or(sliced) {
+                        Some(ui::ProcessListAction::Signal(req)) => self.pending_action = Some(req),
+                        Some(ui::ProcessListAction::ShowAll) => {
+                            self.process_table.show_gpu(snapshot.index)
+                        }
+                        None => {}
+                    }
                     main_ui.separator();
-                    ui::draw_footer(main_ui, snapshot);
+                    ui::draw_footer(main_ui, snapshot, history, self.settings.window_ms);
 
-                    if i < self.snapshots.len() - 1 {
+                    if n < visible.len() - 1 {
                         main_ui.add_space(6.0);
                         main_ui.separator();
                         main_ui.add_space(6.0);
                     }
                 }
             });
+
+        self.draw_action_confirm(ctx);
+    }
+}
+
+#[cfg(windows)]
+const TRAY_GREEN: [u8; 3] = [118, 185, 0];
+#[cfg(windows)]
+const TRAY_RED: [u8; 3] = [255, 70, 70];
+
+/// 16x16 solid RGBA tray icon
+#[cfg(windows)]
+fn tray_icon_image([r, g, b]: [u8; 3]) -> tray_icon::Icon {
+    let icon_rgba = [r, g, b, 255].repeat(16 * 16);
+    tray_icon::Icon::from_rgba(icon_rgba, 16, 16).expect("Failed to create icon")
+}
+
+/// Extra OS window drawn in the same pass as the main one, while `open`;
+/// closing it clears `open`
+fn secondary_window(
+    ctx: &egui::Context,
+    id: &str,
+    title: &str,
+    size: [f32; 2],
+    open: &mut bool,
+    mut add_contents: impl FnMut(&mut egui::Ui),
+) {
+    if !*open {
+        return;
+    }
+    ctx.show_viewport_immediate(
+        egui::ViewportId::from_hash_of(id),
+        egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size(size),
+        |ctx, _class| {
+            egui::CentralPanel::default()
+                .frame(
+                    egui::Frame::new()
+                        .fill(ui::BG_DARK)
+                        .inner_margin(egui::Margin::same(4)),
+                )
+                .show(ctx, |ui| add_contents(ui));
+            if ctx.input(|i| i.viewport().close_requested()) {
+                *open = false;
+            }
+        },
+    );
+}
+
+/// Note alerts firing and clearing on stderr
+fn log_alerts(engine: &AlertEngine, transitions: &[Transition]) {
+    for t in transitions {
+        match t {
+            Transition::Fired(alert) => {
+                eprintln!("Alert on GPU {}: {}", alert.gpu, engine.describe(alert))
+            }
+            Transition::Cleared { rule, gpu } => {
+                eprintln!("Alert on GPU {} cleared: {}", gpu, engine.rules()[*rule].name)
+            }
+        }
+    }
+}
+
+fn window_level(always_on_top: bool) -> egui::viewport::WindowLevel {
+    if always_on_top {
+        egui::viewport::WindowLevel::AlwaysOnTop
+    } else {
+        egui::viewport::WindowLevel::Normal
     }
 }
 
@@ -464,26 +1448,218 @@ fn set_window_opacity(_pct: u8) {
     // Not supported on this platform
 }
 
+/// Open the local GPUs. `--sim[=N]` forces N simulated GPUs; otherwise NVML
+/// is used. Without it, a machine with `agents` to show shows just theirs;
+/// one without is an error, never a quiet switch to made-up numbers.
+fn open_monitor(
+    opts: &cli::Options,
+    agents: bool,
+) -> Result<(GpuMonitor, Option<String>), String> {
+    if let Some(gpus) = opts.sim {
+        return Ok((
+            GpuMonitor::simulated(sim_config(gpus)),
+            Some("Simulated GPU data".into()),
+        ));
+    }
+
+    match GpuMonitor::init() {
+        Ok(monitor) => Ok((monitor, None)),
+        Err(e) if agents => {
+            eprintln!("Warning: NVML unavailable ({}), showing agents only", e);
+            Ok((GpuMonitor::simulated(sim_config(0)), None))
+        }
+        Err(e) => Err(format!(
+            "Failed to initialize NVML: {} (use --sim for simulated data)",
+            e
+        )),
+    }
+}
+
+fn sim_config(gpus: u32) -> SimConfig {
+    SimConfig {
+        gpus,
+        ..Default::default()
+    }
+}
+
+/// Release builds on Windows have no console of their own; borrow the
+/// parent's so CLI output reaches the terminal that launched us.
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+/// `snapshot` / `watch` / `tui` / `exporter` / `session` / `agent`: no window, no tray, and no
+/// silent fallback to the simulator — on a headless box fake numbers would be worse than an error.
+fn run_headless(opts: &cli::Options) -> ! {
+    let mut monitor = match opts.sim {
+        Some(gpus) => GpuMonitor::simulated(sim_config(gpus)),
+        None => match GpuMonitor::init() {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                eprintln!("Failed to initialize NVML: {} (use --sim for simulated data)", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let result = match opts.command {
+        cli::Command::Snapshot => {
+            headless::snapshot(&mut monitor, opts.format.unwrap_or(cli::Format::Json))
+        }
+        cli::Command::Watch => {
+            let interval = Duration::from_millis(opts.interval_ms.unwrap_or(500));
+            headless::watch(&mut monitor, interval)
+        }
+        cli::Command::Tui => {
+            let interval = Duration::from_millis(opts.interval_ms.unwrap_or(500));
+            tui::run(&mut monitor, interval)
+        }
+        cli::Command::Exporter => {
+            let addr = opts.listen.as_deref().unwrap_or("0.0.0.0:9835");
+            std::net::TcpListener::bind(addr).and_then(|listener| {
+                eprintln!("Serving Prometheus metrics on http://{}/metrics", addr);
+                prometheus::run(&mut monitor, listener)
+            })
+        }
+        cli::Command::Session => {
+            let interval = Duration::from_millis(opts.interval_ms.unwrap_or(500));
+            let name = opts
+                .session_name
+                .clone()
+                .or_else(|| opts.session_command.first().cloned())
+                .unwrap_or_else(|| "session".into());
+            let markdown = opts.format != Some(cli::Format::Json);
+            // Only the energy rates matter here
+            let settings = match opts.config.clone().or_else(SettingsFile::default_path) {
+                Some(path) => SettingsFile::open(path).1,
+                None => Settings::default(),
+            };
+            match session::run(
+                &mut monitor,
+                name,
+                &opts.session_command,
+                interval,
+                markdown,
+                &settings.energy,
+            ) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => Err(e),
+            }
+        }
+        cli::Command::Agent => {
+            let interval = Duration::from_millis(opts.interval_ms.unwrap_or(500));
+            let addr = opts.listen.as_deref().unwrap_or(agent::DEFAULT_ADDR);
+            std::net::TcpListener::bind(addr).and_then(|listener| {
+                eprintln!("Serving GPUs to nvdash windows on {}", addr);
+                agent::run(&mut monitor, listener, interval, opts.share_cmdline)
+            })
+        }
+        cli::Command::Gui => unreachable!("the GUI is not a headless command"),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
 fn main() -> eframe::Result<()> {
+    let opts = match cli::parse() {
+        Ok(opts) => opts,
+        Err(e) => {
+            attach_console();
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if opts.help {
+        attach_console();
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if opts.command != cli::Command::Gui {
+        attach_console();
+        run_headless(&opts);
+    }
+
+    let config = match opts.config.clone().or_else(SettingsFile::default_path) {
+        Some(path) => {
+            let (file, settings, error) = SettingsFile::open(path);
+            (Some(file), settings, error)
+        }
+        None => (None, Settings::default(), None),
+    };
+    let settings = &config.1;
+
+    let mut agents = opts.connect.clone();
+    if opts.replay.is_none() {
+        for addr in &settings.agents {
+            if !agents.contains(addr) {
+                agents.push(addr.clone());
+            }
+        }
+    }
+    let (source, notice) = match &opts.replay {
+        Some(path) => match record::load(path) {
+            Ok(frames) => (
+                Source::Replay(Player::new(frames)),
+                Some(format!("Replaying {}", path.display())),
+            ),
+            Err(e) => {
+                eprintln!("Cannot load recording: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => match open_monitor(&opts, !agents.is_empty()) {
+            Ok((monitor, notice)) => (Source::Live(monitor), notice),
+            Err(e) => {
+                attach_console();
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let recorder = match &opts.record {
+        Some(path) => {
+            let path = path.clone().unwrap_or_else(Recorder::default_path);
+            match Recorder::create(&path) {
+                Ok(rec) => Some(rec),
+                Err(e) => {
+                    eprintln!("Cannot create {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let interval = Duration::from_millis(settings.poll_ms);
+    let remotes: Vec<Remote> = agents
+        .into_iter()
+        .map(|addr| Remote::connect(addr, interval))
+        .collect();
+
     // Create the tray icon (Windows only)
     #[cfg(windows)]
     let shared = {
         use tray_icon::menu::{Menu, MenuItem};
         use tray_icon::TrayIconBuilder;
 
-        // 16x16 NVIDIA green RGBA icon
-        let mut icon_rgba = Vec::with_capacity(16 * 16 * 4);
-        for _ in 0..(16 * 16) {
-            icon_rgba.push(118); // R
-            icon_rgba.push(185); // G
-            icon_rgba.push(0); // B
-            icon_rgba.push(255); // A
-        }
-        let icon = tray_icon::Icon::from_rgba(icon_rgba, 16, 16).expect("Failed to create icon");
+        let icon = tray_icon_image(TRAY_GREEN);
 
-        // Right-click menu with "Quit"
+        // Right-click menu with the session toggle and "Quit"
         let menu = Menu::new();
+        let session_item = MenuItem::new("Start session", true, None);
         let quit_item = MenuItem::new("Quit", true, None);
+        menu.append(&session_item).expect("Failed to add menu item");
         menu.append(&quit_item).expect("Failed to add menu item");
 
         let _tray_icon = TrayIconBuilder::new()
@@ -495,21 +1671,31 @@ fn main() -> eframe::Result<()> {
 
         // Keep tray icon alive by leaking it (it needs to live for the entire app lifetime)
         // Box::leak keeps it alive without needing a global variable
-        Box::leak(Box::new(_tray_icon));
+        let tray: &'static tray_icon::TrayIcon = Box::leak(Box::new(_tray_icon));
 
         let shared = Arc::new(Mutex::new(Shared {
             hwnd: std::ptr::null_mut(),
             visible: true,
+            tray,
+            session_item,
         }));
 
         shared
     };
 
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_title("nvdash")
+        .with_inner_size([380.0, 240.0])
+        .with_min_inner_size([340.0, 140.0])
+        .with_window_level(window_level(settings.always_on_top))
+        .with_decorations(settings.decorations);
+    if let Some(g) = settings.window {
+        viewport = viewport
+            .with_position([g.x, g.y])
+            .with_inner_size([g.width, g.height]);
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_title("nvdash")
-            .with_inner_size([380.0, 240.0])
-            .with_min_inner_size([340.0, 140.0]),
+        viewport,
         ..Default::default()
     };
 
@@ -519,7 +1705,7 @@ fn main() -> eframe::Result<()> {
         eframe::run_native(
             "nvdash",
             options,
-            Box::new(move |cc| Ok(Box::new(NvDash::new(cc, shared_clone)))),
+            Box::new(move |cc| Ok(Box::new(NvDash::new(cc, source, remotes, recorder, notice, config, shared_clone)))),
         )
     }
 
@@ -528,7 +1714,7 @@ fn main() -> eframe::Result<()> {
         eframe::run_native(
             "nvdash",
             options,
-            Box::new(|cc| Ok(Box::new(NvDash::new(cc)))),
+            Box::new(|cc| Ok(Box::new(NvDash::new(cc, source, remotes, recorder, notice, config)))),
         )
     }
 }
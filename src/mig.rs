@@ -0,0 +1,126 @@
+//! MIG (Multi-Instance GPU) enumeration.
+//!
+//! nvml-wrapper has no MIG API, so the few calls needed are made through the
+//! raw bindings on a second handle to the same NVML library. MIG device
+//! handles are then wrapped back into `Device`s to reuse the safe wrappers
+//! for UUID, memory and processes.
+
+use crate::gpu::{MigInstance, ProcessKind};
+use crate::nvml::{gpu_process, LIBRARY};
+use nvml_wrapper::{Device, Nvml};
+use nvml_wrapper_sys::bindings::{
+    nvmlDeviceAttributes_t, nvmlDevice_t, nvmlReturn_enum_NVML_SUCCESS, NvmlLib,
+    NVML_DEVICE_MIG_ENABLE,
+};
+
+pub struct MigApi {
+    lib: NvmlLib,
+}
+
+impl MigApi {
+    /// `None` if the library can't be opened or predates MIG (R450)
+    pub fn load() -> Option<Self> {
+        // SAFETY: the same library nvml-wrapper already loaded and initialized
+        let lib = unsafe { NvmlLib::new(LIBRARY) }.ok()?;
+        // The raw wrappers panic on a missing symbol, so check them all here
+        let complete = lib.nvmlDeviceGetMigMode.is_ok()
+            && lib.nvmlDeviceGetMaxMigDeviceCount.is_ok()
+            && lib.nvmlDeviceGetMigDeviceHandleByIndex.is_ok()
+            && lib.nvmlDeviceGetGpuInstanceId.is_ok()
+            && lib.nvmlDeviceGetComputeInstanceId.is_ok()
+            && lib.nvmlDeviceGetAttributes_v2.is_ok();
+        complete.then_some(Self { lib })
+    }
+
+    /// MIG slices of `device`; empty if MIG is off or unsupported
+    pub fn instances(&self, nvml: &Nvml, device: &Device) -> Vec<MigInstance> {
+        // SAFETY: `device` is a live handle from `nvml`, and every pointer
+        // passed below points at a local that outlives the call
+        unsafe {
+            let parent = device.handle();
+            let (mut current, mut pending) = (0, 0);
+            if self
+                .lib
+                .nvmlDeviceGetMigMode(parent, &mut current, &mut pending)
+                != nvmlReturn_enum_NVML_SUCCESS
+                || current != NVML_DEVICE_MIG_ENABLE
+            {
+                return Vec::new();
+            }
+            let mut max = 0;
+            if self.lib.nvmlDeviceGetMaxMigDeviceCount(parent, &mut max)
+                != nvmlReturn_enum_NVML_SUCCESS
+            {
+                return Vec::new();
+            }
+
+            let mut instances = Vec::new();
+            for i in 0..max {
+                let mut handle: nvmlDevice_t = std::ptr::null_mut();
+                // Unpopulated slots are NotFound
+                if self
+                    .lib
+                    .nvmlDeviceGetMigDeviceHandleByIndex(parent, i, &mut handle)
+                    != nvmlReturn_enum_NVML_SUCCESS
+                {
+                    continue;
+                }
+                instances.push(self.instance(Device::new(handle, nvml)));
+            }
+            instances.sort_by_key(|m| (m.gpu_instance_id, m.compute_instance_id));
+            instances
+        }
+    }
+
+    unsafe fn instance(&self, mig: Device) -> MigInstance {
+        let handle = mig.handle();
+        let (mut gi, mut ci) = (0, 0);
+        self.lib.nvmlDeviceGetGpuInstanceId(handle, &mut gi);
+        self.lib.nvmlDeviceGetComputeInstanceId(handle, &mut ci);
+
+        let mut attrs: nvmlDeviceAttributes_t = std::mem::zeroed();
+        let profile = if self.lib.nvmlDeviceGetAttributes_v2(handle, &mut attrs)
+            == nvmlReturn_enum_NVML_SUCCESS
+        {
+            profile_name(&attrs)
+        } else {
+            String::new()
+        };
+
+        let (vram_used_mb, vram_total_mb) = match mig.memory_info() {
+            Ok(mem) => (mem.used / (1024 * 1024), mem.total / (1024 * 1024)),
+            Err(_) => (0, 0),
+        };
+        let mut processes: Vec<_> = mig
+            .running_compute_processes()
+            .unwrap_or_default()
+            .iter()
+            .map(|p| gpu_process(p, ProcessKind::Compute))
+            .collect();
+        processes.sort_by_key(|p| std::cmp::Reverse(p.vram_mb));
+
+        MigInstance {
+            gpu_instance_id: gi,
+            compute_instance_id: ci,
+            profile,
+            uuid: mig.uuid().unwrap_or_default(),
+            vram_used_mb,
+            vram_total_mb,
+            processes,
+        }
+    }
+}
+
+/// `1g.10gb`, or `1c.2g.20gb` when the compute instance is a fraction of
+/// its GPU instance, as `nvidia-smi` names them
+fn profile_name(attrs: &nvmlDeviceAttributes_t) -> String {
+    let gb = attrs.memorySizeMB.div_ceil(1024);
+    if attrs.computeInstanceSliceCount != attrs.gpuInstanceSliceCount {
+        format!(
+            "{}c.{}g.{}gb",
+            attrs.computeInstanceSliceCount, attrs.gpuInstanceSliceCount, gb
+        )
+    } else {
+        format!("{}g.{}gb", attrs.gpuInstanceSliceCount, gb)
+    }
+}
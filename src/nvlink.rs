@@ -0,0 +1,141 @@
+//! NVLink per-link state, error counters and throughput.
+//!
+//! State, version, remote end and error counters come through nvml-wrapper.
+//! Throughput comes from the cumulative per-link data counters, NVML field
+//! values scoped to one link, which the wrapper can't address; those are
+//! read through the raw bindings as in `mig`, and turned into rates between
+//! two polls.
+
+use crate::gpu::NvLinkStatus;
+use crate::nvml::LIBRARY;
+use nvml_wrapper::enum_wrappers::nv_link::ErrorCounter;
+use nvml_wrapper::{Device, Nvml};
+use nvml_wrapper_sys::bindings::field_id::{
+    NVML_FI_DEV_NVLINK_THROUGHPUT_DATA_RX, NVML_FI_DEV_NVLINK_THROUGHPUT_DATA_TX,
+};
+use nvml_wrapper_sys::bindings::{
+    nvmlFieldValue_t, nvmlReturn_enum_NVML_SUCCESS,
+    nvmlValueType_enum_NVML_VALUE_TYPE_UNSIGNED_LONG_LONG, NvmlLib, NVML_NVLINK_MAX_LINKS,
+};
+use std::collections::HashMap;
+
+/// A link's cumulative data counters at one poll
+#[derive(Clone, Copy)]
+struct Counters {
+    /// Driver timestamp, µs
+    t_us: i64,
+    tx_kib: u64,
+    rx_kib: u64,
+}
+
+pub struct NvLinkReader {
+    /// `None` if the library can't be opened; links then report no throughput
+    lib: Option<NvmlLib>,
+    /// Counters at the previous poll, by (device index, link)
+    previous: HashMap<(u32, u32), Counters>,
+}
+
+impl NvLinkReader {
+    pub fn new() -> Self {
+        // SAFETY: the same library nvml-wrapper already loaded and initialized
+        let lib = unsafe { NvmlLib::new(LIBRARY) }
+            .ok()
+            // The raw wrappers panic on a missing symbol
+            .filter(|lib| lib.nvmlDeviceGetFieldValues.is_ok());
+        Self {
+            lib,
+            previous: HashMap::new(),
+        }
+    }
+
+    /// Links of device `index`; empty if it has no NVLink
+    pub fn links(&mut self, nvml: &Nvml, index: u32, device: &Device) -> Vec<NvLinkStatus> {
+        let mut links = Vec::new();
+        for link in 0..NVML_NVLINK_MAX_LINKS {
+            let wrapper = device.link_wrapper_for(link);
+            // Unsupported past the device's last link, or without NVLink
+            let Ok(active) = wrapper.is_active() else {
+                continue;
+            };
+            if !active {
+                self.previous.remove(&(index, link));
+                links.push(NvLinkStatus {
+                    link,
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            let remote_bus_id = wrapper
+                .remote_pci_info()
+                .map(|pci| pci.bus_id)
+                .unwrap_or_default();
+            let remote_gpu = nvml
+                .device_by_pci_bus_id(remote_bus_id.as_str())
+                .and_then(|d| d.index())
+                .ok();
+            let count = |counter| wrapper.error_counter(counter).unwrap_or(0);
+            let (tx_kb_s, rx_kb_s) = self.throughput(index, link, device);
+            links.push(NvLinkStatus {
+                link,
+                active,
+                version: wrapper.version().unwrap_or(0),
+                remote_bus_id,
+                remote_gpu,
+                tx_kb_s,
+                rx_kb_s,
+                crc_errors: count(ErrorCounter::DlCrcFlit) + count(ErrorCounter::DlCrcData),
+                replay_errors: count(ErrorCounter::DlReplay),
+                recovery_errors: count(ErrorCounter::DlRecovery),
+            });
+        }
+        links
+    }
+
+    /// KB/s in each direction since the previous poll; `None` on the first
+    fn throughput(&mut self, index: u32, link: u32, device: &Device) -> (Option<u64>, Option<u64>) {
+        // SAFETY: `device` is a live handle
+        let Some(now) = (unsafe { self.counters(device, link) }) else {
+            return (None, None);
+        };
+        let Some(&then) = self.previous.get(&(index, link)) else {
+            self.previous.insert((index, link), now);
+            return (None, None);
+        };
+        let dt_s = (now.t_us - then.t_us) as f64 / 1e6;
+        // The driver hasn't refreshed the counters since the last poll
+        if dt_s <= 0.0 {
+            return (None, None);
+        }
+        self.previous.insert((index, link), now);
+        let rate = |now: u64, then: u64| Some((now.saturating_sub(then) as f64 / dt_s) as u64);
+        (rate(now.tx_kib, then.tx_kib), rate(now.rx_kib, then.rx_kib))
+    }
+
+    unsafe fn counters(&self, device: &Device, link: u32) -> Option<Counters> {
+        let lib = self.lib.as_ref()?;
+        let mut values: [nvmlFieldValue_t; 2] = std::mem::zeroed();
+        let fields = [
+            NVML_FI_DEV_NVLINK_THROUGHPUT_DATA_TX,
+            NVML_FI_DEV_NVLINK_THROUGHPUT_DATA_RX,
+        ];
+        for (value, field) in values.iter_mut().zip(fields) {
+            value.fieldId = field;
+            value.scopeId = link;
+        }
+        if lib.nvmlDeviceGetFieldValues(device.handle(), 2, values.as_mut_ptr())
+            != nvmlReturn_enum_NVML_SUCCESS
+        {
+            return None;
+        }
+        let valid = values.iter().all(|v| {
+            v.nvmlReturn == nvmlReturn_enum_NVML_SUCCESS
+                && v.valueType == nvmlValueType_enum_NVML_VALUE_TYPE_UNSIGNED_LONG_LONG
+        });
+        valid.then(|| Counters {
+            t_us: values[0].timestamp,
+            tx_kib: values[0].value.ullVal,
+            rx_kib: values[1].value.ullVal,
+        })
+    }
+}
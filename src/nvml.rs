@@ -0,0 +1,431 @@
+//! NVML backend: reads live metrics from the NVIDIA driver.
+
+use crate::events::{self, EventReceiver};
+use crate::gpu::{
+    EccCounts, EncoderSession, GpuBackend, GpuError, GpuEvent, GpuSnapshot, ProcessInfo,
+    ProcessKind, ProcessUtil, PcieStatus, ThrottleReason, VideoCodec,
+};
+use crate::mig::MigApi;
+use crate::nvlink::NvLinkReader;
+use nvml_wrapper::bitmasks::device::ThrottleReasons;
+use nvml_wrapper::enum_wrappers::device::{
+    EccCounter, EncoderType, MemoryError, PcieUtilCounter, TemperatureSensor,
+    TemperatureThreshold,
+};
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use nvml_wrapper::error::NvmlError;
+use nvml_wrapper::struct_wrappers::device::EncoderSessionInfo;
+use nvml_wrapper::{Device, Nvml};
+use std::collections::HashMap;
+use std::time::Instant;
+use sysinfo::{
+    Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users,
+    MINIMUM_CPU_UPDATE_INTERVAL,
+};
+
+/// The NVML library, for the calls made through the raw bindings
+#[cfg(windows)]
+pub const LIBRARY: &str = "nvml.dll";
+#[cfg(not(windows))]
+pub const LIBRARY: &str = "libnvidia-ml.so";
+
+/// Events held per device between polls; the oldest go first
+const MAX_PENDING_EVENTS: usize = 256;
+
+pub struct NvmlBackend {
+    nvml: Nvml,
+    device_count: u32,
+    /// Kept between polls so per-process CPU usage has a previous sample
+    sys: System,
+    /// When `sys` first sampled every process's CPU time; the first poll
+    /// waits until a usage figure can be measured against it
+    cpu_sampled: Instant,
+    users: Users,
+    /// Per device: timestamp of the newest process utilization sample seen,
+    /// so each poll only averages samples taken since the last one
+    util_seen_us: Vec<Option<u64>>,
+    /// `None` if the driver predates MIG
+    mig: Option<MigApi>,
+    nvlink: NvLinkReader,
+    events: EventReceiver,
+    /// Per device: events received since it was last read
+    pending_events: Vec<Vec<GpuEvent>>,
+}
+
+impl NvmlBackend {
+    pub fn init() -> Result<Self, NvmlError> {
+        let nvml = Nvml::init()?;
+        let device_count = nvml.device_count()?;
+        // CPU usage is the difference between two samples. Take the first
+        // now, so even a one-shot `nvdash snapshot` reports real figures
+        // rather than 0% for every process.
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing().with_cpu(),
+        );
+        Ok(Self {
+            nvml,
+            device_count,
+            sys,
+            cpu_sampled: Instant::now(),
+            users: Users::new_with_refreshed_list(),
+            util_seen_us: vec![None; device_count as usize],
+            mig: MigApi::load(),
+            nvlink: NvLinkReader::new(),
+            events: events::spawn(),
+            pending_events: vec![Vec::new(); device_count as usize],
+        })
+    }
+
+    pub fn driver_version(&self) -> String {
+        self.nvml
+            .sys_driver_version()
+            .unwrap_or_else(|_| "N/A".into())
+    }
+
+    pub fn cuda_version(&self) -> String {
+        match self.nvml.sys_cuda_driver_version() {
+            Ok(v) => {
+                let major = v / 1000;
+                let minor = (v % 1000) / 10;
+                format!("{}.{}", major, minor)
+            }
+            Err(_) => "N/A".into(),
+        }
+    }
+
+    /// Events for device `index` since the last call
+    fn take_events(&mut self, index: u32) -> Vec<GpuEvent> {
+        for (i, event) in self.events.try_iter() {
+            if let Some(pending) = self.pending_events.get_mut(i as usize) {
+                if pending.len() >= MAX_PENDING_EVENTS {
+                    pending.remove(0);
+                }
+                pending.push(event);
+            }
+        }
+        self.pending_events
+            .get_mut(index as usize)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn read_device(&mut self, index: u32) -> Result<GpuSnapshot, NvmlError> {
+        let device = self.nvml.device_by_index(index)?;
+
+        let name = device.name().unwrap_or_else(|_| "Unknown GPU".into());
+        let uuid = device.uuid().unwrap_or_default();
+
+        let utilization = device.utilization_rates().ok();
+
+        let mem_info = device.memory_info()?;
+
+        let temperature = device.temperature(TemperatureSensor::Gpu).unwrap_or(0);
+        let temp_slowdown_c = device
+            .temperature_threshold(TemperatureThreshold::Slowdown)
+            .ok();
+
+        let fan_speed = device.fan_speed(0).ok();
+
+        let power_draw_mw = device.power_usage().unwrap_or(0) as f64;
+        let power_limit_mw = device.enforced_power_limit().unwrap_or(0) as f64;
+
+        let clock_graphics = device
+            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Graphics)
+            .unwrap_or(0);
+        let clock_memory = device
+            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::Memory)
+            .unwrap_or(0);
+        let clock_sm = device
+            .clock_info(nvml_wrapper::enum_wrappers::device::Clock::SM)
+            .unwrap_or(0);
+
+        let throttle_reasons = device
+            .current_throttle_reasons()
+            .map(decode_throttle_reasons)
+            .unwrap_or_default();
+
+        let ecc = ecc_counts(&device);
+        let pcie = pcie_status(&device);
+        let nvlinks = self.nvlink.links(&self.nvml, index, &device);
+        let encoder_util = device.encoder_utilization().ok().map(|u| u.utilization);
+        let decoder_util = device.decoder_utilization().ok().map(|u| u.utilization);
+        let energy_mj = device.total_energy_consumption().ok();
+
+        // Collect GPU processes from both compute and graphics lists
+        let mut processes = Vec::new();
+
+        if let Ok(compute_procs) = device.running_compute_processes() {
+            for proc in compute_procs {
+                processes.push(gpu_process(&proc, ProcessKind::Compute));
+            }
+        }
+        if let Ok(gfx_procs) = device.running_graphics_processes() {
+            for proc in gfx_procs {
+                if let Some(p) = processes.iter_mut().find(|p| p.pid == proc.pid) {
+                    p.kind = ProcessKind::Both;
+                    continue;
+                }
+                processes.push(gpu_process(&proc, ProcessKind::Graphics));
+            }
+        }
+
+        // Per-process engine usage. The driver only reports processes that
+        // were busy during the period, so the rest are idle, not unknown.
+        let seen = self.util_seen_us.get(index as usize).copied().flatten();
+        let samples = match device.process_utilization_stats(seen) {
+            Ok(samples) => Some(samples),
+            // Nothing sampled since `seen`
+            Err(NvmlError::NotFound) => Some(Vec::new()),
+            Err(_) => None,
+        };
+        if let Some(samples) = samples {
+            let mut sums: HashMap<u32, (ProcessUtil, u32)> = HashMap::new();
+            let mut newest = seen;
+            for s in samples {
+                newest = newest.max(Some(s.timestamp));
+                let (sum, n) = sums.entry(s.pid).or_default();
+                sum.sm += s.sm_util;
+                sum.mem += s.mem_util;
+                sum.enc += s.enc_util;
+                sum.dec += s.dec_util;
+                *n += 1;
+            }
+            if let Some(slot) = self.util_seen_us.get_mut(index as usize) {
+                *slot = newest;
+            }
+            for proc in &mut processes {
+                let util = match sums.get(&proc.pid) {
+                    Some(&(sum, n)) => ProcessUtil {
+                        sm: sum.sm / n,
+                        mem: sum.mem / n,
+                        enc: sum.enc / n,
+                        dec: sum.dec / n,
+                    },
+                    None => ProcessUtil::default(),
+                };
+                proc.utilization = Some(util);
+            }
+        }
+
+        // Encoding needs a CUDA or graphics context, so every session's owner
+        // is already listed
+        if let Ok(sessions) = device.encoder_sessions() {
+            for session in &sessions {
+                if let Some(p) = processes.iter_mut().find(|p| p.pid == session.pid) {
+                    p.encoder_sessions.push(encoder_session(session));
+                }
+            }
+        }
+
+        // MIG slices and the processes running in them
+        let mut mig_instances = match &self.mig {
+            Some(mig) => mig.instances(&self.nvml, &device),
+            None => Vec::new(),
+        };
+
+        // With enough privilege the parent lists slice processes too; keep
+        // them only under their slice
+        processes.retain(|p| {
+            !mig_instances
+                .iter()
+                .any(|m| m.processes.iter().any(|q| q.pid == p.pid))
+        });
+
+        // Batch resolve names, command lines, owners, ...
+        self.resolve_processes(&mut processes);
+        for instance in &mut mig_instances {
+            self.resolve_processes(&mut instance.processes);
+        }
+
+        // Sort by VRAM usage descending
+        processes.sort_by_key(|p| std::cmp::Reverse(p.vram_mb));
+
+        Ok(GpuSnapshot {
+            name,
+            index,
+            driver_version: self.driver_version(),
+            cuda_version: self.cuda_version(),
+            gpu_util: utilization.as_ref().map_or(0, |u| u.gpu),
+            memory_util: utilization.as_ref().map_or(0, |u| u.memory),
+            vram_used_mb: mem_info.used / (1024 * 1024),
+            vram_total_mb: mem_info.total / (1024 * 1024),
+            temperature,
+            fan_speed,
+            power_draw_w: power_draw_mw / 1000.0,
+            power_limit_w: power_limit_mw / 1000.0,
+            clock_graphics_mhz: clock_graphics,
+            clock_memory_mhz: clock_memory,
+            clock_sm_mhz: clock_sm,
+            processes,
+            uuid,
+            mig_instances,
+            ecc,
+            events: self.take_events(index),
+            throttle_reasons,
+            pcie,
+            nvlinks,
+            encoder_util,
+            decoder_util,
+            energy_mj,
+            temp_slowdown_c,
+            host: String::new(),
+            util_unavailable: utilization.is_none(),
+        })
+    }
+}
+
+fn decode_throttle_reasons(bits: ThrottleReasons) -> Vec<ThrottleReason> {
+    const FLAGS: [(ThrottleReasons, ThrottleReason); 9] = [
+        (ThrottleReasons::GPU_IDLE, ThrottleReason::GpuIdle),
+        (
+            ThrottleReasons::APPLICATIONS_CLOCKS_SETTING,
+            ThrottleReason::ApplicationsClocks,
+        ),
+        (ThrottleReasons::SW_POWER_CAP, ThrottleReason::SwPowerCap),
+        (ThrottleReasons::HW_SLOWDOWN, ThrottleReason::HwSlowdown),
+        (ThrottleReasons::SYNC_BOOST, ThrottleReason::SyncBoost),
+        (ThrottleReasons::SW_THERMAL_SLOWDOWN, ThrottleReason::SwThermal),
+        (ThrottleReasons::HW_THERMAL_SLOWDOWN, ThrottleReason::HwThermal),
+        (
+            ThrottleReasons::HW_POWER_BRAKE_SLOWDOWN,
+            ThrottleReason::HwPowerBrake,
+        ),
+        (
+            ThrottleReasons::DISPLAY_CLOCK_SETTING,
+            ThrottleReason::DisplayClocks,
+        ),
+    ];
+    FLAGS
+        .iter()
+        .filter(|(flag, _)| bits.contains(*flag))
+        .map(|&(_, reason)| reason)
+        .collect()
+}
+
+/// `None` if the link can't be read; throughput reads as 0 where unsupported
+fn pcie_status(device: &Device) -> Option<PcieStatus> {
+    Some(PcieStatus {
+        tx_kb_s: device.pcie_throughput(PcieUtilCounter::Send).unwrap_or(0),
+        rx_kb_s: device.pcie_throughput(PcieUtilCounter::Receive).unwrap_or(0),
+        link_gen: device.current_pcie_link_gen().ok()?,
+        max_link_gen: device.max_pcie_link_gen().ok()?,
+        link_width: device.current_pcie_link_width().ok()?,
+        max_link_width: device.max_pcie_link_width().ok()?,
+    })
+}
+
+fn encoder_session(session: &EncoderSessionInfo) -> EncoderSession {
+    EncoderSession {
+        session_id: session.session_id,
+        codec: match session.codec_type {
+            EncoderType::H264 => VideoCodec::H264,
+            EncoderType::HEVC => VideoCodec::Hevc,
+        },
+        width: session.hres,
+        height: session.vres,
+        fps: session.average_fps,
+        latency_us: session.average_latency,
+    }
+}
+
+/// `None` unless ECC is enabled
+fn ecc_counts(device: &Device) -> Option<EccCounts> {
+    let count = |error, counter| device.total_ecc_errors(error, counter).ok();
+    Some(EccCounts {
+        volatile_corrected: count(MemoryError::Corrected, EccCounter::Volatile)?,
+        volatile_uncorrected: count(MemoryError::Uncorrected, EccCounter::Volatile)?,
+        aggregate_corrected: count(MemoryError::Corrected, EccCounter::Aggregate)?,
+        aggregate_uncorrected: count(MemoryError::Uncorrected, EccCounter::Aggregate)?,
+    })
+}
+
+/// A process as NVML lists it; host-side details are filled in later by
+/// `NvmlBackend::resolve_processes`
+pub fn gpu_process(
+    proc: &nvml_wrapper::struct_wrappers::device::ProcessInfo,
+    kind: ProcessKind,
+) -> ProcessInfo {
+    let vram_bytes = match proc.used_gpu_memory {
+        UsedGpuMemory::Used(bytes) => bytes,
+        UsedGpuMemory::Unavailable => 0,
+    };
+    ProcessInfo {
+        pid: proc.pid,
+        name: String::new(),
+        vram_mb: vram_bytes / (1024 * 1024),
+        start_time: 0,
+        cmdline: String::new(),
+        user: String::new(),
+        cpu_pct: 0.0,
+        rss_mb: 0,
+        cwd: String::new(),
+        kind,
+        utilization: None,
+        encoder_sessions: Vec::new(),
+    }
+}
+
+impl NvmlBackend {
+    /// Fill in host-side details from the OS process table
+    fn resolve_processes(&mut self, processes: &mut [ProcessInfo]) {
+        let pids: Vec<Pid> = processes.iter().map(|p| Pid::from_u32(p.pid)).collect();
+        if !pids.is_empty() {
+            let wait = MINIMUM_CPU_UPDATE_INTERVAL.saturating_sub(self.cpu_sampled.elapsed());
+            std::thread::sleep(wait);
+        }
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&pids),
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_memory()
+                .with_cmd(UpdateKind::OnlyIfNotSet)
+                .with_cwd(UpdateKind::OnlyIfNotSet)
+                .with_user(UpdateKind::OnlyIfNotSet),
+        );
+        for proc in processes.iter_mut() {
+            let Some(p) = self.sys.process(Pid::from_u32(proc.pid)) else {
+                proc.name = format!("PID {}", proc.pid);
+                continue;
+            };
+            proc.name = p.name().to_string_lossy().to_string();
+            proc.start_time = p.start_time();
+            proc.cmdline = p
+                .cmd()
+                .iter()
+                .map(|a| a.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ");
+            proc.cpu_pct = p.cpu_usage();
+            proc.rss_mb = p.memory() / (1024 * 1024);
+            proc.cwd = p
+                .cwd()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default();
+            if let Some(uid) = p.user_id() {
+                if self.users.get_user_by_id(uid).is_none() {
+                    // Account created since startup
+                    self.users.refresh();
+                }
+                proc.user = self
+                    .users
+                    .get_user_by_id(uid)
+                    .map(|u| u.name().to_string())
+                    .unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl GpuBackend for NvmlBackend {
+    fn device_count(&self) -> u32 {
+        self.device_count
+    }
+
+    fn snapshot(&mut self, index: u32) -> Result<GpuSnapshot, GpuError> {
+        Ok(self.read_device(index)?)
+    }
+}
@@ -0,0 +1,72 @@
+//! Acting on GPU processes: sending them signals.
+//!
+//! Goes through sysinfo so there is no platform code here. Windows has no
+//! SIGTERM/SIGSTOP/SIGCONT equivalent, so only `Kill` works there.
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Terminate,
+    Kill,
+    Suspend,
+    Resume,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Terminate,
+        Action::Kill,
+        Action::Suspend,
+        Action::Resume,
+    ];
+
+    /// Menu label: `Terminate (SIGTERM)`
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Terminate => "Terminate (SIGTERM)",
+            Action::Kill => "Kill (SIGKILL)",
+            Action::Suspend => "Suspend (SIGSTOP)",
+            Action::Resume => "Resume (SIGCONT)",
+        }
+    }
+
+    /// Verb for the confirmation button
+    pub fn verb(self) -> &'static str {
+        match self {
+            Action::Terminate => "Terminate",
+            Action::Kill => "Kill",
+            Action::Suspend => "Suspend",
+            Action::Resume => "Resume",
+        }
+    }
+
+    fn signal(self) -> Signal {
+        match self {
+            Action::Terminate => Signal::Term,
+            Action::Kill => Signal::Kill,
+            Action::Suspend => Signal::Stop,
+            Action::Resume => Signal::Continue,
+        }
+    }
+}
+
+/// Send `action` to `pid`. A nonzero `start_time` (Unix seconds) must match
+/// the running process, so a PID reused since the last poll is left alone.
+pub fn send(pid: u32, start_time: u64, action: Action) -> Result<(), String> {
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[Pid::from_u32(pid)]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    let process = sys
+        .process(Pid::from_u32(pid))
+        .filter(|p| start_time == 0 || p.start_time() == start_time)
+        .ok_or_else(|| format!("PID {} has exited", pid))?;
+    match process.kill_with(action.signal()) {
+        Some(true) => Ok(()),
+        Some(false) => Err(format!("Cannot signal PID {} (permission denied?)", pid)),
+        None => Err(format!("{} is not supported on this platform", action.verb())),
+    }
+}
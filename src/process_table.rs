@@ -0,0 +1,471 @@
+//! Full process table: every process on one GPU or on all of them, sortable
+//! by any column and narrowed by a search string. The compact list in the
+//! main window only has room for the top three.
+
+use crate::gpu::{GpuSnapshot, MigInstance, ProcessInfo, ProcessUtil};
+use crate::history::GpuHistory;
+use crate::text;
+use crate::ui::{self, ProcessRequest};
+use egui::{self, FontId, RichText};
+use std::cmp::Ordering;
+
+const FONT_SIZE: f32 = 11.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Gpu,
+    Pid,
+    Name,
+    User,
+    Kind,
+    Vram,
+    /// How long the process has been reserving its idle GPU
+    Idle,
+    Sm,
+    Mem,
+    Enc,
+    Dec,
+    /// Encoder sessions
+    Nvenc,
+}
+
+impl Column {
+    const ALL: [Column; 12] = [
+        Column::Gpu,
+        Column::Pid,
+        Column::Name,
+        Column::User,
+        Column::Kind,
+        Column::Vram,
+        Column::Idle,
+        Column::Sm,
+        Column::Mem,
+        Column::Enc,
+        Column::Dec,
+        Column::Nvenc,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Column::Gpu => "GPU",
+            Column::Pid => "PID",
+            Column::Name => "NAME",
+            Column::User => "USER",
+            Column::Kind => "TYPE",
+            Column::Vram => "VRAM",
+            Column::Idle => "IDLE",
+            Column::Sm => "SM%",
+            Column::Mem => "MEM%",
+            Column::Enc => "ENC%",
+            Column::Dec => "DEC%",
+            Column::Nvenc => "NVENC",
+        }
+    }
+
+    /// Sizes and loads read best largest-first, everything else ascending
+    fn descending_by_default(self) -> bool {
+        matches!(
+            self,
+            Column::Vram
+                | Column::Idle
+                | Column::Sm
+                | Column::Mem
+                | Column::Enc
+                | Column::Dec
+                | Column::Nvenc
+        )
+    }
+
+    /// Value of a utilization column; `None` for the other columns
+    fn util(self, proc: &ProcessInfo) -> Option<Option<u32>> {
+        let pick = match self {
+            Column::Sm => |u: ProcessUtil| u.sm,
+            Column::Mem => |u: ProcessUtil| u.mem,
+            Column::Enc => |u: ProcessUtil| u.enc,
+            Column::Dec => |u: ProcessUtil| u.dec,
+            _ => return None,
+        };
+        Some(proc.utilization.map(pick))
+    }
+}
+
+struct Row<'a> {
+    gpu: u32,
+    /// MIG slice the process runs in
+    mig: Option<&'a MigInstance>,
+    proc: &'a ProcessInfo,
+    label: String,
+    /// On an agent's machine, out of reach of signals
+    remote: bool,
+    /// Time spent reserving its idle GPU (see `ui::idle_reservation_ms`)
+    idle_ms: Option<i64>,
+}
+
+impl Row<'_> {
+    fn cmp_by(&self, other: &Self, column: Column) -> Ordering {
+        match column {
+            Column::Gpu => {
+                let slice = |r: &Self| r.mig.map(|m| (m.gpu_instance_id, m.compute_instance_id));
+                (self.gpu, slice(self)).cmp(&(other.gpu, slice(other)))
+            }
+            Column::Pid => self.proc.pid.cmp(&other.proc.pid),
+            Column::Name => self.label.to_lowercase().cmp(&other.label.to_lowercase()),
+            Column::User => self.proc.user.cmp(&other.proc.user),
+            Column::Kind => self.proc.kind.cmp(&other.proc.kind),
+            Column::Vram => self.proc.vram_mb.cmp(&other.proc.vram_mb),
+            Column::Idle => self.idle_ms.cmp(&other.idle_ms),
+            Column::Sm | Column::Mem | Column::Enc | Column::Dec => {
+                column.util(self.proc).cmp(&column.util(other.proc))
+            }
+            Column::Nvenc => self
+                .proc
+                .encoder_sessions
+                .len()
+                .cmp(&other.proc.encoder_sessions.len()),
+        }
+    }
+}
+
+pub struct ProcessTable {
+    pub open: bool,
+    filter: String,
+    all_gpus: bool,
+    /// GPU listed when `all_gpus` is off
+    gpu: u32,
+    sort: Column,
+    descending: bool,
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        Self {
+            open: false,
+            filter: String::new(),
+            all_gpus: false,
+            gpu: 0,
+            sort: Column::Vram,
+            descending: true,
+        }
+    }
+}
+
+impl ProcessTable {
+    /// Open on one GPU's processes
+    pub fn show_gpu(&mut self, gpu: u32) {
+        self.open = true;
+        self.all_gpus = false;
+        self.gpu = gpu;
+    }
+
+    /// A column header was clicked: sort by it, or flip the order if the
+    /// table is already sorted by it
+    fn sort_by(&mut self, column: Column) {
+        if column == self.sort {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            self.descending = column.descending_by_default();
+        }
+    }
+
+    /// Processes passing the GPU selection and filter, in display order
+    fn rows<'a>(
+        &self,
+        snapshots: &'a [GpuSnapshot],
+        histories: &[GpuHistory],
+        idle_mb: &[Option<u64>],
+    ) -> Vec<Row<'a>> {
+        let needle = self.filter.trim().to_lowercase();
+        let mut rows: Vec<Row> = snapshots
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| self.all_gpus || s.index == self.gpu)
+            .flat_map(|(i, s)| {
+                let history = histories.get(i);
+                let idle_mb = idle_mb.get(i).copied().flatten();
+                s.all_processes().map(move |(proc, mig)| Row {
+                    gpu: s.index,
+                    mig,
+                    proc,
+                    label: text::process_label(proc),
+                    remote: !s.host.is_empty(),
+                    idle_ms: history.and_then(|h| ui::idle_reservation_ms(proc, h, idle_mb)),
+                })
+            })
+            .filter(|r| {
+                needle.is_empty()
+                    || r.label.to_lowercase().contains(&needle)
+                    || r.proc.user.to_lowercase().contains(&needle)
+                    || r.proc.pid.to_string().contains(&needle)
+            })
+            .collect();
+        // Ties keep a stable order: biggest VRAM first, then PID
+        rows.sort_by(|a, b| {
+            let primary = a.cmp_by(b, self.sort);
+            let primary = if self.descending {
+                primary.reverse()
+            } else {
+                primary
+            };
+            primary
+                .then_with(|| b.proc.vram_mb.cmp(&a.proc.vram_mb))
+                .then_with(|| a.proc.pid.cmp(&b.proc.pid))
+        });
+        rows
+    }
+
+    /// Filter bar and table. `histories` and `idle_mb` line up with
+    /// `snapshots`, the latter giving each GPU's firing idle-reservation
+    /// threshold. Returns a signal picked from a row's context menu (see
+    /// `ui::process_menu`).
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        snapshots: &[GpuSnapshot],
+        histories: &[GpuHistory],
+        idle_mb: &[Option<u64>],
+        live: bool,
+    ) -> Option<ProcessRequest> {
+        let mut request = None;
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("filter by name, user or PID")
+                    .desired_width(200.0),
+            );
+            ui.checkbox(&mut self.all_gpus, "All GPUs");
+            if !self.all_gpus {
+                egui::ComboBox::from_id_salt("process_table_gpu")
+                    .selected_text(format!("GPU {}", self.gpu))
+                    .show_ui(ui, |ui| {
+                        for s in snapshots {
+                            ui.selectable_value(
+                                &mut self.gpu,
+                                s.index,
+                                format!("GPU {} · {}", s.index, s.name),
+                            );
+                        }
+                    });
+            }
+        });
+
+        let rows = self.rows(snapshots, histories, idle_mb);
+        let total: usize = snapshots
+            .iter()
+            .filter(|s| self.all_gpus || s.index == self.gpu)
+            .map(|s| s.all_processes().count())
+            .sum();
+        ui.label(
+            RichText::new(format!("{} of {} processes", rows.len(), total))
+                .size(10.0)
+                .color(ui::TEXT_DIM),
+        );
+        ui.separator();
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("process_table")
+                .striped(true)
+                .num_columns(Column::ALL.len())
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for column in Column::ALL {
+                        let mut title = column.title().to_string();
+                        if column == self.sort {
+                            title.push_str(if self.descending { " ▼" } else { " ▲" });
+                        }
+                        let header = RichText::new(title)
+                            .size(10.0)
+                            .color(ui::TEXT_SECONDARY);
+                        if ui.selectable_label(column == self.sort, header).clicked() {
+                            self.sort_by(column);
+                        }
+                    }
+                    ui.end_row();
+
+                    let cell = |s: String, color| {
+                        RichText::new(s)
+                            .color(color)
+                            .font(FontId::monospace(FONT_SIZE))
+                    };
+                    for row in &rows {
+                        let proc = row.proc;
+                        match row.mig {
+                            Some(mig) => ui
+                                .label(cell(
+                                    format!("{}:{}", row.gpu, mig.gpu_instance_id),
+                                    ui::TEXT_SECONDARY,
+                                ))
+                                .on_hover_text(format!("MIG {}", text::mig_label(mig))),
+                            None => ui.label(cell(row.gpu.to_string(), ui::TEXT_SECONDARY)),
+                        };
+                        ui.label(cell(proc.pid.to_string(), ui::TEXT_SECONDARY));
+                        let resp = ui
+                            .add(
+                                egui::Label::new(cell(
+                                    text::truncate(&row.label, 60).to_string(),
+                                    ui::TEXT_PRIMARY,
+                                ))
+                                .sense(egui::Sense::click()),
+                            )
+                            .on_hover_text(ui::process_details(proc, None));
+                        if let Some(req) = ui::process_menu(&resp, proc, live && !row.remote) {
+                            request = Some(req);
+                        }
+                        let user = if proc.user.is_empty() { "-" } else { &proc.user };
+                        ui.label(cell(user.to_string(), ui::TEXT_SECONDARY));
+                        ui.label(cell(proc.kind.label().to_string(), ui::TEXT_SECONDARY));
+                        ui.label(cell(text::fmt_mb(proc.vram_mb), ui::ACCENT_CYAN));
+                        match row.idle_ms {
+                            Some(ms) => ui
+                                .label(cell(text::fmt_duration(ms), ui::ACCENT_AMBER))
+                                .on_hover_text(ui::idle_reservation_text(proc, ms)),
+                            None => ui.label(cell("-".into(), ui::TEXT_SECONDARY)),
+                        };
+                        for column in [Column::Sm, Column::Mem, Column::Enc, Column::Dec] {
+                            let value = match column.util(proc).flatten() {
+                                Some(v) => format!("{}%", v),
+                                None => "-".into(),
+                            };
+                            ui.label(cell(value, ui::TEXT_SECONDARY));
+                        }
+                        match proc.encoder_sessions.len() {
+                            0 => ui.label(cell("-".into(), ui::TEXT_SECONDARY)),
+                            n => {
+                                let sessions: Vec<String> =
+                                    proc.encoder_sessions.iter().map(text::encoder_session).collect();
+                                ui.label(cell(n.to_string(), ui::NVIDIA_GREEN))
+                                    .on_hover_text(sessions.join("\n"))
+                            }
+                        };
+                        ui.end_row();
+                    }
+                });
+        });
+
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(pid: u32, name: &str, user: &str, vram_mb: u64, sm: u32) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.into(),
+            user: user.into(),
+            vram_mb,
+            utilization: Some(ProcessUtil {
+                sm,
+                mem: 0,
+                enc: 0,
+                dec: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn gpus() -> Vec<GpuSnapshot> {
+        let gpu = |index, processes| GpuSnapshot {
+            processes,
+            ..GpuSnapshot::placeholder(index)
+        };
+        vec![
+            gpu(
+                0,
+                vec![
+                    proc(310, "python", "alice", 4000, 90),
+                    proc(42, "Xorg", "root", 300, 2),
+                    proc(1200, "ollama", "bob", 4000, 40),
+                ],
+            ),
+            gpu(
+                1,
+                vec![
+                    proc(77, "python", "bob", 8000, 10),
+                    proc(5310, "blender", "carol", 4000, 40),
+                ],
+            ),
+        ]
+    }
+
+    /// PIDs `table` lists for `gpus()`, in order
+    fn pids(table: &ProcessTable) -> Vec<u32> {
+        let snapshots = gpus();
+        let rows = table.rows(&snapshots, &[], &[]);
+        rows.iter().map(|r| r.proc.pid).collect()
+    }
+
+    fn all_gpus() -> ProcessTable {
+        ProcessTable {
+            all_gpus: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_one_gpu_or_all_of_them() {
+        let mut table = ProcessTable::default();
+        assert_eq!(pids(&table), [310, 1200, 42]);
+        table.show_gpu(1);
+        assert_eq!(pids(&table), [77, 5310]);
+        table.all_gpus = true;
+        assert_eq!(pids(&table), [77, 310, 1200, 5310, 42]);
+        table.show_gpu(0);
+        assert!(!table.all_gpus);
+        assert_eq!(pids(&table), [310, 1200, 42]);
+    }
+
+    #[test]
+    fn filters_by_name_user_or_pid() {
+        let mut table = all_gpus();
+        let mut matching = |filter: &str| {
+            table.filter = filter.into();
+            pids(&table)
+        };
+        assert_eq!(matching("PYTHON"), [77, 310]);
+        assert_eq!(matching(" bob "), [77, 1200]);
+        // Part of a PID matches too
+        assert_eq!(matching("31"), [310, 5310]);
+        assert!(matching("nobody").is_empty());
+        assert_eq!(matching(""), [77, 310, 1200, 5310, 42]);
+    }
+
+    #[test]
+    fn columns_start_in_their_natural_order() {
+        let mut table = all_gpus();
+        table.sort_by(Column::Pid);
+        assert!(!table.descending);
+        assert_eq!(pids(&table), [42, 77, 310, 1200, 5310]);
+        // Names compare without case
+        table.sort_by(Column::Name);
+        assert_eq!(pids(&table), [5310, 1200, 77, 310, 42]);
+        table.sort_by(Column::Sm);
+        assert!(table.descending);
+        assert_eq!(pids(&table), [310, 1200, 5310, 77, 42]);
+        // Clicking again flips it
+        table.sort_by(Column::Sm);
+        assert_eq!(pids(&table), [42, 77, 1200, 5310, 310]);
+
+        for column in Column::ALL {
+            let mut table = all_gpus();
+            table.sort = Column::Pid;
+            table.sort_by(column);
+            assert_eq!(table.descending, column.descending_by_default());
+        }
+    }
+
+    #[test]
+    fn ties_go_to_the_bigger_process_then_the_lower_pid() {
+        let mut table = all_gpus();
+        table.sort_by(Column::Gpu);
+        assert_eq!(pids(&table), [310, 1200, 42, 77, 5310]);
+        table.sort_by(Column::User);
+        assert_eq!(pids(&table), [310, 77, 1200, 5310, 42]);
+        // Flipping the order leaves the tie-break alone
+        table.sort_by(Column::User);
+        assert_eq!(pids(&table), [42, 5310, 77, 1200, 310]);
+    }
+}
@@ -0,0 +1,462 @@
+//! `nvdash exporter`: serves the widget's numbers in Prometheus text format.
+//!
+//! Every device gauge carries `gpu` (index), `name` and `uuid` labels;
+//! per-process gauges add `pid` and `process`. MIG slice gauges add
+//! `gpu_instance`, `compute_instance`, `profile` and `mig_uuid`, and
+//! processes running in a slice carry its `gpu_instance` and
+//! `compute_instance`. NVLink gauges add `link` and `remote` (the PCI bus
+//! ID at the other end). The energy and error counts only ever grow, so
+//! they are counters, named `_total`; everything else is a gauge. Devices
+//! are read when `/metrics` is scraped, so the exporter costs nothing
+//! between scrapes.
+
+use crate::gpu::{
+    GpuMonitor, GpuSnapshot, MigInstance, NvLinkStatus, ProcessInfo, ThrottleReason,
+};
+use crate::http::{self, Request, Response};
+use std::fmt::Write;
+use std::io;
+use std::net::TcpListener;
+use std::sync::Mutex;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const MB: f64 = 1024.0 * 1024.0;
+
+type Gauge = fn(&GpuSnapshot) -> Option<f64>;
+
+/// Device-level metrics: name, help text, value
+const DEVICE_GAUGES: &[(&str, &str, Gauge)] = &[
+    (
+        "nvdash_gpu_utilization_percent",
+        "GPU core utilization (0-100); absent where the driver can't read it.",
+        |s| s.gpu_util().map(|u| u as f64),
+    ),
+    (
+        "nvdash_memory_utilization_percent",
+        "Memory controller utilization (0-100); absent where the driver can't read it.",
+        |s| s.memory_util().map(|u| u as f64),
+    ),
+    ("nvdash_vram_used_bytes", "Device memory in use.", |s| {
+        Some(s.vram_used_mb as f64 * MB)
+    }),
+    ("nvdash_vram_total_bytes", "Total device memory.", |s| {
+        Some(s.vram_total_mb as f64 * MB)
+    }),
+    ("nvdash_temperature_celsius", "GPU core temperature.", |s| {
+        Some(s.temperature as f64)
+    }),
+    (
+        "nvdash_power_draw_watts",
+        "Current board power draw.",
+        |s| Some(s.power_draw_w),
+    ),
+    ("nvdash_power_limit_watts", "Enforced power limit.", |s| {
+        Some(s.power_limit_w)
+    }),
+    (
+        "nvdash_energy_consumed_joules_total",
+        "Energy used since the driver loaded; absent before Volta.",
+        |s| s.energy_mj.map(|mj| mj as f64 / 1000.0),
+    ),
+    ("nvdash_clock_graphics_mhz", "Graphics clock.", |s| {
+        Some(s.clock_graphics_mhz as f64)
+    }),
+    ("nvdash_clock_memory_mhz", "Memory clock.", |s| {
+        Some(s.clock_memory_mhz as f64)
+    }),
+    ("nvdash_clock_sm_mhz", "SM clock.", |s| {
+        Some(s.clock_sm_mhz as f64)
+    }),
+    (
+        "nvdash_fan_speed_percent",
+        "Fan speed (0-100); absent on fanless boards.",
+        |s| s.fan_speed.map(|f| f as f64),
+    ),
+    (
+        "nvdash_encoder_utilization_percent",
+        "Video encoder (NVENC) utilization (0-100).",
+        |s| s.encoder_util.map(|u| u as f64),
+    ),
+    (
+        "nvdash_decoder_utilization_percent",
+        "Video decoder (NVDEC) utilization (0-100).",
+        |s| s.decoder_util.map(|u| u as f64),
+    ),
+    (
+        "nvdash_pcie_tx_bytes_per_second",
+        "PCIe throughput from the GPU to the host.",
+        |s| s.pcie.map(|p| p.tx_kb_s as f64 * 1024.0),
+    ),
+    (
+        "nvdash_pcie_rx_bytes_per_second",
+        "PCIe throughput from the host to the GPU.",
+        |s| s.pcie.map(|p| p.rx_kb_s as f64 * 1024.0),
+    ),
+    ("nvdash_pcie_link_gen", "Current PCIe link generation.", |s| {
+        s.pcie.map(|p| p.link_gen as f64)
+    }),
+    ("nvdash_pcie_link_gen_max", "Maximum PCIe link generation.", |s| {
+        s.pcie.map(|p| p.max_link_gen as f64)
+    }),
+    ("nvdash_pcie_link_width", "Current PCIe link width (lanes).", |s| {
+        s.pcie.map(|p| p.link_width as f64)
+    }),
+    (
+        "nvdash_pcie_link_width_max",
+        "Maximum PCIe link width (lanes).",
+        |s| s.pcie.map(|p| p.max_link_width as f64),
+    ),
+    (
+        "nvdash_ecc_corrected_volatile_errors_total",
+        "Corrected ECC errors since the driver loaded; absent without ECC.",
+        |s| s.ecc.map(|e| e.volatile_corrected as f64),
+    ),
+    (
+        "nvdash_ecc_uncorrected_volatile_errors_total",
+        "Uncorrected ECC errors since the driver loaded; absent without ECC.",
+        |s| s.ecc.map(|e| e.volatile_uncorrected as f64),
+    ),
+    (
+        "nvdash_ecc_corrected_aggregate_errors_total",
+        "Corrected ECC errors over the board's lifetime; absent without ECC.",
+        |s| s.ecc.map(|e| e.aggregate_corrected as f64),
+    ),
+    (
+        "nvdash_ecc_uncorrected_aggregate_errors_total",
+        "Uncorrected ECC errors over the board's lifetime; absent without ECC.",
+        |s| s.ecc.map(|e| e.aggregate_uncorrected as f64),
+    ),
+];
+
+type MigGauge = fn(&MigInstance) -> f64;
+
+/// Per-slice gauges on MIG-partitioned devices
+const MIG_GAUGES: &[(&str, &str, MigGauge)] = &[
+    (
+        "nvdash_mig_vram_used_bytes",
+        "Memory in use in a MIG slice.",
+        |m| m.vram_used_mb as f64 * MB,
+    ),
+    (
+        "nvdash_mig_vram_total_bytes",
+        "Memory of a MIG slice.",
+        |m| m.vram_total_mb as f64 * MB,
+    ),
+];
+
+type NvLinkGauge = fn(&NvLinkStatus) -> Option<f64>;
+
+/// Per-link metrics on devices with NVLink
+const NVLINK_GAUGES: &[(&str, &str, NvLinkGauge)] = &[
+    ("nvdash_nvlink_active", "Whether an NVLink is up (1) or not (0).", |l| {
+        Some(l.active as u8 as f64)
+    }),
+    ("nvdash_nvlink_version", "NVLink version; absent while down.", |l| {
+        l.active.then_some(l.version as f64)
+    }),
+    (
+        "nvdash_nvlink_tx_bytes_per_second",
+        "NVLink data throughput sent; absent where the driver doesn't count it.",
+        |l| l.tx_kb_s.map(|kb| kb as f64 * 1024.0),
+    ),
+    (
+        "nvdash_nvlink_rx_bytes_per_second",
+        "NVLink data throughput received; absent where the driver doesn't count it.",
+        |l| l.rx_kb_s.map(|kb| kb as f64 * 1024.0),
+    ),
+    (
+        "nvdash_nvlink_crc_errors_total",
+        "NVLink flit and data CRC errors since the driver loaded.",
+        |l| Some(l.crc_errors as f64),
+    ),
+    (
+        "nvdash_nvlink_replay_errors_total",
+        "NVLink replay errors since the driver loaded.",
+        |l| Some(l.replay_errors as f64),
+    ),
+    (
+        "nvdash_nvlink_recovery_errors_total",
+        "NVLink recovery errors since the driver loaded.",
+        |l| Some(l.recovery_errors as f64),
+    ),
+];
+
+type ProcessGauge = fn(&ProcessInfo) -> Option<f64>;
+
+/// Per-process gauges, same layout as `DEVICE_GAUGES`
+const PROCESS_GAUGES: &[(&str, &str, ProcessGauge)] = &[
+    (
+        "nvdash_process_vram_used_bytes",
+        "Device memory held by a process.",
+        |p| Some(p.vram_mb as f64 * MB),
+    ),
+    (
+        "nvdash_process_sm_utilization_percent",
+        "Share of SM time used by a process (0-100).",
+        |p| p.utilization.map(|u| u.sm as f64),
+    ),
+    (
+        "nvdash_process_memory_utilization_percent",
+        "Share of memory controller time used by a process (0-100).",
+        |p| p.utilization.map(|u| u.mem as f64),
+    ),
+    (
+        "nvdash_process_encoder_utilization_percent",
+        "Share of video encoder time used by a process (0-100).",
+        |p| p.utilization.map(|u| u.enc as f64),
+    ),
+    (
+        "nvdash_process_decoder_utilization_percent",
+        "Share of video decoder time used by a process (0-100).",
+        |p| p.utilization.map(|u| u.dec as f64),
+    ),
+    (
+        "nvdash_process_encoder_sessions",
+        "Hardware encoder sessions a process has open.",
+        |p| Some(p.encoder_sessions.len() as f64),
+    ),
+];
+
+/// `# HELP` and `# TYPE` lines for a metric; the tables name counters, and
+/// only counters, with `_total`
+fn describe(out: &mut String, name: &str, help: &str) {
+    let kind = if name.ends_with("_total") {
+        "counter"
+    } else {
+        "gauge"
+    };
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escape a label value per the exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn device_labels(s: &GpuSnapshot) -> String {
+    format!(
+        "gpu=\"{}\",name=\"{}\",uuid=\"{}\"",
+        s.index,
+        escape(&s.name),
+        escape(&s.uuid)
+    )
+}
+
+fn mig_labels(m: &MigInstance) -> String {
+    format!(
+        "gpu_instance=\"{}\",compute_instance=\"{}\"",
+        m.gpu_instance_id, m.compute_instance_id
+    )
+}
+
+/// Render every metric for the given snapshots
+pub fn render(snapshots: &[GpuSnapshot]) -> String {
+    let mut out = String::new();
+
+    // writeln! into a String cannot fail
+    let _ = writeln!(
+        out,
+        "# HELP nvdash_gpu_info Static device information; always 1."
+    );
+    let _ = writeln!(out, "# TYPE nvdash_gpu_info gauge");
+    for s in snapshots {
+        let _ = writeln!(
+            out,
+            "nvdash_gpu_info{{{},driver_version=\"{}\",cuda_version=\"{}\"}} 1",
+            device_labels(s),
+            escape(&s.driver_version),
+            escape(&s.cuda_version)
+        );
+    }
+
+    for (name, help, gauge) in DEVICE_GAUGES {
+        describe(&mut out, name, help);
+        for s in snapshots {
+            if let Some(v) = gauge(s) {
+                let _ = writeln!(out, "{}{{{}}} {}", name, device_labels(s), v);
+            }
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP nvdash_throttle_reason_active Whether a clock throttle reason is active (1) or not (0)."
+    );
+    let _ = writeln!(out, "# TYPE nvdash_throttle_reason_active gauge");
+    for s in snapshots {
+        for reason in ThrottleReason::ALL {
+            // Same names as the JSON output
+            let label = serde_json::to_value(reason).unwrap_or_default();
+            let active = s.throttle_reasons.contains(&reason) as u8;
+            let _ = writeln!(
+                out,
+                "nvdash_throttle_reason_active{{{},reason=\"{}\"}} {}",
+                device_labels(s),
+                label.as_str().unwrap_or_default(),
+                active
+            );
+        }
+    }
+
+    for (name, help, gauge) in MIG_GAUGES {
+        describe(&mut out, name, help);
+        for s in snapshots {
+            for m in &s.mig_instances {
+                let _ = writeln!(
+                    out,
+                    "{}{{{},{},profile=\"{}\",mig_uuid=\"{}\"}} {}",
+                    name,
+                    device_labels(s),
+                    mig_labels(m),
+                    escape(&m.profile),
+                    escape(&m.uuid),
+                    gauge(m)
+                );
+            }
+        }
+    }
+
+    for (name, help, gauge) in NVLINK_GAUGES {
+        describe(&mut out, name, help);
+        for s in snapshots {
+            for l in &s.nvlinks {
+                if let Some(v) = gauge(l) {
+                    let _ = writeln!(
+                        out,
+                        "{}{{{},link=\"{}\",remote=\"{}\"}} {}",
+                        name,
+                        device_labels(s),
+                        l.link,
+                        escape(&l.remote_bus_id),
+                        v
+                    );
+                }
+            }
+        }
+    }
+
+    for (name, help, gauge) in PROCESS_GAUGES {
+        describe(&mut out, name, help);
+        for s in snapshots {
+            for (p, mig) in s.all_processes() {
+                if let Some(v) = gauge(p) {
+                    let slice = mig.map(|m| format!(",{}", mig_labels(m))).unwrap_or_default();
+                    let _ = writeln!(
+                        out,
+                        "{}{{{}{},pid=\"{}\",process=\"{}\"}} {}",
+                        name,
+                        device_labels(s),
+                        slice,
+                        p.pid,
+                        escape(&p.name),
+                        v
+                    );
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Serve `/metrics` on `listener` until the process is killed
+pub fn run(monitor: &mut GpuMonitor, listener: TcpListener) -> io::Result<()> {
+    // Concurrent scrapes take turns reading the devices
+    let monitor = Mutex::new(monitor);
+    http::serve(listener, |req: &Request| match req.path.as_str() {
+        "/metrics" => match monitor.lock() {
+            Ok(mut monitor) => Response::ok(CONTENT_TYPE, render(&monitor.snapshot_readable())),
+            Err(_) => Response::unavailable(),
+        },
+        "/" => Response::ok(
+            "text/html; charset=utf-8",
+            "<html><body><h1>nvdash exporter</h1><a href=\"/metrics\">/metrics</a></body></html>\n"
+                .into(),
+        ),
+        _ => Response::not_found(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimConfig;
+
+    /// Start an exporter over simulated GPUs on a free loopback port
+    fn exporter(gpus: u32) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut monitor = GpuMonitor::simulated(SimConfig {
+                gpus,
+                // Busy enough that every GPU has processes
+                job_interval_s: 1.0,
+                ..Default::default()
+            });
+            run(&mut monitor, listener)
+        });
+        addr
+    }
+
+    #[test]
+    fn serves_metrics_over_http() {
+        let addr = exporter(2);
+        let body = http::get(&addr, "/metrics").unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        let has = |line: &str| lines.contains(&line);
+
+        let help = "# HELP nvdash_gpu_utilization_percent GPU core utilization (0-100)";
+        assert!(lines.iter().any(|l| l.starts_with(help)));
+        assert!(has("# TYPE nvdash_gpu_utilization_percent gauge"));
+        assert!(has("# TYPE nvdash_energy_consumed_joules_total counter"));
+        assert!(has("# TYPE nvdash_nvlink_crc_errors_total counter"));
+        assert!(has("# TYPE nvdash_process_vram_used_bytes gauge"));
+
+        // Every sample follows its metric's TYPE line
+        let mut typed = Vec::new();
+        for line in &lines {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                typed.push(rest.split(' ').next().unwrap());
+            } else if !line.starts_with('#') {
+                let name = line.split('{').next().unwrap();
+                assert_eq!(typed.last(), Some(&name), "untyped sample: {}", line);
+            }
+        }
+
+        for gpu in 0..2 {
+            let labels = format!(
+                "gpu=\"{}\",name=\"NVIDIA GeForce RTX 4090 (sim)\",uuid=\"GPU-5151d000-0000-4000-8000-{:012x}\"",
+                gpu, gpu
+            );
+            assert!(has(&format!(
+                "nvdash_gpu_info{{{},driver_version=\"sim\",cuda_version=\"12.4\"}} 1",
+                labels
+            )));
+            assert!(lines.iter().any(|l| l.starts_with(&format!(
+                "nvdash_energy_consumed_joules_total{{{}}} ",
+                labels
+            ))));
+            assert!(lines.iter().any(|l| l.starts_with(&format!(
+                "nvdash_nvlink_active{{{},link=\"0\",remote=\"00000000:{:02X}:00.0\"}} 1",
+                labels,
+                (gpu ^ 1) + 1
+            ))));
+
+            let process = format!("nvdash_process_vram_used_bytes{{{},pid=\"", labels);
+            let rows: Vec<&&str> = lines.iter().filter(|l| l.starts_with(&process)).collect();
+            assert!(!rows.is_empty(), "no processes on GPU {}", gpu);
+            assert!(rows.iter().all(|l| l.contains("\",process=\"")));
+        }
+
+        assert!(http::get(&addr, "/nope").is_err());
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
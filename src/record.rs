@@ -0,0 +1,310 @@
+//! Recording and replay of snapshot streams.
+//!
+//! A recording is a JSON Lines file: one [`Frame`] per poll, holding the
+//! wall-clock timestamp and every device's [`GpuSnapshot`]. Frames are
+//! flushed as they are written so a crash loses at most the last line.
+
+use crate::gpu::GpuSnapshot;
+use crate::history::{GpuHistory, MAX_WINDOW_MS};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Playback speeds offered in the replay bar
+pub const SPEEDS: [f64; 7] = [0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 60.0];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frame {
+    /// Unix time in milliseconds
+    pub timestamp_ms: i64,
+    pub gpus: Vec<GpuSnapshot>,
+}
+
+impl Frame {
+    /// Stamp a set of snapshots with the current time
+    pub fn now(gpus: Vec<GpuSnapshot>) -> Self {
+        Self {
+            timestamp_ms: chrono::Utc::now().timestamp_millis(),
+            gpus,
+        }
+    }
+
+    /// One past the highest GPU index in the frame
+    pub fn device_count(&self) -> usize {
+        self.gpus
+            .iter()
+            .map(|g| g.index as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The GPUs laid out by index over `count` places, with placeholders
+    /// for any this frame is missing
+    pub fn gpus_by_index(&self, count: usize) -> Vec<GpuSnapshot> {
+        let mut gpus: Vec<GpuSnapshot> = (0..count as u32).map(GpuSnapshot::placeholder).collect();
+        for snap in &self.gpus {
+            if let Some(slot) = gpus.get_mut(snap.index as usize) {
+                *slot = snap.clone();
+            }
+        }
+        gpus
+    }
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+    frames: u64,
+}
+
+impl Recorder {
+    /// Start a recording at `path`, which must not exist yet: an earlier
+    /// recording is never overwritten
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    e.kind(),
+                    "file already exists; choose another name or remove it",
+                ),
+                _ => e,
+            })?;
+        let out = BufWriter::new(file);
+        Ok(Self {
+            path,
+            out,
+            frames: 0,
+        })
+    }
+
+    /// `nvdash-YYYYmmdd-HHMMSS.jsonl` in the working directory
+    pub fn default_path() -> PathBuf {
+        PathBuf::from(format!(
+            "nvdash-{}.jsonl",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn record(&mut self, snapshots: &[GpuSnapshot]) -> io::Result<()> {
+        let frame = Frame::now(snapshots.to_vec());
+        serde_json::to_writer(&mut self.out, &frame)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Read every frame from a recording. A truncated final line (e.g. the
+/// recorder was killed mid-write) is ignored; any other bad line is an error.
+pub fn load(path: &Path) -> io::Result<Vec<Frame>> {
+    let reader = BufReader::new(File::open(path)?);
+    let lines: Vec<String> = reader.lines().collect::<io::Result<_>>()?;
+    let mut frames = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Frame>(line) {
+            Ok(frame) => frames.push(frame),
+            Err(_) if i + 1 == lines.len() => break,
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: {}", path.display(), i + 1, e),
+                ))
+            }
+        }
+    }
+    if frames.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: recording has no frames", path.display()),
+        ));
+    }
+    Ok(frames)
+}
+
+/// Plays a loaded recording back against a virtual clock
+pub struct Player {
+    frames: Vec<Frame>,
+    /// One past the highest GPU index in any frame
+    devices: usize,
+    pos: usize,
+    /// Playhead, in ms since the first frame
+    clock_ms: f64,
+    pub playing: bool,
+    pub speed: f64,
+    last_tick: Instant,
+}
+
+impl Player {
+    pub fn new(frames: Vec<Frame>) -> Self {
+        assert!(!frames.is_empty(), "cannot play an empty recording");
+        // A GPU that failed to read drops out of a frame; the others keep
+        // their index, so lay frames out by it rather than by position
+        let devices = frames.iter().map(Frame::device_count).max().unwrap_or(0);
+        Self {
+            frames,
+            devices,
+            pos: 0,
+            clock_ms: 0.0,
+            playing: true,
+            speed: 1.0,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn current(&self) -> &Frame {
+        &self.frames[self.pos]
+    }
+
+    pub fn device_count(&self) -> usize {
+        self.devices
+    }
+
+    /// Every GPU at the playhead, positioned by index
+    pub fn snapshots(&self) -> Vec<GpuSnapshot> {
+        self.current().gpus_by_index(self.devices)
+    }
+
+    pub fn duration_ms(&self) -> f64 {
+        (self.frames[self.frames.len() - 1].timestamp_ms - self.frames[0].timestamp_ms) as f64
+    }
+
+    pub fn position_ms(&self) -> f64 {
+        self.clock_ms
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos + 1 == self.frames.len()
+    }
+
+    /// Advance the playhead by wall time elapsed since the last tick.
+    /// Returns the frames passed over, oldest first.
+    pub fn tick(&mut self) -> &[Frame] {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick).as_secs_f64() * 1000.0;
+        self.last_tick = now;
+        if !self.playing {
+            return &[];
+        }
+        self.clock_ms = (self.clock_ms + elapsed * self.speed).min(self.duration_ms());
+        let start = self.pos;
+        let t0 = self.frames[0].timestamp_ms;
+        while !self.at_end()
+            && (self.frames[self.pos + 1].timestamp_ms - t0) as f64 <= self.clock_ms
+        {
+            self.pos += 1;
+        }
+        if self.at_end() {
+            self.playing = false;
+        }
+        &self.frames[start + 1..self.pos + 1]
+    }
+
+    pub fn seek(&mut self, ms: f64) {
+        self.clock_ms = ms.clamp(0.0, self.duration_ms());
+        let t0 = self.frames[0].timestamp_ms;
+        let target = t0 + self.clock_ms as i64;
+        self.pos = self
+            .frames
+            .partition_point(|f| f.timestamp_ms <= target)
+            .saturating_sub(1);
+    }
+
+    /// Histories as they would have looked at the playhead
+    pub fn histories(&self) -> Vec<GpuHistory> {
+        let oldest = self.current().timestamp_ms - MAX_WINDOW_MS;
+        let start = self.frames.partition_point(|f| f.timestamp_ms < oldest);
+        let mut histories: Vec<GpuHistory> = (0..self.device_count())
+            .map(|_| GpuHistory::new())
+            .collect();
+        for frame in &self.frames[start..=self.pos] {
+            for snap in &frame.gpus {
+                if let Some(h) = histories.get_mut(snap.index as usize) {
+                    h.push_at(frame.timestamp_ms, snap);
+                }
+            }
+        }
+        histories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpu(index: u32) -> GpuSnapshot {
+        GpuSnapshot {
+            uuid: format!("GPU-{}", index),
+            vram_used_mb: 100 * (index as u64 + 1),
+            ..GpuSnapshot::placeholder(index)
+        }
+    }
+
+    fn frame(timestamp_ms: i64, indices: &[u32]) -> Frame {
+        Frame {
+            timestamp_ms,
+            gpus: indices.iter().map(|&i| gpu(i)).collect(),
+        }
+    }
+
+    #[test]
+    fn gpus_keep_their_place_when_frames_change_shape() {
+        // GPU 0 failed to read in the second frame; GPU 2 showed up later
+        let mut player = Player::new(vec![
+            frame(0, &[0, 1]),
+            frame(1000, &[1]),
+            frame(2000, &[0, 1, 2]),
+        ]);
+        assert_eq!(player.device_count(), 3);
+
+        player.seek(1000.0);
+        let uuids: Vec<String> = player.snapshots().into_iter().map(|s| s.uuid).collect();
+        assert_eq!(uuids, ["", "GPU-1", ""]);
+
+        player.seek(2000.0);
+        let histories = player.histories();
+        assert_eq!(histories.len(), 3);
+        let latest: Vec<Option<f64>> = histories
+            .iter()
+            .map(|h| {
+                h.vram_used
+                    .samples(MAX_WINDOW_MS, 2000)
+                    .last()
+                    .map(|s| s.avg)
+            })
+            .collect();
+        assert_eq!(latest, [Some(100.0), Some(200.0), Some(300.0)]);
+    }
+
+    #[test]
+    fn never_records_over_an_existing_file() {
+        let path = std::env::temp_dir().join(format!("nvdash-{}-exists.jsonl", std::process::id()));
+        std::fs::write(&path, "keep me\n").unwrap();
+        let err = Recorder::create(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me\n");
+
+        std::fs::remove_file(&path).unwrap();
+        let mut rec = Recorder::create(&path).unwrap();
+        rec.record(&[gpu(0)]).unwrap();
+        assert_eq!(load(&path).unwrap().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
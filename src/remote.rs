@@ -0,0 +1,413 @@
+//! GPUs on other machines, read from an `nvdash agent` (see `agent`).
+//!
+//! Each agent gets a thread that asks for the frames it hasn't seen yet at
+//! the poll interval; the first request takes everything the agent holds,
+//! so the sparklines come up with history. The window drains the frames
+//! each poll with [`merge`] and lists the agent's GPUs after its own.
+
+use crate::agent;
+use crate::gpu::GpuSnapshot;
+use crate::history::GpuHistory;
+use crate::http;
+use crate::record::Frame;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Inbox {
+    frames: Vec<Frame>,
+    /// Why the last request failed; cleared by the next good one
+    error: Option<String>,
+}
+
+impl Inbox {
+    /// Queue `frame` for the window. Frames it hasn't taken are kept no
+    /// longer than the agent keeps them, so a window that stops polling
+    /// (minimised, say) doesn't pile them up.
+    fn push(&mut self, frame: Frame) {
+        let oldest = frame.timestamp_ms - agent::RETENTION_MS;
+        self.frames.push(frame);
+        let stale = self.frames.partition_point(|f| f.timestamp_ms < oldest);
+        self.frames.drain(..stale);
+    }
+}
+
+pub struct Remote {
+    /// `host:port` as given
+    pub addr: String,
+    /// GPUs the agent reported last; 0 until it first answers
+    pub gpus: usize,
+    inbox: Arc<Mutex<Inbox>>,
+}
+
+impl Remote {
+    /// Start fetching from the agent at `addr`; stops when dropped
+    pub fn connect(addr: String, interval: Duration) -> Self {
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+        let weak = Arc::downgrade(&inbox);
+        let thread_addr = addr.clone();
+        std::thread::spawn(move || fetch(&thread_addr, interval, weak));
+        Self {
+            addr,
+            gpus: 0,
+            inbox,
+        }
+    }
+
+    /// Frames received since the last call, oldest first, and the error
+    /// keeping the agent from answering, if any
+    pub fn take(&self) -> (Vec<Frame>, Option<String>) {
+        match self.inbox.lock() {
+            Ok(mut inbox) => (std::mem::take(&mut inbox.frames), inbox.error.clone()),
+            Err(_) => (Vec::new(), None),
+        }
+    }
+}
+
+/// Take in what the agents sent since the last poll. Each agent's GPUs
+/// follow the `local` ones in `snapshots` and `histories`, in the order the
+/// agents were given, and are numbered on from them; within an agent they
+/// are placed by the agent's own index. Returns the last error an agent
+/// reported.
+pub fn merge(
+    remotes: &mut [Remote],
+    local: usize,
+    snapshots: &mut Vec<GpuSnapshot>,
+    histories: &mut Vec<GpuHistory>,
+) -> Option<String> {
+    let mut error = None;
+    let mut offset = local;
+    for remote in remotes {
+        let (frames, e) = remote.take();
+        if let Some(e) = e {
+            error = Some(format!("Agent {}: {}", remote.addr, e));
+        }
+        let Some(last) = frames.last() else {
+            offset += remote.gpus;
+            continue;
+        };
+
+        // The agent's first answer, or a GPU coming or going there
+        let count = last.device_count();
+        if count != remote.gpus {
+            let end = offset + remote.gpus;
+            if count > remote.gpus {
+                let added = count - remote.gpus;
+                histories.splice(end..end, (0..added).map(|_| GpuHistory::new()));
+                snapshots.splice(
+                    end..end,
+                    (0..added).map(|i| GpuSnapshot::placeholder((end + i) as u32)),
+                );
+            } else {
+                histories.drain(offset + count..end);
+                snapshots.drain(offset + count..end);
+            }
+            remote.gpus = count;
+        }
+
+        let histories = &mut histories[offset..offset + count];
+        for frame in &frames {
+            for snap in &frame.gpus {
+                if let Some(history) = histories.get_mut(snap.index as usize) {
+                    history.push_at(frame.timestamp_ms, snap);
+                }
+            }
+        }
+        for (slot, snap) in snapshots[offset..offset + count]
+            .iter_mut()
+            .zip(last.gpus_by_index(count))
+        {
+            *slot = GpuSnapshot {
+                host: remote.addr.clone(),
+                ..snap
+            };
+        }
+        offset += count;
+    }
+    // Number on from the local GPUs; a resized agent shifts the ones after it
+    for (i, snap) in snapshots.iter_mut().enumerate().skip(local) {
+        snap.index = i as u32;
+    }
+    error
+}
+
+fn fetch(addr: &str, interval: Duration, inbox: Weak<Mutex<Inbox>>) {
+    let mut since = 0;
+    loop {
+        let started = Instant::now();
+        let result = http::get(addr, &format!("/frames?since={}", since));
+        let Some(inbox) = inbox.upgrade() else {
+            return;
+        };
+        let Ok(mut inbox) = inbox.lock() else {
+            return;
+        };
+        match result {
+            Ok(body) => {
+                inbox.error = None;
+                for line in body.lines().filter(|l| !l.trim().is_empty()) {
+                    match serde_json::from_str::<Frame>(line) {
+                        Ok(frame) => {
+                            since = since.max(frame.timestamp_ms);
+                            inbox.push(frame);
+                        }
+                        Err(e) => inbox.error = Some(format!("bad frame: {}", e)),
+                    }
+                }
+            }
+            Err(e) => inbox.error = Some(e.to_string()),
+        }
+        drop(inbox);
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::{GpuMonitor, ProcessInfo};
+    use crate::sim::SimConfig;
+    use std::net::TcpListener;
+
+    /// Start an agent over simulated GPUs on a free loopback port
+    fn agent(gpus: u32) -> String {
+        agent_sharing(gpus, false)
+    }
+
+    fn agent_sharing(gpus: u32, share_cmdline: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut monitor = GpuMonitor::simulated(SimConfig {
+                gpus,
+                job_interval_s: 0.2,
+                ..Default::default()
+            });
+            agent::run(
+                &mut monitor,
+                listener,
+                Duration::from_millis(20),
+                share_cmdline,
+            )
+        });
+        addr
+    }
+
+    /// The next frames `remote` receives
+    fn next_frames(remote: &Remote) -> Vec<Frame> {
+        for _ in 0..200 {
+            let (frames, error) = remote.take();
+            assert_eq!(error, None);
+            if !frames.is_empty() {
+                return frames;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no frames from {}", remote.addr);
+    }
+
+    fn now_ms() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    /// A remote fed by hand rather than by an agent
+    fn fed(addr: &str) -> Remote {
+        Remote {
+            addr: addr.into(),
+            gpus: 0,
+            inbox: Arc::new(Mutex::new(Inbox::default())),
+        }
+    }
+
+    fn send(remote: &Remote, t_ms: i64, gpus: u32) {
+        let gpus = (0..gpus)
+            .map(|i| GpuSnapshot {
+                uuid: format!("{}-{}", remote.addr, i),
+                ..GpuSnapshot::placeholder(i)
+            })
+            .collect();
+        let mut inbox = remote.inbox.lock().unwrap();
+        inbox.push(Frame {
+            timestamp_ms: t_ms,
+            gpus,
+        });
+    }
+
+    #[test]
+    fn backfills_then_follows_an_agent() {
+        let addr = agent(2);
+        // Let the agent build up some history first
+        std::thread::sleep(Duration::from_millis(300));
+        let connected_ms = now_ms();
+        let remote = Remote::connect(addr.clone(), Duration::from_millis(50));
+
+        let backfill = next_frames(&remote);
+        assert!(backfill.len() >= 5, "{} frames", backfill.len());
+        assert!(backfill[0].timestamp_ms < connected_ms - 100);
+        for frame in &backfill {
+            let indices: Vec<u32> = frame.gpus.iter().map(|g| g.index).collect();
+            assert_eq!(indices, [0, 1]);
+        }
+
+        // Later requests only bring what is new
+        let mut newest = backfill.last().unwrap().timestamp_ms;
+        for _ in 0..3 {
+            for frame in next_frames(&remote) {
+                assert!(frame.timestamp_ms > newest);
+                newest = frame.timestamp_ms;
+            }
+        }
+
+        let body = http::get(&addr, &format!("/frames?since={}", newest)).unwrap();
+        for line in body.lines() {
+            let frame: Frame = serde_json::from_str(line).unwrap();
+            assert!(frame.timestamp_ms > newest);
+        }
+        let latest: Frame = serde_json::from_str(&http::get(&addr, "/snapshot").unwrap()).unwrap();
+        assert_eq!(latest.gpus.len(), 2);
+        assert!(http::get(&addr, "/nope").is_err());
+    }
+
+    /// The processes in the first frame from `addr` that has any
+    fn served_processes(addr: &str) -> Vec<ProcessInfo> {
+        for _ in 0..200 {
+            if let Ok(body) = http::get(addr, "/snapshot") {
+                let frame: Frame = serde_json::from_str(&body).unwrap();
+                let procs: Vec<ProcessInfo> =
+                    frame.gpus.into_iter().flat_map(|g| g.processes).collect();
+                if !procs.is_empty() {
+                    return procs;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no processes from {}", addr);
+    }
+
+    #[test]
+    fn command_lines_stay_private_unless_shared() {
+        for proc in served_processes(&agent(1)) {
+            assert!(!proc.name.is_empty());
+            assert_eq!((proc.cmdline.as_str(), proc.cwd.as_str()), ("", ""));
+        }
+        for proc in served_processes(&agent_sharing(1, true)) {
+            assert!(!proc.cmdline.is_empty());
+            assert!(!proc.cwd.is_empty());
+        }
+    }
+
+    #[test]
+    fn agents_gpus_follow_the_local_ones() {
+        let (two, one) = (agent(2), agent(1));
+        let mut remotes = vec![
+            // Nothing listens on port 1
+            Remote::connect("127.0.0.1:1".into(), Duration::from_millis(50)),
+            Remote::connect(two.clone(), Duration::from_millis(50)),
+            Remote::connect(one.clone(), Duration::from_millis(50)),
+        ];
+        let mut snapshots = vec![GpuSnapshot::placeholder(0)];
+        let mut histories = vec![GpuHistory::new()];
+
+        let mut error = None;
+        for _ in 0..200 {
+            error = error.or(merge(&mut remotes, 1, &mut snapshots, &mut histories));
+            if remotes[1].gpus == 2 && remotes[2].gpus == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let gpus: Vec<usize> = remotes.iter().map(|r| r.gpus).collect();
+        assert_eq!(gpus, [0, 2, 1]);
+        assert!(error.unwrap().starts_with("Agent 127.0.0.1:1: "));
+
+        let layout: Vec<(u32, &str)> = snapshots
+            .iter()
+            .map(|s| (s.index, s.host.as_str()))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                (0, ""),
+                (1, two.as_str()),
+                (2, two.as_str()),
+                (3, one.as_str())
+            ]
+        );
+        // The agents' own numbering is kept in their GPUs' UUIDs
+        assert!(snapshots[1].uuid.ends_with("000000000000"));
+        assert!(snapshots[2].uuid.ends_with("000000000001"));
+        assert!(snapshots[3].uuid.ends_with("000000000000"));
+
+        // Histories were filled from the backfill
+        assert_eq!(histories.len(), 4);
+        assert!(histories[1..]
+            .iter()
+            .all(|h| h.vram_used.latest_ms().is_some()));
+    }
+
+    #[test]
+    fn a_resized_agent_shifts_the_ones_after_it() {
+        let mut remotes = vec![fed("a:1"), fed("b:1")];
+        let mut snapshots = vec![GpuSnapshot::placeholder(0)];
+        let mut histories = vec![GpuHistory::new()];
+        let mut layout = |remotes: &mut [Remote]| {
+            merge(remotes, 1, &mut snapshots, &mut histories);
+            snapshots
+                .iter()
+                .map(|s| (s.index, s.uuid.clone()))
+                .collect::<Vec<_>>()
+        };
+        let gpu = |index: u32, uuid: &str| (index, uuid.to_string());
+
+        send(&remotes[0], 1_000, 3);
+        send(&remotes[1], 1_000, 1);
+        assert_eq!(
+            layout(&mut remotes),
+            [
+                gpu(0, ""),
+                gpu(1, "a:1-0"),
+                gpu(2, "a:1-1"),
+                gpu(3, "a:1-2"),
+                gpu(4, "b:1-0")
+            ]
+        );
+
+        // One of a's GPUs is gone; b moves up. A quiet agent keeps its place.
+        send(&remotes[0], 2_000, 2);
+        assert_eq!(
+            layout(&mut remotes),
+            [
+                gpu(0, ""),
+                gpu(1, "a:1-0"),
+                gpu(2, "a:1-1"),
+                gpu(3, "b:1-0")
+            ]
+        );
+        assert_eq!(remotes[0].gpus, 2);
+
+        // b grows
+        send(&remotes[1], 3_000, 2);
+        assert_eq!(
+            layout(&mut remotes),
+            [
+                gpu(0, ""),
+                gpu(1, "a:1-0"),
+                gpu(2, "a:1-1"),
+                gpu(3, "b:1-0"),
+                gpu(4, "b:1-1")
+            ]
+        );
+    }
+
+    #[test]
+    fn an_unread_inbox_keeps_only_what_the_agent_would() {
+        let remote = fed("a:1");
+        let minute = 60_000;
+        for t in 0..30 {
+            send(&remote, t * minute, 1);
+        }
+        let (frames, _) = remote.take();
+        let kept: Vec<i64> = frames.iter().map(|f| f.timestamp_ms / minute).collect();
+        assert_eq!(kept, (19..30).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,437 @@
+//! Named sessions: per-GPU statistics gathered between a start and a stop,
+//! summarized as a report to paste into experiment notes.
+//!
+//! Statistics are time-weighted: each snapshot's values count for the time
+//! since the previous one, so changing the poll rate mid-run doesn't skew
+//! the means. A session can run in the window (started from the bottom bar
+//! or the tray) or from the command line with `nvdash session`.
+
+use crate::energy::{EnergyMeter, EnergyRates, J_PER_KWH};
+use crate::gpu::{GpuMonitor, GpuSnapshot, IDLE_UTIL};
+use crate::text;
+use serde::Serialize;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Longest gap between snapshots that counts toward the statistics; beyond
+/// it the GPU wasn't being watched
+const MAX_GAP_MS: i64 = 60 * 1000;
+
+/// Running totals for one GPU
+struct Tally {
+    index: u32,
+    name: String,
+    uuid: String,
+    /// Agent the GPU is on; empty for local ones
+    host: String,
+    vram_total_mb: u64,
+    /// Time spent at each utilization percent, for the mean and percentiles
+    util_ms: [i64; 101],
+    peak_vram_mb: u64,
+    throttled_ms: i64,
+    idle_ms: i64,
+    energy: EnergyMeter,
+}
+
+impl Tally {
+    fn new(snapshot: &GpuSnapshot) -> Self {
+        Self {
+            index: snapshot.index,
+            name: snapshot.name.clone(),
+            uuid: snapshot.uuid.clone(),
+            host: snapshot.host.clone(),
+            vram_total_mb: snapshot.vram_total_mb,
+            util_ms: [0; 101],
+            peak_vram_mb: 0,
+            throttled_ms: 0,
+            idle_ms: 0,
+            energy: EnergyMeter::default(),
+        }
+    }
+
+    /// Whether `snapshot` is this GPU. Indices repeat across agents and
+    /// shift as they come and go, so go by UUID where the device has one.
+    fn tracks(&self, snapshot: &GpuSnapshot) -> bool {
+        self.host == snapshot.host
+            && if snapshot.uuid.is_empty() {
+                self.uuid.is_empty() && self.index == snapshot.index
+            } else {
+                self.uuid == snapshot.uuid
+            }
+    }
+
+    fn push(&mut self, t_ms: i64, dt_ms: i64, snapshot: &GpuSnapshot) {
+        // Time with unknown utilization counts toward neither the mean
+        // nor the idle fraction
+        if let Some(util) = snapshot.gpu_util() {
+            self.util_ms[util.min(100) as usize] += dt_ms;
+            if util < IDLE_UTIL {
+                self.idle_ms += dt_ms;
+            }
+        }
+        self.peak_vram_mb = self.peak_vram_mb.max(snapshot.vram_used_mb);
+        if snapshot.throttled().next().is_some() {
+            self.throttled_ms += dt_ms;
+        }
+        self.energy.push_at(t_ms, snapshot);
+    }
+
+    fn observed_ms(&self) -> i64 {
+        self.util_ms.iter().sum()
+    }
+
+    /// Utilization at or below which `share` of the time was spent
+    fn percentile(&self, share: f64) -> u32 {
+        let target = self.observed_ms() as f64 * share;
+        let mut seen = 0;
+        for (util, &ms) in self.util_ms.iter().enumerate() {
+            seen += ms;
+            if ms > 0 && seen as f64 >= target {
+                return util as u32;
+            }
+        }
+        0
+    }
+
+    fn report(&self, rates: &EnergyRates) -> GpuReport {
+        let observed = self.observed_ms().max(1) as f64;
+        let weighted: i64 = self
+            .util_ms
+            .iter()
+            .enumerate()
+            .map(|(util, &ms)| util as i64 * ms)
+            .sum();
+        let joules = self.energy.total_j;
+        GpuReport {
+            index: self.index,
+            name: self.name.clone(),
+            uuid: self.uuid.clone(),
+            mean_util_pct: weighted as f64 / observed,
+            p95_util_pct: self.percentile(0.95),
+            peak_vram_mb: self.peak_vram_mb,
+            vram_total_mb: self.vram_total_mb,
+            throttled_s: self.throttled_ms as f64 / 1000.0,
+            idle_fraction: self.idle_ms as f64 / observed,
+            energy_kwh: joules / J_PER_KWH,
+            cost: rates.cost(joules),
+            co2_g: rates.co2_g(joules),
+        }
+    }
+}
+
+/// A session in progress
+pub struct Session {
+    pub name: String,
+    /// Unix ms
+    pub start_ms: i64,
+    last_ms: Option<i64>,
+    gpus: Vec<Tally>,
+}
+
+impl Session {
+    pub fn start(name: String, start_ms: i64) -> Self {
+        Self {
+            name,
+            start_ms,
+            last_ms: None,
+            gpus: Vec::new(),
+        }
+    }
+
+    /// Account for every GPU's snapshot taken at `t_ms`
+    pub fn push_at(&mut self, t_ms: i64, snapshots: &[GpuSnapshot]) {
+        let dt_ms = match self.last_ms {
+            Some(last) if (0..=MAX_GAP_MS).contains(&(t_ms - last)) => t_ms - last,
+            _ => 0,
+        };
+        self.last_ms = Some(t_ms);
+        for snapshot in snapshots {
+            let position = self.gpus.iter().position(|g| g.tracks(snapshot));
+            let tally = match position {
+                Some(i) => &mut self.gpus[i],
+                None => {
+                    self.gpus.push(Tally::new(snapshot));
+                    self.gpus.last_mut().expect("just pushed")
+                }
+            };
+            tally.push(t_ms, dt_ms, snapshot);
+        }
+    }
+
+    /// Stop at the last snapshot and summarize
+    pub fn finish(self, rates: &EnergyRates) -> SessionReport {
+        SessionReport {
+            name: self.name,
+            start_ms: self.start_ms,
+            end_ms: self.last_ms.unwrap_or(self.start_ms),
+            currency: rates.currency.clone(),
+            gpus: self.gpus.iter().map(|g| g.report(rates)).collect(),
+        }
+    }
+}
+
+/// One GPU's statistics over a session
+#[derive(Clone, Debug, Serialize)]
+pub struct GpuReport {
+    pub index: u32,
+    pub name: String,
+    pub uuid: String,
+    pub mean_util_pct: f64,
+    pub p95_util_pct: u32,
+    pub peak_vram_mb: u64,
+    pub vram_total_mb: u64,
+    /// Time any performance-limiting throttle reason was active
+    pub throttled_s: f64,
+    /// Share of the session spent below `IDLE_UTIL`
+    pub idle_fraction: f64,
+    pub energy_kwh: f64,
+    /// In `SessionReport::currency`
+    pub cost: f64,
+    pub co2_g: f64,
+}
+
+/// End-of-session summary; the JSON output is this struct
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionReport {
+    pub name: String,
+    /// Unix ms
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub currency: String,
+    pub gpus: Vec<GpuReport>,
+}
+
+impl SessionReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let start = chrono::DateTime::from_timestamp_millis(self.start_ms)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let mut md = format!(
+            "### {}\n\n{} – {} ({})\n\n",
+            self.name,
+            start,
+            text::fmt_clock(self.end_ms),
+            text::fmt_duration(self.end_ms - self.start_ms)
+        );
+        md.push_str(
+            "| GPU | Mean util | p95 util | Peak VRAM | Throttled | Idle | Energy | Cost | CO₂ |\n",
+        );
+        md.push_str("|---|--:|--:|--:|--:|--:|--:|--:|--:|\n");
+        for g in &self.gpus {
+            md.push_str(&format!(
+                "| {} · {} | {:.0}% | {}% | {} / {} | {} | {:.0}% | {} | {}{:.2} | {} |\n",
+                g.index,
+                g.name,
+                g.mean_util_pct,
+                g.p95_util_pct,
+                text::fmt_mb(g.peak_vram_mb),
+                text::fmt_mb(g.vram_total_mb),
+                text::fmt_duration((g.throttled_s * 1000.0) as i64),
+                g.idle_fraction * 100.0,
+                text::fmt_energy(g.energy_kwh * J_PER_KWH),
+                self.currency,
+                g.cost,
+                text::fmt_co2(g.co2_g)
+            ));
+        }
+        if self.gpus.len() > 1 {
+            let kwh: f64 = self.gpus.iter().map(|g| g.energy_kwh).sum();
+            let cost: f64 = self.gpus.iter().map(|g| g.cost).sum();
+            let co2: f64 = self.gpus.iter().map(|g| g.co2_g).sum();
+            md.push_str(&format!(
+                "| **Total** | | | | | | {} | {}{:.2} | {} |\n",
+                text::fmt_energy(kwh * J_PER_KWH),
+                self.currency,
+                cost,
+                text::fmt_co2(co2)
+            ));
+        }
+        md
+    }
+
+    /// `nvdash-session-YYYYmmdd-HHMMSS.<extension>` in the working directory
+    pub fn default_path(&self, extension: &str) -> PathBuf {
+        let start = chrono::DateTime::from_timestamp_millis(self.start_ms)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y%m%d-%H%M%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        PathBuf::from(format!("nvdash-session-{}.{}", start, extension))
+    }
+}
+
+/// `nvdash session`: track until `command` exits, or until Enter is
+/// pressed when there is none, then print the report. Returns the
+/// command's exit code.
+pub fn run(
+    monitor: &mut GpuMonitor,
+    name: String,
+    command: &[String],
+    interval: Duration,
+    markdown: bool,
+    rates: &EnergyRates,
+) -> io::Result<i32> {
+    let mut child = match command.split_first() {
+        Some((program, args)) => Some(Command::new(program).args(args).spawn()?),
+        None => None,
+    };
+    let (stop_tx, stop_rx) = mpsc::channel();
+    if child.is_none() {
+        eprintln!("Session \"{}\" started; press Enter to stop", name);
+        std::thread::spawn(move || {
+            let _ = io::stdin().lock().read_line(&mut String::new());
+            let _ = stop_tx.send(());
+        });
+    }
+
+    let now = || chrono::Utc::now().timestamp_millis();
+    let mut session = Session::start(name, now());
+    let code = loop {
+        let started = Instant::now();
+        session.push_at(now(), &monitor.snapshot_readable());
+        if let Some(child) = &mut child {
+            if let Some(status) = child.try_wait()? {
+                break status.code().unwrap_or(1);
+            }
+        } else if stop_rx.try_recv() != Err(mpsc::TryRecvError::Empty) {
+            break 0;
+        }
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    };
+    // Count the last stretch up to the stop
+    session.push_at(now(), &monitor.snapshot_readable());
+
+    let report = session.finish(rates);
+    let mut out = io::stdout().lock();
+    if markdown {
+        out.write_all(report.to_markdown().as_bytes())?;
+    } else {
+        writeln!(out, "{}", report.to_json())?;
+    }
+    out.flush()?;
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::ThrottleReason;
+
+    fn gpu(index: u32, gpu_util: u32) -> GpuSnapshot {
+        GpuSnapshot {
+            gpu_util,
+            util_unavailable: false,
+            uuid: format!("GPU-{}", index),
+            vram_total_mb: 1000,
+            ..GpuSnapshot::placeholder(index)
+        }
+    }
+
+    fn report(session: Session) -> SessionReport {
+        session.finish(&EnergyRates::default())
+    }
+
+    #[test]
+    fn statistics_are_time_weighted() {
+        let mut session = Session::start("run".into(), 0);
+        session.push_at(0, &[gpu(0, 100)]);
+        // Each reading counts for the time since the one before
+        session.push_at(1_000, &[gpu(0, 100)]);
+        session.push_at(4_000, &[gpu(0, 0)]);
+        let report = report(session);
+        assert_eq!(report.end_ms, 4_000);
+
+        let g = &report.gpus[0];
+        assert_eq!(g.mean_util_pct, 25.0);
+        assert_eq!(g.idle_fraction, 0.75);
+        // 75% of the time at 0%, the rest at 100%
+        assert_eq!(g.p95_util_pct, 100);
+    }
+
+    #[test]
+    fn percentiles_follow_time_spent() {
+        let mut tally = Tally::new(&gpu(0, 0));
+        for (util, ms) in [(10, 500), (50, 400), (90, 100)] {
+            tally.push(0, ms, &gpu(0, util));
+        }
+        assert_eq!(tally.percentile(0.5), 10);
+        assert_eq!(tally.percentile(0.51), 50);
+        assert_eq!(tally.percentile(0.9), 50);
+        assert_eq!(tally.percentile(0.95), 90);
+        assert_eq!(Tally::new(&gpu(0, 0)).percentile(0.95), 0);
+    }
+
+    #[test]
+    fn long_gaps_are_not_counted() {
+        let mut session = Session::start("run".into(), 0);
+        session.push_at(0, &[gpu(0, 80)]);
+        session.push_at(1_000, &[gpu(0, 80)]);
+        // Asleep for two minutes: the gap isn't observed time
+        session.push_at(1_000 + 2 * MAX_GAP_MS, &[gpu(0, 0)]);
+        session.push_at(2_000 + 2 * MAX_GAP_MS, &[gpu(0, 40)]);
+        let g = &report(session).gpus[0];
+        assert_eq!(g.mean_util_pct, 60.0);
+        assert_eq!(g.idle_fraction, 0.0);
+    }
+
+    #[test]
+    fn throttling_and_peak_vram() {
+        let mut session = Session::start("run".into(), 0);
+        let busy = |vram_used_mb, throttle_reasons| GpuSnapshot {
+            vram_used_mb,
+            throttle_reasons,
+            ..gpu(0, 90)
+        };
+        session.push_at(0, &[busy(200, vec![])]);
+        session.push_at(2_000, &[busy(700, vec![ThrottleReason::HwSlowdown])]);
+        // Idle isn't a slowdown
+        session.push_at(3_000, &[busy(300, vec![ThrottleReason::GpuIdle])]);
+        let g = &report(session).gpus[0];
+        assert_eq!(g.throttled_s, 2.0);
+        assert_eq!(g.peak_vram_mb, 700);
+    }
+
+    #[test]
+    fn gpus_are_told_apart_by_uuid_and_host() {
+        let local = gpu(0, 100);
+        let remote = GpuSnapshot {
+            host: "node1:9836".into(),
+            ..gpu(0, 0)
+        };
+        // Renumbered after the local GPUs, as the window does
+        let moved = GpuSnapshot {
+            index: 1,
+            ..remote.clone()
+        };
+        let mut session = Session::start("run".into(), 0);
+        session.push_at(0, &[local.clone(), remote]);
+        session.push_at(1_000, &[local, moved]);
+        let report = report(session);
+        let utils: Vec<f64> = report.gpus.iter().map(|g| g.mean_util_pct).collect();
+        assert_eq!(utils, [100.0, 0.0]);
+    }
+
+    #[test]
+    fn markdown_totals_multiple_gpus() {
+        let mut session = Session::start("run-42".into(), 0);
+        session.push_at(0, &[gpu(0, 50), gpu(1, 50)]);
+        session.push_at(60_000, &[gpu(0, 50), gpu(1, 50)]);
+        let md = report(session).to_markdown();
+        assert!(md.starts_with("### run-42\n"));
+        assert!(md.contains("| 0 · GPU 0 (error) | 50% | 50% |"));
+        assert!(md.contains("| **Total** |"));
+    }
+}
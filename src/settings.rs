@@ -0,0 +1,296 @@
+//! Persisted user settings.
+//!
+//! Stored as TOML in the platform config directory
+//! (`~/.config/nvdash/config.toml`, `%APPDATA%\nvdash\config.toml`, ...).
+//! The app writes the file when something changes and re-reads it when it
+//! is edited externally; both are checked at most once per second. While
+//! the file fails to parse, nothing is written, so a typo in a hand-edited
+//! file never costs the rest of it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::alerts::{self, Rule};
+use crate::energy::EnergyRates;
+use crate::gpu::{GpuRef, GpuSnapshot};
+use crate::history;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// Every GPU, one below the other
+    #[default]
+    Stacked,
+    /// One GPU at a time, with a tab row to switch
+    Tabs,
+}
+
+/// Outer position and inner size of the window, in points
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub poll_ms: u64,
+    pub opacity_pct: u8,
+    pub always_on_top: bool,
+    pub decorations: bool,
+    /// Sparkline time window
+    pub window_ms: i64,
+    pub layout: Layout,
+    /// GPUs to show, by local index or UUID (see `GpuRef`); empty shows all
+    pub gpus: Vec<GpuRef>,
+    pub window: Option<WindowGeometry>,
+    pub alerts: Vec<Rule>,
+    pub energy: EnergyRates,
+    /// Show VRAM and temperature forecasts that land within this many minutes
+    pub forecast_horizon_min: u32,
+    /// Agents (`host:port`) whose GPUs are shown after the local ones; read
+    /// at startup
+    pub agents: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            poll_ms: 500,
+            opacity_pct: 100,
+            always_on_top: false,
+            decorations: true,
+            window_ms: history::WINDOWS[0],
+            layout: Layout::Stacked,
+            gpus: Vec::new(),
+            window: None,
+            alerts: alerts::default_rules(),
+            energy: EnergyRates::default(),
+            forecast_horizon_min: 60,
+            agents: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Clamp values a hand-edited file might get wrong
+    fn sanitized(mut self) -> Self {
+        self.poll_ms = self.poll_ms.clamp(50, 60_000);
+        self.opacity_pct = self.opacity_pct.clamp(10, 100);
+        self.window_ms = self.window_ms.clamp(1000, history::MAX_WINDOW_MS);
+        self.energy.price_per_kwh = self.energy.price_per_kwh.max(0.0);
+        self.energy.co2_g_per_kwh = self.energy.co2_g_per_kwh.max(0.0);
+        self.forecast_horizon_min = self.forecast_horizon_min.clamp(1, 24 * 60);
+        self
+    }
+
+    pub fn shows_gpu(&self, snapshot: &GpuSnapshot) -> bool {
+        self.gpus.is_empty() || self.gpus.iter().any(|g| g.matches(snapshot))
+    }
+}
+
+/// The config file on disk and what we last knew about it
+pub struct SettingsFile {
+    path: PathBuf,
+    /// Modification time after our last read or write
+    mtime: Option<SystemTime>,
+    last_check: Instant,
+    /// The last read failed; saving now would replace the user's file
+    /// with defaults
+    broken: bool,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read(path: &Path) -> Result<Settings, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::from_str::<Settings>(&text)
+        .map(Settings::sanitized)
+        .map_err(|e| {
+            format!(
+                "{}: {} (changes won't be saved until it is fixed)",
+                path.display(),
+                e
+            )
+        })
+}
+
+impl SettingsFile {
+    /// `<config dir>/nvdash/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("nvdash").join("config.toml"))
+    }
+
+    /// Open the config at `path`. A missing file yields defaults; an
+    /// unreadable one yields defaults plus the error to show.
+    pub fn open(path: PathBuf) -> (Self, Settings, Option<String>) {
+        let mtime = modified(&path);
+        let (settings, error) = if mtime.is_none() {
+            (Settings::default(), None)
+        } else {
+            match read(&path) {
+                Ok(s) => (s, None),
+                Err(e) => (
+                    Settings::default(),
+                    Some(format!("Config not loaded: {}", e)),
+                ),
+            }
+        };
+        let file = Self {
+            path,
+            mtime,
+            last_check: Instant::now(),
+            broken: error.is_some(),
+        };
+        (file, settings, error)
+    }
+
+    pub fn save(&mut self, settings: &Settings) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(settings)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(&self.path, text)?;
+        self.mtime = modified(&self.path);
+        Ok(())
+    }
+
+    /// Called every frame. Once per second: if the file was changed by
+    /// someone else, returns the reloaded settings (or why they failed to
+    /// parse); otherwise saves `current` if it differs from `saved` and the
+    /// file last parsed.
+    pub fn sync(
+        &mut self,
+        current: &Settings,
+        saved: &mut Settings,
+    ) -> Option<Result<Settings, String>> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let mtime = modified(&self.path);
+        if mtime.is_some() && mtime != self.mtime {
+            self.mtime = mtime;
+            let reloaded = read(&self.path);
+            if let Ok(s) = &reloaded {
+                *saved = s.clone();
+            }
+            self.broken = reloaded.is_err();
+            return Some(reloaded);
+        }
+
+        if current != saved && !self.broken {
+            if let Err(e) = self.save(current) {
+                return Some(Err(format!("Cannot save {}: {}", self.path.display(), e)));
+            }
+            *saved = current.clone();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config file path of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nvdash-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("config.toml")
+    }
+
+    /// Let `sync` act on its next call instead of waiting out the interval
+    fn due(file: &mut SettingsFile) {
+        file.last_check = Instant::now() - CHECK_INTERVAL;
+    }
+
+    #[test]
+    fn a_malformed_file_is_never_overwritten() {
+        let path = scratch("malformed");
+        let text = "poll_ms = 250\n[[alerts]\nmetric = \"temperature\"\n";
+        fs::write(&path, text).unwrap();
+
+        let (mut file, settings, error) = SettingsFile::open(path.clone());
+        assert!(error.is_some());
+        assert_eq!(settings, Settings::default());
+
+        // The window moving is enough to make the settings differ
+        let mut saved = settings.clone();
+        let mut current = settings;
+        current.window = Some(WindowGeometry {
+            x: 10.0,
+            y: 20.0,
+            width: 300.0,
+            height: 400.0,
+        });
+        due(&mut file);
+        assert_eq!(file.sync(&current, &mut saved), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn saving_resumes_once_the_file_parses_again() {
+        let path = scratch("resumes");
+        fs::write(&path, "poll_ms = 250\n").unwrap();
+        let (mut file, mut saved, error) = SettingsFile::open(path.clone());
+        assert_eq!(error, None);
+        assert_eq!(saved.poll_ms, 250);
+
+        // A broken edit is reported and left alone
+        fs::write(&path, "poll_ms = \n").unwrap();
+        file.mtime = None;
+        due(&mut file);
+        assert!(matches!(
+            file.sync(&saved.clone(), &mut saved),
+            Some(Err(_))
+        ));
+        let mut current = saved.clone();
+        current.opacity_pct = 80;
+        due(&mut file);
+        assert_eq!(file.sync(&current, &mut saved), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "poll_ms = \n");
+
+        // Fixed, it is reloaded and later changes are saved again
+        fs::write(&path, "poll_ms = 1000\n").unwrap();
+        file.mtime = None;
+        due(&mut file);
+        let reloaded = file.sync(&current, &mut saved).unwrap().unwrap();
+        assert_eq!(reloaded.poll_ms, 1000);
+        let mut current = reloaded;
+        current.opacity_pct = 80;
+        due(&mut file);
+        assert_eq!(file.sync(&current, &mut saved), None);
+        let (_, written, _) = SettingsFile::open(path.clone());
+        assert_eq!(written, current);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn shown_gpus_are_local_indices_or_uuids() {
+        let settings: Settings = toml::from_str("gpus = [1, \"GPU-b\"]").unwrap();
+        let gpu = |host: &str, uuid: &str, index| GpuSnapshot {
+            host: host.into(),
+            uuid: uuid.into(),
+            ..GpuSnapshot::placeholder(index)
+        };
+        assert!(!settings.shows_gpu(&gpu("", "GPU-a", 0)));
+        assert!(settings.shows_gpu(&gpu("", "GPU-c", 1)));
+        // An agent's GPU listed second isn't this machine's GPU 1
+        assert!(!settings.shows_gpu(&gpu("box:9836", "GPU-d", 1)));
+        assert!(settings.shows_gpu(&gpu("box:9836", "GPU-b", 5)));
+    }
+}
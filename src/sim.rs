@@ -0,0 +1,538 @@
+//! Simulated backend: synthetic but plausible GPU telemetry.
+//!
+//! Each device runs a small state machine of ML jobs that come and go,
+//! alternating training and eval phases. Utilization, VRAM, power,
+//! temperature, fan and clocks are derived from that load so the
+//! dashboard can be demoed and developed on machines without an NVIDIA GPU.
+
+use crate::gpu::{
+    EncoderSession, EventKind, GpuBackend, GpuError, GpuEvent, GpuSnapshot, NvLinkStatus,
+    PcieStatus, ProcessInfo, ProcessKind, ProcessUtil, ThrottleReason, VideoCodec,
+};
+use std::time::Instant;
+
+/// (executable, command line) of the jobs the simulator starts
+const PROCESSES: [(&str, &str); 7] = [
+    ("python", "python train.py --cfg configs/big.yaml"),
+    ("python3", "python3 -m torch.distributed.run finetune.py --lr 3e-5"),
+    ("torchrun", "torchrun --nproc_per_node=1 pretrain.py"),
+    (
+        "jupyter-kernel",
+        "python -m ipykernel_launcher -f kernel-4f2a.json",
+    ),
+    ("tritonserver", "tritonserver --model-repository=/models"),
+    ("ollama", "ollama serve"),
+    (
+        "ffmpeg",
+        "ffmpeg -hwaccel cuda -i input.mp4 -c:v hevc_nvenc output.mp4",
+    ),
+];
+
+/// NVLinks bridging each pair of simulated GPUs
+const BRIDGE_LINKS: u32 = 4;
+/// Per-link, per-direction gradient traffic at full load, KB/s
+const BRIDGE_KB_S: f64 = 9_000_000.0;
+
+/// Memory held by the driver / desktop even with no jobs running
+const BASE_VRAM_MB: u64 = 350;
+const AMBIENT_C: f64 = 30.0;
+/// Where the simulated card would start hardware slowdown
+const SLOWDOWN_C: u32 = 88;
+/// Thermal time constant in seconds
+const THERMAL_TAU_S: f64 = 20.0;
+/// Largest step the simulation takes at once, so long gaps don't explode
+const MAX_STEP_S: f64 = 1.0;
+/// Simulated seconds run before the first snapshot, so it shows a warm card
+const WARMUP_S: u32 = 120;
+
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub gpus: u32,
+    pub name: String,
+    pub vram_total_mb: u64,
+    pub power_limit_w: f64,
+    /// Average seconds between new jobs starting on a device
+    pub job_interval_s: f64,
+    pub seed: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            gpus: 1,
+            name: "NVIDIA GeForce RTX 4090 (sim)".into(),
+            vram_total_mb: 24564,
+            power_limit_w: 450.0,
+            job_interval_s: 45.0,
+            seed: 0x5eed,
+        }
+    }
+}
+
+/// xorshift64* — good enough for jitter, no extra dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.unit()
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+}
+
+struct SimJob {
+    pid: u32,
+    name: &'static str,
+    cmdline: &'static str,
+    vram_mb: f64,
+    vram_target_mb: f64,
+    /// Simulated Unix start time in seconds
+    start_time: u64,
+    /// Fraction of the device this job keeps busy while training
+    intensity: f64,
+    remaining_s: f64,
+}
+
+impl SimJob {
+    /// Transcodes on the video engines rather than training
+    fn video(&self) -> bool {
+        self.name == "ffmpeg"
+    }
+
+    /// (encoder, decoder) percent busy
+    fn video_util(&self) -> (f64, f64) {
+        if self.video() {
+            (self.intensity * 70.0, self.intensity * 45.0)
+        } else {
+            (0.0, 0.0)
+        }
+    }
+}
+
+struct SimDevice {
+    rng: Rng,
+    jobs: Vec<SimJob>,
+    /// Seconds until the current train/eval phase flips
+    phase_left_s: f64,
+    eval: bool,
+    util: f64,
+    temperature: f64,
+    power_w: f64,
+    /// Energy counter, J
+    energy_j: f64,
+    /// Out of the idle P-state
+    busy: bool,
+    /// P-state changes since the last snapshot
+    events: Vec<GpuEvent>,
+    /// Simulated wall clock (Unix seconds); runs `WARMUP_S` behind at first
+    /// and catches up during warmup
+    clock_s: f64,
+    last_step: Instant,
+}
+
+impl SimDevice {
+    fn new(config: &SimConfig, seed: u64) -> Self {
+        let mut dev = Self {
+            rng: Rng::new(seed),
+            jobs: Vec::new(),
+            phase_left_s: 30.0,
+            eval: false,
+            util: 0.0,
+            temperature: AMBIENT_C + 5.0,
+            power_w: 0.0,
+            energy_j: 0.0,
+            busy: false,
+            events: Vec::new(),
+            clock_s: chrono::Utc::now().timestamp_millis() as f64 / 1000.0 - WARMUP_S as f64,
+            last_step: Instant::now(),
+        };
+        // Start with a job already running so the first frame isn't empty
+        let job = dev.spawn_job();
+        dev.jobs.push(job);
+        for _ in 0..WARMUP_S {
+            dev.step(config, 1.0);
+        }
+        dev.events.clear();
+        dev
+    }
+
+    fn spawn_job(&mut self) -> SimJob {
+        let (name, cmdline) = PROCESSES[(self.rng.next_u64() % PROCESSES.len() as u64) as usize];
+        // PIDs aren't reused while their process is alive
+        let pid = loop {
+            let pid = 1000 + (self.rng.next_u64() % 60000) as u32;
+            if self.jobs.iter().all(|j| j.pid != pid) {
+                break pid;
+            }
+        };
+        let mut job = SimJob {
+            pid,
+            name,
+            cmdline,
+            vram_mb: 0.0,
+            vram_target_mb: self.rng.range(900.0, 9000.0),
+            start_time: self.clock_s as u64,
+            intensity: self.rng.range(0.3, 1.0),
+            remaining_s: self.rng.range(60.0, 600.0),
+        };
+        // A forgotten notebook: holds its memory for an hour, computes nothing
+        if name == "jupyter-kernel" {
+            job.intensity = 0.0;
+            job.remaining_s *= 6.0;
+        }
+        job
+    }
+
+    fn step(&mut self, config: &SimConfig, dt: f64) {
+        self.clock_s += dt;
+
+        // Job churn
+        for job in &mut self.jobs {
+            job.remaining_s -= dt;
+            job.vram_mb += (job.vram_target_mb - job.vram_mb) * (dt / 4.0).min(1.0);
+        }
+        self.jobs.retain(|j| j.remaining_s > 0.0);
+
+        let committed: f64 = self.jobs.iter().map(|j| j.vram_target_mb).sum();
+        if self.rng.chance(dt / config.job_interval_s.max(1.0)) {
+            let job = self.spawn_job();
+            let free = config.vram_total_mb as f64 - BASE_VRAM_MB as f64 - committed;
+            if job.vram_target_mb < free {
+                self.jobs.push(job);
+            }
+        }
+
+        // Alternate long training phases with short eval dips
+        self.phase_left_s -= dt;
+        if self.phase_left_s <= 0.0 {
+            self.eval = !self.eval;
+            self.phase_left_s = if self.eval {
+                self.rng.range(3.0, 10.0)
+            } else {
+                self.rng.range(20.0, 90.0)
+            };
+        }
+
+        let load: f64 = self.jobs.iter().map(|j| j.intensity).sum::<f64>().min(1.0);
+        let phase_factor = if self.eval { 0.45 } else { 1.0 };
+        let mut target = load * phase_factor * 99.0;
+        // Occasional dataloader stall
+        if !self.jobs.is_empty() && self.rng.chance(dt * 0.05) {
+            target *= 0.2;
+        }
+        target = (target + self.rng.range(-4.0, 4.0)).clamp(0.0, 100.0);
+        self.util += (target - self.util) * (dt * 2.0).min(1.0);
+
+        // Drops to the idle P-state when there is nothing to do
+        let busy = self.util > 5.0;
+        if busy != self.busy {
+            self.busy = busy;
+            self.events.push(GpuEvent {
+                t_ms: (self.clock_s * 1000.0) as i64,
+                kind: EventKind::PstateChange,
+                xid: None,
+            });
+        }
+
+        let idle_w = config.power_limit_w * 0.06;
+        self.power_w = (idle_w
+            + (config.power_limit_w * 0.95 - idle_w) * self.util / 100.0
+            + self.rng.range(-3.0, 3.0))
+        .clamp(0.0, config.power_limit_w);
+        self.energy_j += self.power_w * dt;
+
+        let temp_target = AMBIENT_C + 55.0 * self.power_w / config.power_limit_w.max(1.0);
+        self.temperature += (temp_target - self.temperature) * (dt / THERMAL_TAU_S).min(1.0);
+    }
+
+    fn advance(&mut self, config: &SimConfig) {
+        let now = Instant::now();
+        let mut elapsed = now.duration_since(self.last_step).as_secs_f64();
+        self.last_step = now;
+        while elapsed > 0.0 {
+            let dt = elapsed.min(MAX_STEP_S);
+            self.step(config, dt);
+            elapsed -= dt;
+        }
+    }
+}
+
+pub struct SimBackend {
+    config: SimConfig,
+    devices: Vec<SimDevice>,
+}
+
+impl SimBackend {
+    pub fn new(config: SimConfig) -> Self {
+        let devices = (0..config.gpus as u64)
+            .map(|i| {
+                SimDevice::new(
+                    &config,
+                    config.seed.wrapping_add(i.wrapping_mul(0x9e37_79b9)),
+                )
+            })
+            .collect();
+        Self { config, devices }
+    }
+}
+
+impl GpuBackend for SimBackend {
+    fn device_count(&self) -> u32 {
+        self.devices.len() as u32
+    }
+
+    fn simulated(&self) -> bool {
+        true
+    }
+
+    fn snapshot(&mut self, index: u32) -> Result<GpuSnapshot, GpuError> {
+        let config = &self.config;
+        // Both ends of a bridge move the same traffic: as much as the less
+        // busy of the pair can keep up with
+        let partner = index ^ 1;
+        let partner_load = self.devices.get(partner as usize).map(|d| d.util / 100.0);
+        let dev = self
+            .devices
+            .get_mut(index as usize)
+            .ok_or_else(|| format!("no simulated GPU {}", index))?;
+        dev.advance(config);
+
+        // Jobs split the device's utilization in proportion to their intensity
+        let total_intensity: f64 = dev.jobs.iter().map(|j| j.intensity).sum::<f64>().max(1.0);
+        let mut processes: Vec<ProcessInfo> = dev
+            .jobs
+            .iter()
+            .map(|j| ProcessInfo {
+                pid: j.pid,
+                name: j.name.to_string(),
+                vram_mb: j.vram_mb as u64,
+                start_time: j.start_time,
+                cmdline: j.cmdline.to_string(),
+                user: "sim".into(),
+                // A dataloader worker or two, busier when the GPU is
+                cpu_pct: (j.intensity * dev.util * 1.5) as f32,
+                rss_mb: 2048 + (j.vram_mb / 4.0) as u64,
+                cwd: "/home/sim/project".into(),
+                kind: ProcessKind::Compute,
+                utilization: Some({
+                    let sm = dev.util * j.intensity / total_intensity;
+                    let (enc, dec) = j.video_util();
+                    ProcessUtil {
+                        sm: sm.round() as u32,
+                        mem: (sm * 0.6).round() as u32,
+                        enc: enc.round() as u32,
+                        dec: dec.round() as u32,
+                    }
+                }),
+                // One 1080p HEVC stream, faster the more of the GPU it gets
+                encoder_sessions: j
+                    .video()
+                    .then_some(EncoderSession {
+                        session_id: j.pid,
+                        codec: VideoCodec::Hevc,
+                        width: 1920,
+                        height: 1080,
+                        fps: (j.intensity * 240.0) as u32,
+                        latency_us: (2500.0 + (1.0 - j.intensity) * 4000.0) as u32,
+                    })
+                    .into_iter()
+                    .collect(),
+            })
+            .collect();
+        processes.sort_by_key(|p| std::cmp::Reverse(p.vram_mb));
+
+        let (encoder, decoder) = dev.jobs.iter().fold((0.0, 0.0), |(enc, dec), j| {
+            let (e, d) = j.video_util();
+            (enc + e, dec + d)
+        });
+
+        let vram_used_mb = (BASE_VRAM_MB + processes.iter().map(|p| p.vram_mb).sum::<u64>())
+            .min(config.vram_total_mb);
+        let load = dev.util / 100.0;
+        // Boost clocks sag a little as the card heats up
+        let thermal_sag = ((dev.temperature - 70.0).max(0.0) * 8.0) as u32;
+        let clock_graphics_mhz = if load > 0.02 {
+            (1800.0 + 720.0 * load) as u32 - thermal_sag.min(400)
+        } else {
+            210
+        };
+        let fan = ((dev.temperature - 35.0) * 1.8 + 30.0).clamp(30.0, 100.0);
+
+        let mut throttle_reasons = Vec::new();
+        if !dev.busy {
+            throttle_reasons.push(ThrottleReason::GpuIdle);
+        }
+        if dev.power_w > config.power_limit_w * 0.92 {
+            throttle_reasons.push(ThrottleReason::SwPowerCap);
+        }
+        // Where the boost clock starts sagging noticeably
+        if dev.temperature > 78.0 {
+            throttle_reasons.push(ThrottleReason::SwThermal);
+        }
+
+        // GPUs are bridged in pairs, like workstation NVLink bridges; the
+        // pair syncs gradients while training and goes quiet during eval
+        let nvlinks = if let Some(partner_load) = partner_load {
+            let sync = if dev.eval { 0.05 } else { 1.0 };
+            let kb_s = Some((load.min(partner_load) * sync * BRIDGE_KB_S) as u64);
+            (0..BRIDGE_LINKS)
+                .map(|link| NvLinkStatus {
+                    link,
+                    active: true,
+                    version: 3,
+                    remote_bus_id: format!("00000000:{:02X}:00.0", partner + 1),
+                    remote_gpu: Some(partner),
+                    tx_kb_s: kb_s,
+                    rx_kb_s: kb_s,
+                    ..Default::default()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(GpuSnapshot {
+            name: config.name.clone(),
+            index,
+            driver_version: "sim".into(),
+            cuda_version: "12.4".into(),
+            gpu_util: dev.util.round() as u32,
+            memory_util: (dev.util * 0.6).round() as u32,
+            vram_used_mb,
+            vram_total_mb: config.vram_total_mb,
+            temperature: dev.temperature.round() as u32,
+            fan_speed: Some(fan.round() as u32),
+            power_draw_w: dev.power_w,
+            power_limit_w: config.power_limit_w,
+            clock_graphics_mhz,
+            clock_memory_mhz: if load > 0.02 { 10501 } else { 405 },
+            clock_sm_mhz: clock_graphics_mhz,
+            processes,
+            uuid: format!("GPU-5151d000-0000-4000-8000-{:012x}", index),
+            mig_instances: Vec::new(),
+            ecc: None,
+            events: std::mem::take(&mut dev.events),
+            throttle_reasons,
+            // Gen4 x16 that drops to Gen1 when idle, like the real thing;
+            // batches stream in, checkpoints and metrics trickle out
+            pcie: Some(PcieStatus {
+                tx_kb_s: (load * 900_000.0) as u32,
+                rx_kb_s: (load * 6_000_000.0 * if dev.eval { 0.4 } else { 1.0 }) as u32,
+                link_gen: if dev.busy { 4 } else { 1 },
+                max_link_gen: 4,
+                link_width: 16,
+                max_link_width: 16,
+            }),
+            nvlinks,
+            encoder_util: Some(encoder.min(100.0).round() as u32),
+            decoder_util: Some(decoder.min(100.0).round() as u32),
+            energy_mj: Some((dev.energy_j * 1000.0) as u64),
+            temp_slowdown_c: Some(SLOWDOWN_C),
+            host: String::new(),
+            util_unavailable: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn config(gpus: u32) -> SimConfig {
+        SimConfig {
+            gpus,
+            // Plenty of churn within a simulated hour
+            job_interval_s: 10.0,
+            ..Default::default()
+        }
+    }
+
+    /// Steps the simulation by fixed amounts, independent of wall time
+    fn run(sim: &mut SimBackend, seconds: u32) {
+        for _ in 0..seconds {
+            for dev in &mut sim.devices {
+                dev.step(&sim.config, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let config = config(1);
+        let mut a = SimDevice::new(&config, 42);
+        let mut b = SimDevice::new(&config, 42);
+        for _ in 0..600 {
+            a.step(&config, 1.0);
+            b.step(&config, 1.0);
+        }
+        let jobs = |d: &SimDevice| -> Vec<(u32, &str, u64)> {
+            d.jobs
+                .iter()
+                .map(|j| (j.pid, j.name, j.vram_target_mb.to_bits()))
+                .collect()
+        };
+        assert_eq!(jobs(&a), jobs(&b));
+        assert_eq!(a.util.to_bits(), b.util.to_bits());
+        assert_eq!(a.temperature.to_bits(), b.temperature.to_bits());
+        assert_eq!(a.energy_j.to_bits(), b.energy_j.to_bits());
+
+        let other = SimDevice::new(&config, 43);
+        assert_ne!(jobs(&a), jobs(&other));
+    }
+
+    #[test]
+    fn snapshots_stay_within_limits() {
+        let mut sim = SimBackend::new(config(2));
+        let mut seen = HashSet::new();
+        for _ in 0..360 {
+            run(&mut sim, 10);
+            for i in 0..sim.device_count() {
+                let snap = sim.snapshot(i).unwrap();
+                assert_eq!(snap.index, i);
+                assert!(snap.vram_used_mb <= snap.vram_total_mb);
+                assert!(snap.gpu_util <= 100 && snap.memory_util <= 100);
+                assert!(snap.power_draw_w <= snap.power_limit_w);
+                assert!(snap.temperature < snap.temp_slowdown_c.unwrap());
+
+                let pids: HashSet<u32> = snap.processes.iter().map(|p| p.pid).collect();
+                assert_eq!(pids.len(), snap.processes.len(), "duplicate PID");
+                for p in &snap.processes {
+                    let util = p.utilization.unwrap();
+                    assert!(util.sm <= 100 && util.enc <= 100 && util.dec <= 100);
+                    seen.insert((i, p.pid));
+                }
+            }
+        }
+        // Jobs came and went rather than one running all hour
+        assert!(seen.len() > 10, "only {} processes seen", seen.len());
+    }
+
+    #[test]
+    fn bridges_pairs_only() {
+        let mut sim = SimBackend::new(config(3));
+        let links = |sim: &mut SimBackend, i| sim.snapshot(i).unwrap().nvlinks;
+        assert!(links(&mut sim, 0).iter().all(|l| l.remote_gpu == Some(1)));
+        assert!(links(&mut sim, 1).iter().all(|l| l.remote_gpu == Some(0)));
+        assert!(links(&mut sim, 2).is_empty());
+        assert!(sim.snapshot(3).is_err());
+    }
+}
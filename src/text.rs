@@ -0,0 +1,319 @@
+//! Plain-text renderings shared by the egui widget, the terminal UI and
+//! headless output: block-character sparklines and bars, number formatting.
+
+use crate::energy::{EnergyRates, J_PER_KWH};
+use crate::events;
+use crate::gpu::{
+    EncoderSession, EventKind, GpuEvent, GpuSnapshot, MigInstance, NvLinkStatus, PcieStatus,
+    ProcessInfo,
+};
+
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Temperature range mapped onto the bar and heat colors
+pub const TEMP_LOW: f64 = 30.0;
+pub const TEMP_HIGH: f64 = 90.0;
+
+/// One block character per cell (see `Series::resample`); empty cells are spaces
+pub fn sparkline(cells: &[Option<f64>], y_min: f64, y_max: f64) -> String {
+    let range = (y_max - y_min).max(1.0);
+    cells
+        .iter()
+        .map(|cell| match cell {
+            Some(v) => {
+                let t = ((v - y_min) / range).clamp(0.0, 1.0);
+                let idx = (t * 7.0).round() as usize;
+                SPARK_BLOCKS[idx.min(7)]
+            }
+            None => ' ',
+        })
+        .collect()
+}
+
+/// Label for a window length: `1m`, `6h`
+pub fn fmt_window(window_ms: i64) -> String {
+    let secs = window_ms / 1000;
+    if secs >= 3600 && secs % 3600 == 0 {
+        format!("{}h", secs / 3600)
+    } else if secs >= 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Length of a stretch of time: `2h 42m`, `3m 10s`, `45s`
+pub fn fmt_duration(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Local wall-clock time of a Unix-ms timestamp: `14:03:27`
+pub fn fmt_clock(t_ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(t_ms)
+        .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Filled and empty cell counts for a temperature bar `width` cells wide
+pub fn temp_bar_cells(temperature: u32, width: usize) -> (usize, usize) {
+    let pct = ((temperature as f64 - TEMP_LOW) / (TEMP_HIGH - TEMP_LOW)).clamp(0.0, 1.0);
+    let filled = (pct * width as f64).round() as usize;
+    (filled, width - filled)
+}
+
+/// Soft heat color as RGB: sage green → muted gold → dusty rose
+pub fn heat_rgb(value: f64, low: f64, high: f64) -> [u8; 3] {
+    let t = ((value - low) / (high - low)).clamp(0.0, 1.0);
+    if t < 0.5 {
+        let s = (t * 2.0) as f32;
+        [
+            (105.0 + (195.0 - 105.0) * s) as u8,
+            (160.0 + (165.0 - 160.0) * s) as u8,
+            (95.0 + (90.0 - 95.0) * s) as u8,
+        ]
+    } else {
+        let s = ((t - 0.5) * 2.0) as f32;
+        [
+            (195.0 + (190.0 - 195.0) * s) as u8,
+            (165.0 - 65.0 * s) as u8,
+            (90.0 + (10.0 - 90.0) * s) as u8,
+        ]
+    }
+}
+
+/// Power draw as a percentage of the enforced limit
+pub fn power_pct(snapshot: &GpuSnapshot) -> f64 {
+    if snapshot.power_limit_w > 0.0 {
+        (snapshot.power_draw_w / snapshot.power_limit_w) * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// `42%`, or `-` when the device doesn't report it
+pub fn fmt_pct(value: Option<u32>) -> String {
+    value
+        .map(|v| format!("{}%", v))
+        .unwrap_or_else(|| "-".into())
+}
+
+/// `512M` / `3.4G`
+pub fn fmt_mb(mb: u64) -> String {
+    if mb >= 1024 {
+        format!("{:.1}G", mb as f64 / 1024.0)
+    } else {
+        format!("{}M", mb)
+    }
+}
+
+/// Throughput from KB/s: `850K` / `12M` / `3.4G`
+pub fn fmt_kb_s(kb_s: f64) -> String {
+    if kb_s >= 1024.0 * 1024.0 {
+        format!("{:.1}G", kb_s / (1024.0 * 1024.0))
+    } else if kb_s >= 1024.0 {
+        format!("{:.0}M", kb_s / 1024.0)
+    } else {
+        format!("{:.0}K", kb_s)
+    }
+}
+
+/// `Gen4 x16`, or `Gen1 x4 (max Gen4 x16)` when below the maximum
+pub fn fmt_pcie_link(pcie: &PcieStatus) -> String {
+    let current = format!("Gen{} x{}", pcie.link_gen, pcie.link_width);
+    if pcie.link_gen < pcie.max_link_gen || pcie.link_width < pcie.max_link_width {
+        format!(
+            "{} (max Gen{} x{})",
+            current, pcie.max_link_gen, pcie.max_link_width
+        )
+    } else {
+        current
+    }
+}
+
+/// `GPU 1`, or the remote PCI bus ID for an NVSwitch or other device
+pub fn nvlink_remote(link: &NvLinkStatus) -> String {
+    match link.remote_gpu {
+        Some(gpu) => format!("GPU {}", gpu),
+        None if link.remote_bus_id.is_empty() => "-".into(),
+        None => link.remote_bus_id.clone(),
+    }
+}
+
+/// `NVLink 4/4 up  TX 34G/s  RX 33G/s  0 errors`, summed over the links
+pub fn nvlink_summary(snapshot: &GpuSnapshot) -> Option<String> {
+    if snapshot.nvlinks.is_empty() {
+        return None;
+    }
+    let active: Vec<&NvLinkStatus> = snapshot.nvlinks.iter().filter(|l| l.active).collect();
+    let mut summary = format!("NVLink {}/{} up", active.len(), snapshot.nvlinks.len());
+    let tx: Option<u64> = active.iter().map(|l| l.tx_kb_s).sum();
+    let rx: Option<u64> = active.iter().map(|l| l.rx_kb_s).sum();
+    if let (Some(tx), Some(rx)) = (tx, rx) {
+        summary.push_str(&format!(
+            "  TX {}/s  RX {}/s",
+            fmt_kb_s(tx as f64),
+            fmt_kb_s(rx as f64)
+        ));
+    }
+    let errors: u64 = snapshot.nvlinks.iter().map(|l| l.errors()).sum();
+    summary.push_str(&format!("  {} errors", errors));
+    Some(summary)
+}
+
+/// `enc 12% · dec 40%`, or `None` while both engines are idle or unreported
+pub fn video_summary(snapshot: &GpuSnapshot) -> Option<String> {
+    let pct = |util: Option<u32>| util.map_or("-".into(), |u| format!("{}%", u));
+    let busy = snapshot.encoder_util.unwrap_or(0) + snapshot.decoder_util.unwrap_or(0) > 0;
+    busy.then(|| {
+        format!(
+            "enc {} · dec {}",
+            pct(snapshot.encoder_util),
+            pct(snapshot.decoder_util)
+        )
+    })
+}
+
+/// `HEVC 1920×1080 · 60 fps · 3.2 ms`
+pub fn encoder_session(session: &EncoderSession) -> String {
+    format!(
+        "{} {}×{} · {} fps · {:.1} ms",
+        session.codec.label(),
+        session.width,
+        session.height,
+        session.fps,
+        session.latency_us as f64 / 1000.0
+    )
+}
+
+/// `412 Wh`, `1.23 kWh`
+pub fn fmt_energy(joules: f64) -> String {
+    let kwh = joules / J_PER_KWH;
+    if kwh >= 1.0 {
+        format!("{:.2} kWh", kwh)
+    } else {
+        format!("{:.0} Wh", kwh * 1000.0)
+    }
+}
+
+/// `165 g`, `1.2 kg`, `3.4 t`
+pub fn fmt_co2(grams: f64) -> String {
+    if grams >= 1e6 {
+        format!("{:.1} t", grams / 1e6)
+    } else if grams >= 1000.0 {
+        format!("{:.1} kg", grams / 1000.0)
+    } else {
+        format!("{:.0} g", grams)
+    }
+}
+
+/// `1.23 kWh · $0.18 · 492 g CO₂`
+pub fn energy_summary(joules: f64, rates: &EnergyRates) -> String {
+    format!(
+        "{} · {}{:.2} · {} CO₂",
+        fmt_energy(joules),
+        rates.currency,
+        rates.cost(joules),
+        fmt_co2(rates.co2_g(joules))
+    )
+}
+
+/// `3.4/24G`
+pub fn fmt_vram(snapshot: &GpuSnapshot) -> String {
+    format!(
+        "{:.1}/{:.0}G",
+        snapshot.vram_used_mb as f64 / 1024.0,
+        snapshot.vram_total_mb as f64 / 1024.0
+    )
+}
+
+/// `XID 79: GPU has fallen off the bus`, `ECC uncorrected`
+pub fn event_summary(event: &GpuEvent) -> String {
+    match (event.kind, event.xid) {
+        (EventKind::Xid, Some(code)) => match events::xid_description(code) {
+            Some(what) => format!("XID {}: {}", code, what),
+            None => format!("XID {}", code),
+        },
+        (kind, _) => kind.label().to_string(),
+    }
+}
+
+/// `ECC 0/2 volatile · 3/2 aggregate` (corrected/uncorrected)
+pub fn ecc_summary(snapshot: &GpuSnapshot) -> Option<String> {
+    let ecc = snapshot.ecc?;
+    Some(format!(
+        "ECC {}/{} volatile · {}/{} aggregate",
+        ecc.volatile_corrected,
+        ecc.volatile_uncorrected,
+        ecc.aggregate_corrected,
+        ecc.aggregate_uncorrected
+    ))
+}
+
+/// `SW power cap · SW thermal`, or `None` when nothing costs performance
+pub fn throttle_summary(snapshot: &GpuSnapshot) -> Option<String> {
+    let reasons: Vec<&str> = snapshot.throttled().map(|r| r.label()).collect();
+    (!reasons.is_empty()).then(|| reasons.join(" · "))
+}
+
+/// `1g.10gb GI 3 CI 0`
+pub fn mig_label(mig: &MigInstance) -> String {
+    let profile = if mig.profile.is_empty() { "MIG" } else { &mig.profile };
+    format!(
+        "{} GI {} CI {}",
+        profile, mig.gpu_instance_id, mig.compute_instance_id
+    )
+}
+
+/// `3.2/9.5G`
+pub fn fmt_mig_vram(mig: &MigInstance) -> String {
+    format!(
+        "{:.1}/{:.1}G",
+        mig.vram_used_mb as f64 / 1024.0,
+        mig.vram_total_mb as f64 / 1024.0
+    )
+}
+
+/// First `max` characters of `s` (never splits a UTF-8 sequence)
+pub fn truncate(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+/// What to call a process in a list: its command line without the
+/// program's directory (`python train.py --cfg big.yaml`), else its name
+pub fn process_label(proc: &ProcessInfo) -> String {
+    if proc.cmdline.is_empty() {
+        return proc.name.clone();
+    }
+    let (program, args) = match proc.cmdline.split_once(' ') {
+        Some((program, args)) => (program, Some(args)),
+        None => (proc.cmdline.as_str(), None),
+    };
+    let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    match args {
+        Some(args) => format!("{} {}", program, args),
+        None => program.to_string(),
+    }
+}
+
+/// `GFX 2520 · MEM 10501 · SM 2520 · FAN 64%`
+pub fn clock_summary(snapshot: &GpuSnapshot) -> String {
+    let mut parts = vec![
+        format!("GFX {}", snapshot.clock_graphics_mhz),
+        format!("MEM {}", snapshot.clock_memory_mhz),
+        format!("SM {}", snapshot.clock_sm_mhz),
+    ];
+    if let Some(fan) = snapshot.fan_speed {
+        parts.push(format!("FAN {}%", fan));
+    }
+    parts.join(" · ")
+}
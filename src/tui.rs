@@ -0,0 +1,406 @@
+//! `nvdash tui`: the widget's compact view drawn in a terminal.
+//!
+//! Same header, sparklines, temperature bar, process list and clock footer
+//! as the egui window, one GPU at a time. ←/→ (or Tab, h/l, 1-9) switch
+//! GPUs, w cycles the sparkline time window; q or Esc quits.
+
+use crate::gpu::{GpuMonitor, GpuSnapshot};
+use crate::history::{self, GpuHistory};
+use crate::text::{self, TEMP_HIGH, TEMP_LOW};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::{Duration, Instant};
+
+// Same palette as the egui widget
+const NVIDIA_GREEN: Color = Color::Rgb(118, 185, 0);
+const ACCENT_CYAN: Color = Color::Rgb(80, 180, 190);
+const ACCENT_RED: Color = Color::Rgb(255, 70, 70);
+const ACCENT_AMBER: Color = Color::Rgb(230, 170, 60);
+const TEXT_PRIMARY: Color = Color::Rgb(220, 220, 230);
+const TEXT_SECONDARY: Color = Color::Rgb(140, 140, 155);
+const TEXT_DIM: Color = Color::Rgb(90, 90, 105);
+const BAR_TRACK: Color = Color::Rgb(40, 40, 48);
+
+/// Label column (" GPU  ") plus room for the value after a sparkline
+const SPARK_CHROME: u16 = 6 + 12;
+/// Rows that are not the process list: header, 3 metrics, footer, 3 rules.
+/// Devices that report PCIe add one more, as do busy video engines.
+const FIXED_ROWS: u16 = 8;
+
+struct App<'a> {
+    monitor: &'a mut GpuMonitor,
+    snapshots: Vec<GpuSnapshot>,
+    histories: Vec<GpuHistory>,
+    selected: usize,
+    /// Index into `history::WINDOWS`
+    window: usize,
+    interval: Duration,
+    last_poll: Instant,
+    error_msg: Option<String>,
+}
+
+pub fn run(monitor: &mut GpuMonitor, interval: Duration) -> io::Result<()> {
+    let mut app = App {
+        monitor,
+        snapshots: Vec::new(),
+        histories: Vec::new(),
+        selected: 0,
+        window: 0,
+        interval,
+        last_poll: Instant::now(),
+        error_msg: None,
+    };
+    app.poll();
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+fn style(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+fn rule(width: u16) -> Line<'static> {
+    Line::styled("─".repeat(width as usize), style(BAR_TRACK))
+}
+
+impl App<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+
+            let timeout = self.interval.saturating_sub(self.last_poll.elapsed());
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    let count = self.snapshots.len().max(1);
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(())
+                        }
+                        KeyCode::Right | KeyCode::Tab | KeyCode::Char('l') => {
+                            self.selected = (self.selected + 1) % count;
+                        }
+                        KeyCode::Left | KeyCode::BackTab | KeyCode::Char('h') => {
+                            self.selected = (self.selected + count - 1) % count;
+                        }
+                        KeyCode::Char('w') => {
+                            self.window = (self.window + 1) % history::WINDOWS.len();
+                        }
+                        KeyCode::Char(c @ '1'..='9') => {
+                            let idx = c as usize - '1' as usize;
+                            if idx < self.snapshots.len() {
+                                self.selected = idx;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            if self.last_poll.elapsed() >= self.interval {
+                self.poll();
+            }
+        }
+    }
+
+    fn poll(&mut self) {
+        self.last_poll = Instant::now();
+        self.error_msg = None;
+        let count = self.monitor.device_count() as usize;
+        self.histories.resize_with(count, GpuHistory::new);
+        self.snapshots.truncate(count);
+
+        for i in 0..count {
+            match self.monitor.snapshot(i as u32) {
+                Ok(snap) => {
+                    self.histories[i].push(&snap);
+                    if i < self.snapshots.len() {
+                        self.snapshots[i] = snap;
+                    } else {
+                        self.snapshots.push(snap);
+                    }
+                }
+                Err(e) => {
+                    self.error_msg = Some(format!("GPU {} poll error: {}", i, e));
+                    if i >= self.snapshots.len() {
+                        self.snapshots.push(GpuSnapshot::placeholder(i as u32));
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&self, f: &mut Frame) {
+        let [tabs, body, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(FIXED_ROWS),
+            Constraint::Length(1),
+        ])
+        .areas(f.area());
+
+        self.draw_tabs(f, tabs);
+        match self.snapshots.get(self.selected) {
+            Some(snap) => draw_gpu(
+                f,
+                body,
+                snap,
+                &self.histories[self.selected],
+                history::WINDOWS[self.window],
+            ),
+            None => f.render_widget(
+                Paragraph::new(" No GPUs found").style(style(TEXT_DIM)),
+                body,
+            ),
+        }
+
+        let help_line = match &self.error_msg {
+            Some(err) => Line::styled(format!(" {}", err), style(ACCENT_RED)),
+            None => Line::styled(
+                format!(
+                    " ←/→ switch GPU · 1-9 jump · w window ({}) · q quit",
+                    text::fmt_window(history::WINDOWS[self.window])
+                ),
+                style(TEXT_DIM),
+            ),
+        };
+        f.render_widget(Paragraph::new(help_line), help);
+    }
+
+    fn draw_tabs(&self, f: &mut Frame, area: Rect) {
+        let mut spans = Vec::new();
+        for (i, snap) in self.snapshots.iter().enumerate() {
+            let label = format!(" {} {} ", i + 1, text::truncate(&snap.name, 24));
+            let s = if i == self.selected {
+                style(NVIDIA_GREEN).add_modifier(Modifier::REVERSED | Modifier::BOLD)
+            } else {
+                style(TEXT_SECONDARY)
+            };
+            spans.push(Span::styled(label, s));
+            spans.push(Span::raw(" "));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+}
+
+fn heat(value: f64, low: f64, high: f64) -> Color {
+    let [r, g, b] = text::heat_rgb(value, low, high);
+    Color::Rgb(r, g, b)
+}
+
+fn draw_gpu(
+    f: &mut Frame,
+    area: Rect,
+    snapshot: &GpuSnapshot,
+    history: &GpuHistory,
+    window_ms: i64,
+) {
+    let width = area.width;
+    let spark_width = width.saturating_sub(SPARK_CHROME).max(8) as usize;
+    let end_ms = history.latest_ms();
+    let mut lines = Vec::new();
+
+    // Header: name left, badges right
+    let mut badges = Vec::new();
+    if let Some(throttled) = text::throttle_summary(snapshot) {
+        badges.push(Span::styled(format!("▼ {}", throttled), style(ACCENT_AMBER)));
+        badges.push(Span::raw("  "));
+    }
+    badges.extend([
+        Span::styled(
+            format!("{}°C", snapshot.temperature),
+            style(heat(snapshot.temperature as f64, TEMP_LOW, TEMP_HIGH)),
+        ),
+        Span::raw("  "),
+        Span::styled(
+            format!("{:.0}W", snapshot.power_draw_w),
+            style(heat(text::power_pct(snapshot), 0.0, 100.0)),
+        ),
+    ]);
+    let [header_area, rest] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+    f.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("⬢ ", style(NVIDIA_GREEN)),
+            Span::styled(snapshot.name.clone(), style(TEXT_PRIMARY)),
+        ])),
+        header_area,
+    );
+    f.render_widget(
+        Paragraph::new(Line::from(badges)).alignment(Alignment::Right),
+        header_area,
+    );
+    lines.push(rule(width));
+
+    // Sparklines
+    lines.push(Line::from(vec![
+        Span::styled(" GPU  ", style(TEXT_DIM)),
+        Span::styled(
+            text::sparkline(
+                &history.gpu_util.resample(window_ms, end_ms, spark_width),
+                0.0,
+                100.0,
+            ),
+            style(NVIDIA_GREEN),
+        ),
+        Span::styled(
+            format!("  {}", text::fmt_pct(snapshot.gpu_util())),
+            style(TEXT_SECONDARY),
+        ),
+    ]));
+    lines.push(Line::from(vec![
+        Span::styled(" VRAM ", style(TEXT_DIM)),
+        Span::styled(
+            text::sparkline(
+                &history.vram_used.resample(window_ms, end_ms, spark_width),
+                0.0,
+                snapshot.vram_total_mb as f64,
+            ),
+            style(ACCENT_CYAN),
+        ),
+        Span::styled(
+            format!("  {}", text::fmt_vram(snapshot)),
+            style(TEXT_SECONDARY),
+        ),
+    ]));
+
+    // PCIe TX/RX, scaled to the link's maximum
+    if let Some(pcie) = snapshot.pcie {
+        let half = (spark_width / 2).saturating_sub(1).max(4);
+        let max = pcie.max_kb_s();
+        let mut spans = vec![
+            Span::styled(" PCIe ", style(TEXT_DIM)),
+            Span::styled(
+                text::sparkline(&history.pcie_tx.resample(window_ms, end_ms, half), 0.0, max),
+                style(NVIDIA_GREEN),
+            ),
+            Span::raw("  "),
+            Span::styled(
+                text::sparkline(&history.pcie_rx.resample(window_ms, end_ms, half), 0.0, max),
+                style(ACCENT_CYAN),
+            ),
+            Span::styled(
+                format!(
+                    "  ↑{} ↓{}",
+                    text::fmt_kb_s(pcie.tx_kb_s as f64),
+                    text::fmt_kb_s(pcie.rx_kb_s as f64)
+                ),
+                style(TEXT_SECONDARY),
+            ),
+        ];
+        if pcie.degraded(snapshot.gpu_util()) {
+            spans.push(Span::styled(
+                format!("  {}", text::fmt_pcie_link(&pcie)),
+                style(ACCENT_AMBER),
+            ));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    // Encoder and decoder, once either has been busy
+    let video = history.video_busy(window_ms, end_ms);
+    if video {
+        let half = (spark_width / 2).saturating_sub(1).max(4);
+        let pct = |util: Option<u32>| util.map_or("-".into(), |u| format!("{}%", u));
+        lines.push(Line::from(vec![
+            Span::styled(" VID  ", style(TEXT_DIM)),
+            Span::styled(
+                text::sparkline(&history.encoder.resample(window_ms, end_ms, half), 0.0, 100.0),
+                style(NVIDIA_GREEN),
+            ),
+            Span::raw("  "),
+            Span::styled(
+                text::sparkline(&history.decoder.resample(window_ms, end_ms, half), 0.0, 100.0),
+                style(ACCENT_CYAN),
+            ),
+            Span::styled(
+                format!(
+                    "  E{} D{}",
+                    pct(snapshot.encoder_util),
+                    pct(snapshot.decoder_util)
+                ),
+                style(TEXT_SECONDARY),
+            ),
+        ]));
+    }
+
+    // Temperature bar
+    let (filled, empty) = text::temp_bar_cells(snapshot.temperature, spark_width);
+    lines.push(Line::from(vec![
+        Span::styled(" TEMP ", style(TEXT_DIM)),
+        Span::styled(
+            "█".repeat(filled),
+            style(heat(snapshot.temperature as f64, TEMP_LOW, TEMP_HIGH)),
+        ),
+        Span::styled("░".repeat(empty), style(BAR_TRACK)),
+        Span::styled(
+            format!("  {}°C", snapshot.temperature),
+            style(TEXT_SECONDARY),
+        ),
+    ]));
+    lines.push(rule(width));
+
+    // Processes: as many as fit, those in MIG slices nested under the slice
+    let mut rows: Vec<(String, String, Color)> = snapshot
+        .processes
+        .iter()
+        .map(|p| (text::process_label(p), text::fmt_mb(p.vram_mb), TEXT_PRIMARY))
+        .collect();
+    for mig in &snapshot.mig_instances {
+        rows.push((
+            format!("▸ {}", text::mig_label(mig)),
+            text::fmt_mig_vram(mig),
+            TEXT_SECONDARY,
+        ));
+        rows.extend(mig.processes.iter().map(|p| {
+            (
+                format!("  {}", text::process_label(p)),
+                text::fmt_mb(p.vram_mb),
+                TEXT_PRIMARY,
+            )
+        }));
+    }
+    let fixed = FIXED_ROWS + snapshot.pcie.is_some() as u16 + video as u16;
+    let room = area.height.saturating_sub(fixed).max(1) as usize;
+    if rows.is_empty() {
+        lines.push(Line::styled(" No GPU processes", style(TEXT_DIM)));
+    } else {
+        let shown = if rows.len() > room {
+            room.saturating_sub(1).max(1)
+        } else {
+            room
+        };
+        for (label, vram, color) in rows.iter().take(shown) {
+            let name_width = (width as usize).saturating_sub(vram.chars().count() + 2);
+            let name = text::truncate(label, name_width.saturating_sub(1));
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {:<w$}", name, w = name_width), style(*color)),
+                Span::styled(vram.clone(), style(ACCENT_CYAN)),
+            ]));
+        }
+        let remaining = rows.len().saturating_sub(shown);
+        if remaining > 0 {
+            lines.push(Line::styled(
+                format!(" +{} more", remaining),
+                style(TEXT_DIM),
+            ));
+        }
+    }
+    lines.push(rule(width));
+    lines.push(Line::styled(
+        format!(" {}", text::clock_summary(snapshot)),
+        style(TEXT_DIM),
+    ));
+
+    f.render_widget(Paragraph::new(lines), rest);
+}
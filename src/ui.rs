@@ -1,6 +1,10 @@
-use crate::gpu::{GpuHistory, GpuSnapshot};
+use crate::energy::EnergyRates;
+use crate::forecast;
+use crate::gpu::{GpuSnapshot, ProcessInfo};
+use crate::history::{GpuHistory, ProcessKey, ProcessRecord, Series};
+use crate::process::Action;
+use crate::text::{self, TEMP_HIGH, TEMP_LOW};
 use egui::{self, Color32, FontId, RichText, Stroke, Vec2};
-use std::collections::VecDeque;
 
 // ── Color Palette ──────────────────────────────────────────────────────────
 pub const BG_DARK: Color32 = Color32::from_rgb(18, 18, 22);
@@ -15,10 +19,14 @@ pub const TEXT_DIM: Color32 = Color32::from_rgb(90, 90, 105);
 pub const NVIDIA_GREEN: Color32 = Color32::from_rgb(118, 185, 0);
 pub const ACCENT_CYAN: Color32 = Color32::from_rgb(80, 180, 190);
 pub const ACCENT_RED: Color32 = Color32::from_rgb(255, 70, 70);
+pub const ACCENT_AMBER: Color32 = Color32::from_rgb(230, 170, 60);
 
-const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 const SPARK_WIDTH: usize = 36;
+/// Per-process VRAM sparkline in the process list
+const PROC_SPARK_WIDTH: usize = 10;
 const BAR_WIDTH: usize = 28;
+/// Processes listed in the energy panel
+const ENERGY_PROCESSES: usize = 10;
 const FONT_SIZE: f32 = 11.0;
 
 // ── Theming ──────────────────────────────────────────────────────────────
@@ -43,22 +51,8 @@ pub fn setup_style(ctx: &egui::Context) {
 
 /// Soft heat color: sage green → muted gold → dusty rose
 pub fn heat_color(value: f64, low: f64, high: f64) -> Color32 {
-    let t = ((value - low) / (high - low)).clamp(0.0, 1.0);
-    if t < 0.5 {
-        let s = (t * 2.0) as f32;
-        Color32::from_rgb(
-            (105.0 + (195.0 - 105.0) * s) as u8,
-            (160.0 + (165.0 - 160.0) * s) as u8,
-            (95.0 + (90.0 - 95.0) * s) as u8,
-        )
-    } else {
-        let s = ((t - 0.5) * 2.0) as f32;
-        Color32::from_rgb(
-            (195.0 + (190.0 - 195.0) * s) as u8,
-            (165.0 - 65.0 * s) as u8,
-            (90.0 + (10.0 - 90.0) * s) as u8,
-        )
-    }
+    let [r, g, b] = text::heat_rgb(value, low, high);
+    Color32::from_rgb(r, g, b)
 }
 
 // ── Helpers ──────────────────────────────────────────────────────────────
@@ -71,39 +65,30 @@ fn tf(color: Color32) -> egui::TextFormat {
     }
 }
 
-fn build_sparkline_str(data: &VecDeque<f64>, y_min: f64, y_max: f64) -> String {
-    let range = (y_max - y_min).max(1.0);
-    let n = data.len();
-    let mut s = String::with_capacity(SPARK_WIDTH);
-    for i in 0..SPARK_WIDTH {
-        let di = i as i32 - (SPARK_WIDTH as i32 - n as i32);
-        if di >= 0 && (di as usize) < n {
-            let v = data[di as usize];
-            let t = ((v - y_min) / range).clamp(0.0, 1.0);
-            let idx = (t * 7.0).round() as usize;
-            s.push(SPARK_BLOCKS[idx.min(7)]);
-        } else {
-            s.push(' ');
-        }
-    }
-    s
-}
-
 // ── Drawing Functions ────────────────────────────────────────────────────
 
-/// Header: ⬢ GPU name (left), temp + power badges (right)
-pub fn draw_header(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
-    let temp_color = heat_color(snapshot.temperature as f64, 30.0, 90.0);
-    let pwr_pct = if snapshot.power_limit_w > 0.0 {
-        (snapshot.power_draw_w / snapshot.power_limit_w) * 100.0
-    } else {
-        0.0
-    };
-    let pwr_color = heat_color(pwr_pct, 0.0, 100.0);
+/// Header: ⬢ GPU name (left), temp + power badges (right), plus an amber
+/// badge while the clocks are throttled, a red one when any of `alerts`
+/// (alert descriptions) is firing and one for critical events within the
+/// window. Returns true when the events badge is clicked.
+pub fn draw_header(
+    ui: &mut egui::Ui,
+    snapshot: &GpuSnapshot,
+    history: &GpuHistory,
+    window_ms: i64,
+    alerts: &[String],
+) -> bool {
+    let events = history.critical_events(window_ms, history.latest_ms());
+    let mut events_clicked = false;
+    let temp_color = heat_color(snapshot.temperature as f64, TEMP_LOW, TEMP_HIGH);
+    let pwr_color = heat_color(text::power_pct(snapshot), 0.0, 100.0);
 
     ui.horizontal(|ui| {
         let mut name_job = egui::text::LayoutJob::default();
         name_job.append("⬢ ", 0.0, tf(NVIDIA_GREEN));
+        if !snapshot.host.is_empty() {
+            name_job.append(&format!("{} · ", snapshot.host), 0.0, tf(TEXT_DIM));
+        }
         name_job.append(&snapshot.name, 0.0, tf(TEXT_PRIMARY));
         let resp = ui.label(name_job);
         resp.on_hover_text(format!(
@@ -113,11 +98,7 @@ pub fn draw_header(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
 
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
             let mut badge_job = egui::text::LayoutJob::default();
-            badge_job.append(
-                &format!("{}°C", snapshot.temperature),
-                0.0,
-                tf(temp_color),
-            );
+            badge_job.append(&format!("{}°C", snapshot.temperature), 0.0, tf(temp_color));
             badge_job.append("  ", 0.0, tf(TEXT_DIM));
             badge_job.append(
                 &format!("{:.0}W", snapshot.power_draw_w),
@@ -125,52 +106,240 @@ pub fn draw_header(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
                 tf(pwr_color),
             );
             ui.label(badge_job);
+
+            let throttled: Vec<_> = snapshot.throttled().collect();
+            if !throttled.is_empty() {
+                let label = match throttled.as_slice() {
+                    [reason] => format!("▼ {}", reason.label()),
+                    _ => "▼ throttled".to_string(),
+                };
+                let mut hover = vec![format!(
+                    "Throttled: {}",
+                    text::throttle_summary(snapshot).unwrap_or_default()
+                )];
+                hover.extend(throttle_shares(history, window_ms));
+                ui.label(
+                    egui::RichText::new(label)
+                        .size(10.0)
+                        .strong()
+                        .color(BG_DARK)
+                        .background_color(ACCENT_AMBER),
+                )
+                .on_hover_text(hover.join("\n"));
+            }
+
+            if !alerts.is_empty() {
+                let label = match alerts.len() {
+                    1 => "⚠ alert".to_string(),
+                    n => format!("⚠ {} alerts", n),
+                };
+                ui.label(
+                    egui::RichText::new(label)
+                        .size(10.0)
+                        .strong()
+                        .color(BG_DARK)
+                        .background_color(ACCENT_RED),
+                )
+                .on_hover_text(alerts.join("\n"));
+            }
+
+            if let Some(latest) = events.last() {
+                let label = match (events.len(), latest.xid) {
+                    (1, Some(code)) => format!("⚡ XID {}", code),
+                    (1, None) => format!("⚡ {}", latest.kind.label()),
+                    (n, _) => format!("⚡ {} errors", n),
+                };
+                let hover: Vec<String> = events
+                    .iter()
+                    .rev()
+                    .map(|e| format!("{}  {}", text::fmt_clock(e.t_ms), text::event_summary(e)))
+                    .collect();
+                events_clicked = ui
+                    .add(
+                        egui::Label::new(
+                            egui::RichText::new(label)
+                                .size(10.0)
+                                .strong()
+                                .color(BG_DARK)
+                                .background_color(ACCENT_RED),
+                        )
+                        .sense(egui::Sense::click()),
+                    )
+                    .on_hover_text(hover.join("\n"))
+                    .clicked();
+            }
         });
     });
+    events_clicked
 }
 
-/// GPU and VRAM text sparklines using block characters
+/// `last 10m: SW power cap 34% · Idle 5%`, or `None` if the clocks ran
+/// free all window
+fn throttle_shares(history: &GpuHistory, window_ms: i64) -> Option<String> {
+    let shares = history.throttle_shares(window_ms, history.latest_ms());
+    if shares.is_empty() {
+        return None;
+    }
+    let parts: Vec<String> = shares
+        .iter()
+        .map(|(reason, share)| format!("{} {:.0}%", reason.label(), share * 100.0))
+        .collect();
+    Some(format!(
+        "last {}: {}",
+        text::fmt_window(window_ms),
+        parts.join(" · ")
+    ))
+}
+
+/// Hover text for a sparkline: min / avg / max over the window
+fn window_stats(
+    series: &Series,
+    window_ms: i64,
+    end_ms: i64,
+    fmt: impl Fn(f64) -> String,
+) -> String {
+    match series.stats(window_ms, end_ms) {
+        Some((min, avg, max)) => format!(
+            "last {}: min {} · avg {} · max {}",
+            text::fmt_window(window_ms),
+            fmt(min),
+            fmt(avg),
+            fmt(max)
+        ),
+        None => format!("last {}: no data", text::fmt_window(window_ms)),
+    }
+}
+
+/// GPU and VRAM text sparklines over the selected time window
 pub fn draw_text_sparklines(
     ui: &mut egui::Ui,
     snapshot: &GpuSnapshot,
     history: &GpuHistory,
+    window_ms: i64,
 ) {
+    let end_ms = history.latest_ms();
+
     // GPU sparkline
     {
-        let spark = build_sparkline_str(&history.gpu_util, 0.0, 100.0);
+        let cells = history.gpu_util.resample(window_ms, end_ms, SPARK_WIDTH);
+        let spark = text::sparkline(&cells, 0.0, 100.0);
         let mut job = egui::text::LayoutJob::default();
         job.append(" GPU  ", 0.0, tf(TEXT_DIM));
         job.append(&spark, 0.0, tf(NVIDIA_GREEN));
-        job.append(&format!("  {}%", snapshot.gpu_util), 0.0, tf(TEXT_SECONDARY));
-        ui.label(job);
+        job.append(
+            &format!("  {}", text::fmt_pct(snapshot.gpu_util())),
+            0.0,
+            tf(TEXT_SECONDARY),
+        );
+        ui.label(job)
+            .on_hover_text(window_stats(&history.gpu_util, window_ms, end_ms, |v| {
+                format!("{:.0}%", v)
+            }));
     }
 
     // VRAM sparkline
     {
-        let spark =
-            build_sparkline_str(&history.vram_used, 0.0, snapshot.vram_total_mb as f64);
+        let cells = history.vram_used.resample(window_ms, end_ms, SPARK_WIDTH);
+        let spark = text::sparkline(&cells, 0.0, snapshot.vram_total_mb as f64);
         let mut job = egui::text::LayoutJob::default();
         job.append(" VRAM ", 0.0, tf(TEXT_DIM));
         job.append(&spark, 0.0, tf(ACCENT_CYAN));
+        job.append(
+            &format!("  {}", text::fmt_vram(snapshot)),
+            0.0,
+            tf(TEXT_SECONDARY),
+        );
+        ui.label(job)
+            .on_hover_text(window_stats(&history.vram_used, window_ms, end_ms, |v| {
+                text::fmt_mb(v as u64)
+            }));
+    }
+
+    // PCIe TX and RX sparklines side by side, scaled to the link's maximum
+    if let Some(pcie) = snapshot.pcie {
+        let width = SPARK_WIDTH / 2 - 1;
+        let max = pcie.max_kb_s();
+        let tx = text::sparkline(&history.pcie_tx.resample(window_ms, end_ms, width), 0.0, max);
+        let rx = text::sparkline(&history.pcie_rx.resample(window_ms, end_ms, width), 0.0, max);
+        let degraded = pcie.degraded(snapshot.gpu_util());
+        let mut job = egui::text::LayoutJob::default();
+        job.append(" PCIe ", 0.0, tf(TEXT_DIM));
+        job.append(&tx, 0.0, tf(NVIDIA_GREEN));
+        job.append("  ", 0.0, tf(TEXT_DIM));
+        job.append(&rx, 0.0, tf(ACCENT_CYAN));
         job.append(
             &format!(
-                "  {:.1}/{:.0}G",
-                snapshot.vram_used_mb as f64 / 1024.0,
-                snapshot.vram_total_mb as f64 / 1024.0
+                "  ↑{} ↓{}",
+                text::fmt_kb_s(pcie.tx_kb_s as f64),
+                text::fmt_kb_s(pcie.rx_kb_s as f64)
             ),
             0.0,
             tf(TEXT_SECONDARY),
         );
-        ui.label(job);
+        if degraded {
+            job.append(" ⚠", 0.0, tf(ACCENT_AMBER));
+        }
+        let fmt = |v: f64| format!("{}/s", text::fmt_kb_s(v));
+        let mut hover = vec![text::fmt_pcie_link(&pcie)];
+        if degraded {
+            hover[0].push_str(" — link trained below its maximum");
+        }
+        hover.push(format!(
+            "TX (to host) {}",
+            window_stats(&history.pcie_tx, window_ms, end_ms, fmt)
+        ));
+        hover.push(format!(
+            "RX (to GPU) {}",
+            window_stats(&history.pcie_rx, window_ms, end_ms, fmt)
+        ));
+        ui.label(job).on_hover_text(hover.join("\n"));
+    }
+
+    // Encoder and decoder side by side, once either has been busy
+    if history.video_busy(window_ms, end_ms) {
+        let width = SPARK_WIDTH / 2 - 1;
+        let enc = text::sparkline(&history.encoder.resample(window_ms, end_ms, width), 0.0, 100.0);
+        let dec = text::sparkline(&history.decoder.resample(window_ms, end_ms, width), 0.0, 100.0);
+        let pct = |util: Option<u32>| util.map_or("-".into(), |u| format!("{}%", u));
+        let mut job = egui::text::LayoutJob::default();
+        job.append(" VID  ", 0.0, tf(TEXT_DIM));
+        job.append(&enc, 0.0, tf(NVIDIA_GREEN));
+        job.append("  ", 0.0, tf(TEXT_DIM));
+        job.append(&dec, 0.0, tf(ACCENT_CYAN));
+        job.append(
+            &format!(
+                "  E{} D{}",
+                pct(snapshot.encoder_util),
+                pct(snapshot.decoder_util)
+            ),
+            0.0,
+            tf(TEXT_SECONDARY),
+        );
+        let fmt = |v: f64| format!("{:.0}%", v);
+        let sessions = snapshot
+            .processes
+            .iter()
+            .map(|p| p.encoder_sessions.len())
+            .sum::<usize>();
+        let hover = [
+            format!(
+                "NVENC {}",
+                window_stats(&history.encoder, window_ms, end_ms, fmt)
+            ),
+            format!(
+                "NVDEC {}",
+                window_stats(&history.decoder, window_ms, end_ms, fmt)
+            ),
+            format!("{} encoder sessions", sessions),
+        ];
+        ui.label(job).on_hover_text(hover.join("\n"));
     }
 }
 
 /// Single temp bar using block characters
 pub fn draw_temp_bar(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
-    let pct = ((snapshot.temperature as f64 - 30.0) / 60.0).clamp(0.0, 1.0);
-    let filled = (pct * BAR_WIDTH as f64).round() as usize;
-    let empty = BAR_WIDTH - filled;
-    let color = heat_color(snapshot.temperature as f64, 30.0, 90.0);
+    let (filled, empty) = text::temp_bar_cells(snapshot.temperature, BAR_WIDTH);
+    let color = heat_color(snapshot.temperature as f64, TEMP_LOW, TEMP_HIGH);
 
     let mut job = egui::text::LayoutJob::default();
     job.append(" TEMP ", 0.0, tf(TEXT_DIM));
@@ -188,69 +357,587 @@ pub fn draw_temp_bar(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
     ui.label(job);
 }
 
-/// Top 3 GPU processes by VRAM
-pub fn draw_process_list(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
+/// Amber line with the VRAM and temperature forecasts due within
+/// `horizon_ms`; draws nothing when neither is
+pub fn draw_forecast(
+    ui: &mut egui::Ui,
+    snapshot: &GpuSnapshot,
+    history: &GpuHistory,
+    horizon_ms: i64,
+) {
+    let due = |ms: Option<i64>| ms.filter(|&ms| ms <= horizon_ms);
+    let mut parts = Vec::new();
+    if let Some(ms) = due(forecast::vram_exhaustion(history, snapshot)) {
+        parts.push(format!("VRAM full in ~{}", text::fmt_duration(ms)));
+    }
+    if let Some(ms) = due(forecast::thermal_throttle(history, snapshot)) {
+        let limit = snapshot.temp_slowdown_c.unwrap_or_default();
+        parts.push(format!("{}°C in ~{}", limit, text::fmt_duration(ms)));
+    }
+    if parts.is_empty() {
+        return;
+    }
+    ui.label(
+        RichText::new(format!(" ↗ {}", parts.join(" · ")))
+            .color(ACCENT_AMBER)
+            .font(FontId::monospace(FONT_SIZE)),
+    )
+    .on_hover_text(format!(
+        "At the trend of the last {}; the temperature limit is where hardware slowdown starts",
+        text::fmt_window(forecast::LOOKBACK_MS)
+    ));
+}
+
+/// A signal picked from a process row's context menu, awaiting the user's
+/// confirmation. `start_time` pins it to the process that was clicked, so a
+/// PID reused in the meantime is left alone.
+pub struct ProcessRequest {
+    pub pid: u32,
+    pub start_time: u64,
+    pub name: String,
+    pub action: Action,
+}
+
+/// What the user did in the compact process list
+pub enum ProcessListAction {
+    Signal(ProcessRequest),
+    /// Clicked "+N more"
+    ShowAll,
+}
+
+/// Right-click menu for a process row: copy actions and, when `live` (the
+/// PIDs are real processes on this machine), signals. Returns the chosen
+/// signal for the caller to confirm.
+pub fn process_menu(resp: &egui::Response, proc: &ProcessInfo, live: bool) -> Option<ProcessRequest> {
+    let mut request = None;
+    resp.context_menu(|ui| {
+        if live {
+            for action in Action::ALL {
+                if ui.button(action.label()).clicked() {
+                    request = Some(ProcessRequest {
+                        pid: proc.pid,
+                        start_time: proc.start_time,
+                        name: proc.name.clone(),
+                        action,
+                    });
+                    ui.close_menu();
+                }
+            }
+            ui.separator();
+        }
+        if ui.button(format!("Copy PID {}", proc.pid)).clicked() {
+            ui.ctx().copy_text(proc.pid.to_string());
+            ui.close_menu();
+        }
+        if !proc.cmdline.is_empty() && ui.button("Copy command line").clicked() {
+            ui.ctx().copy_text(proc.cmdline.clone());
+            ui.close_menu();
+        }
+    });
+    request
+}
+
+/// Top 3 GPU processes by VRAM, each with a sparkline of its own VRAM over
+/// the window; right-click a name for `process_menu`. While an idle-reservation
+/// alert fires, processes holding at least `idle_mb` are flagged instead.
+pub fn draw_process_list(
+    ui: &mut egui::Ui,
+    snapshot: &GpuSnapshot,
+    history: &GpuHistory,
+    window_ms: i64,
+    live: bool,
+    idle_mb: Option<u64>,
+) -> Option<ProcessListAction> {
+    let mut action = None;
     if snapshot.processes.is_empty() {
+        // On a partitioned GPU the processes are listed under their slices
+        if !snapshot.mig_instances.is_empty() {
+            return None;
+        }
         ui.label(
             RichText::new(" No GPU processes")
                 .color(TEXT_DIM)
                 .font(FontId::monospace(FONT_SIZE)),
         );
-        return;
+        return None;
     }
 
     for proc in snapshot.processes.iter().take(3) {
-        let vram_text = if proc.vram_mb >= 1024 {
-            format!("{:.1}G", proc.vram_mb as f64 / 1024.0)
-        } else {
-            format!("{}M", proc.vram_mb)
-        };
+        if let Some(req) = process_row(ui, proc, history, window_ms, 0, live, idle_mb) {
+            action = Some(ProcessListAction::Signal(req));
+        }
+    }
+    if more_processes(ui, snapshot.processes.len(), 3, 0) {
+        action = Some(ProcessListAction::ShowAll);
+    }
+    action
+}
 
-        let name: &str = if proc.name.len() > 28 {
-            &proc.name[..28]
-        } else {
-            &proc.name
-        };
+/// How long `proc` has been holding memory on its idle GPU, if it is one of
+/// the processes an idle-reservation alert there is about: `idle_mb` is the
+/// alert's threshold (see `AlertEngine::idle_reservation_mb`)
+pub fn idle_reservation_ms(
+    proc: &ProcessInfo,
+    history: &GpuHistory,
+    idle_mb: Option<u64>,
+) -> Option<i64> {
+    idle_mb
+        .filter(|&mb| proc.vram_mb >= mb)
+        .and_then(|_| history.idle_for_ms(proc.start_time))
+}
+
+fn idle_owner(proc: &ProcessInfo) -> &str {
+    if proc.user.is_empty() {
+        "unknown owner"
+    } else {
+        &proc.user
+    }
+}
+
+/// `Holding 8.1G on an idle GPU for 2h 5m, owned by alice`
+pub fn idle_reservation_text(proc: &ProcessInfo, idle_ms: i64) -> String {
+    format!(
+        "Holding {} on an idle GPU for {}, owned by {}",
+        text::fmt_mb(proc.vram_mb),
+        text::fmt_duration(idle_ms),
+        idle_owner(proc)
+    )
+}
+
+/// One line of the process list: name, VRAM sparkline and VRAM, indented by
+/// `depth` levels. A process reserving the idle GPU shows its owner and how
+/// long it has been idle in place of the sparkline. Returns a signal picked
+/// from its context menu.
+fn process_row(
+    ui: &mut egui::Ui,
+    proc: &ProcessInfo,
+    history: &GpuHistory,
+    window_ms: i64,
+    depth: usize,
+    live: bool,
+    idle_mb: Option<u64>,
+) -> Option<ProcessRequest> {
+    let end_ms = history.latest_ms();
+    let vram_text = text::fmt_mb(proc.vram_mb);
+    let label = text::process_label(proc);
+    let name = text::truncate(&label, 28usize.saturating_sub(2 * depth));
+    let record = history.processes.get(&ProcessKey {
+        pid: proc.pid,
+        start_time: proc.start_time,
+    });
+    let idle_ms = idle_reservation_ms(proc, history, idle_mb);
 
+    let mut request = None;
+    ui.horizontal(|ui| {
+        let resp = ui.add(
+            egui::Label::new(
+                RichText::new(format!(" {}{}", "  ".repeat(depth), name))
+                    .color(TEXT_PRIMARY)
+                    .font(FontId::monospace(FONT_SIZE)),
+            )
+            .sense(egui::Sense::click()),
+        );
+        let resp = resp.on_hover_text(process_details(proc, record));
+        request = process_menu(&resp, proc, live);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(
+                RichText::new(&vram_text)
+                    .color(ACCENT_CYAN)
+                    .font(FontId::monospace(FONT_SIZE)),
+            );
+            if !proc.encoder_sessions.is_empty() {
+                let sessions: Vec<String> =
+                    proc.encoder_sessions.iter().map(text::encoder_session).collect();
+                ui.label(
+                    RichText::new(format!("⏺{}", proc.encoder_sessions.len()))
+                        .color(NVIDIA_GREEN)
+                        .font(FontId::monospace(FONT_SIZE)),
+                )
+                .on_hover_text(format!("NVENC sessions\n{}", sessions.join("\n")));
+            }
+            if let Some(ms) = idle_ms {
+                ui.label(
+                    RichText::new(format!("idle {} {}", text::fmt_duration(ms), idle_owner(proc)))
+                        .color(ACCENT_AMBER)
+                        .font(FontId::monospace(FONT_SIZE)),
+                )
+                .on_hover_text(idle_reservation_text(proc, ms));
+            } else if let Some(r) = record {
+                let cells = r.vram.resample(window_ms, end_ms, PROC_SPARK_WIDTH);
+                let spark = text::sparkline(&cells, 0.0, r.peak_vram_mb as f64);
+                ui.label(
+                    RichText::new(spark)
+                        .color(TEXT_DIM)
+                        .font(FontId::monospace(FONT_SIZE)),
+                )
+                .on_hover_text(window_stats(&r.vram, window_ms, end_ms, |v| {
+                    text::fmt_mb(v as u64)
+                }));
+            }
+        });
+    });
+    request
+}
+
+/// `+N more` under a list cut short at `shown`; true when clicked
+fn more_processes(ui: &mut egui::Ui, total: usize, shown: usize, depth: usize) -> bool {
+    let remaining = total.saturating_sub(shown);
+    if remaining == 0 {
+        return false;
+    }
+    ui.add(
+        egui::Label::new(
+            RichText::new(format!(" {}+{} more", "  ".repeat(depth), remaining))
+                .color(TEXT_DIM)
+                .font(FontId::monospace(9.0)),
+        )
+        .sense(egui::Sense::click()),
+    )
+    .on_hover_text("Open the process table")
+    .clicked()
+}
+
+/// MIG slices of a partitioned GPU, each with its memory and its top
+/// processes nested beneath. Draws nothing when MIG is off.
+pub fn draw_mig_instances(
+    ui: &mut egui::Ui,
+    snapshot: &GpuSnapshot,
+    history: &GpuHistory,
+    window_ms: i64,
+    live: bool,
+    idle_mb: Option<u64>,
+) -> Option<ProcessListAction> {
+    let mut action = None;
+    for mig in &snapshot.mig_instances {
+        ui.horizontal(|ui| {
+            let mut details = vec![mig.uuid.clone()];
+            details.extend(
+                mig.processes
+                    .iter()
+                    .map(|p| format!("{}  {}", text::fmt_mb(p.vram_mb), text::process_label(p))),
+            );
+            ui.label(
+                RichText::new(format!(" ▸ {}", text::mig_label(mig)))
+                    .color(TEXT_SECONDARY)
+                    .font(FontId::monospace(FONT_SIZE)),
+            )
+            .on_hover_text(details.join("\n"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.label(
+                    RichText::new(text::fmt_mig_vram(mig))
+                        .color(ACCENT_CYAN)
+                        .font(FontId::monospace(FONT_SIZE)),
+                );
+            });
+        });
+        for proc in mig.processes.iter().take(2) {
+            if let Some(req) = process_row(ui, proc, history, window_ms, 1, live, idle_mb) {
+                action = Some(ProcessListAction::Signal(req));
+            }
+        }
+        if more_processes(ui, mig.processes.len(), 2, 1) {
+            action = Some(ProcessListAction::ShowAll);
+        }
+    }
+    action
+}
+
+/// Full command line, owner, host CPU/RAM and working directory
+pub fn process_details(proc: &ProcessInfo, record: Option<&ProcessRecord>) -> String {
+    let mut lines = vec![if proc.cmdline.is_empty() {
+        proc.name.clone()
+    } else {
+        proc.cmdline.clone()
+    }];
+
+    let mut who = vec![format!("PID {}", proc.pid)];
+    if !proc.user.is_empty() {
+        who.push(format!("user {}", proc.user));
+    }
+    if proc.start_time > 0 {
+        who.push(format!(
+            "started {}",
+            text::fmt_clock(proc.start_time as i64 * 1000)
+        ));
+    }
+    lines.push(who.join(" · "));
+
+    let mut usage = vec![
+        format!("CPU {:.0}%", proc.cpu_pct),
+        format!("RSS {}", text::fmt_mb(proc.rss_mb)),
+    ];
+    if let Some(r) = record {
+        usage.push(format!("peak VRAM {}", text::fmt_mb(r.peak_vram_mb)));
+    }
+    lines.push(usage.join(" · "));
+
+    if let Some(u) = proc.utilization {
+        lines.push(format!(
+            "SM {}% · MEM {}% · ENC {}% · DEC {}%",
+            u.sm, u.mem, u.enc, u.dec
+        ));
+    }
+
+    for session in &proc.encoder_sessions {
+        lines.push(format!("NVENC {}", text::encoder_session(session)));
+    }
+
+    if !proc.cwd.is_empty() {
+        lines.push(format!("cwd {}", proc.cwd));
+    }
+    lines.join("\n")
+}
+
+/// `PID 4242 · 14:03:27 – running · peak 9.1G`
+fn process_summary(record: &ProcessRecord) -> String {
+    let end = if record.exited {
+        text::fmt_clock(record.last_seen_ms)
+    } else {
+        "running".into()
+    };
+    format!(
+        "PID {} · {} – {} · peak {}",
+        record.key.pid,
+        text::fmt_clock(record.first_seen_ms),
+        end,
+        text::fmt_mb(record.peak_vram_mb)
+    )
+}
+
+/// One row per process seen in the window: a bar spanning its lifetime on a
+/// shared time axis, with its peak VRAM
+pub fn draw_process_timeline(ui: &mut egui::Ui, history: &GpuHistory, window_ms: i64) {
+    let end_ms = history.latest_ms();
+    let start_ms = end_ms - window_ms;
+    let records = history.processes.timeline(window_ms, end_ms);
+    if records.is_empty() {
+        ui.label(
+            RichText::new(format!(
+                " No processes in the last {}",
+                text::fmt_window(window_ms)
+            ))
+            .color(TEXT_DIM)
+            .font(FontId::monospace(FONT_SIZE)),
+        );
+        return;
+    }
+
+    for r in records {
         ui.horizontal(|ui| {
+            let label = format!(" {:<16}", text::truncate(&r.name, 16));
             ui.label(
-                RichText::new(format!(" {}", name))
+                RichText::new(label)
                     .color(TEXT_PRIMARY)
                     .font(FontId::monospace(FONT_SIZE)),
             );
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 ui.label(
-                    RichText::new(&vram_text)
+                    RichText::new(format!("{:>6}", text::fmt_mb(r.peak_vram_mb)))
                         .color(ACCENT_CYAN)
                         .font(FontId::monospace(FONT_SIZE)),
                 );
+
+                let size = Vec2::new(ui.available_width(), 8.0);
+                let (rect, resp) = ui.allocate_exact_size(size, egui::Sense::hover());
+                let x = |t_ms: i64| {
+                    let f = (t_ms - start_ms) as f32 / window_ms.max(1) as f32;
+                    rect.left() + rect.width() * f.clamp(0.0, 1.0)
+                };
+                let painter = ui.painter();
+                painter.rect_filled(rect, 1.0, BAR_TRACK);
+                let bar = egui::Rect::from_x_y_ranges(
+                    x(r.first_seen_ms)..=x(r.last_seen_ms).max(x(r.first_seen_ms) + 2.0),
+                    rect.y_range(),
+                );
+                let color = if r.exited { TEXT_DIM } else { ACCENT_CYAN };
+                painter.rect_filled(bar, 1.0, color);
+                resp.on_hover_text(process_summary(r));
             });
         });
     }
+}
 
-    let remaining = snapshot.processes.len().saturating_sub(3);
-    if remaining > 0 {
+/// ECC counters and every remembered device event, newest first
+pub fn draw_event_log(ui: &mut egui::Ui, snapshot: &GpuSnapshot, history: &GpuHistory) {
+    if let Some(ecc) = text::ecc_summary(snapshot) {
+        ui.label(
+            RichText::new(format!(" {}", ecc))
+                .color(TEXT_SECONDARY)
+                .font(FontId::monospace(FONT_SIZE)),
+        )
+        .on_hover_text("corrected/uncorrected errors since the driver loaded, and over the board's lifetime");
+    }
+    if history.events.is_empty() {
         ui.label(
-            RichText::new(format!(" +{} more", remaining))
+            RichText::new(" No events")
                 .color(TEXT_DIM)
-                .font(FontId::monospace(9.0)),
+                .font(FontId::monospace(FONT_SIZE)),
         );
+        return;
+    }
+    for event in history.events.iter().rev() {
+        let color = if event.kind.critical() {
+            ACCENT_RED
+        } else {
+            TEXT_SECONDARY
+        };
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!(" {}", text::fmt_clock(event.t_ms)))
+                    .color(TEXT_DIM)
+                    .font(FontId::monospace(FONT_SIZE)),
+            );
+            ui.label(
+                RichText::new(text::event_summary(event))
+                    .color(color)
+                    .font(FontId::monospace(FONT_SIZE)),
+            );
+        });
     }
 }
 
-/// Footer: clocks + fan in a single line
-pub fn draw_footer(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
-    let mut parts = vec![
-        format!("GFX {}", snapshot.clock_graphics_mhz),
-        format!("MEM {}", snapshot.clock_memory_mhz),
-        format!("SM {}", snapshot.clock_sm_mhz),
-    ];
-    if let Some(fan) = snapshot.fan_speed {
-        parts.push(format!("FAN {}%", fan));
-    }
+/// NVLink panel for one GPU: totals, then one row per link with its state,
+/// remote end, throughput and error counters
+pub fn draw_nvlinks(ui: &mut egui::Ui, snapshot: &GpuSnapshot) {
+    let Some(summary) = text::nvlink_summary(snapshot) else {
+        ui.label(
+            RichText::new(" No NVLink")
+                .color(TEXT_DIM)
+                .font(FontId::monospace(FONT_SIZE)),
+        );
+        return;
+    };
     ui.label(
-        RichText::new(format!(" {}", parts.join(" · ")))
+        RichText::new(format!(" {}", summary))
+            .color(TEXT_SECONDARY)
+            .font(FontId::monospace(FONT_SIZE)),
+    );
+
+    let cell = |s: String, color| {
+        RichText::new(s)
+            .color(color)
+            .font(FontId::monospace(FONT_SIZE))
+    };
+    egui::Grid::new(("nvlinks", snapshot.index))
+        .striped(true)
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            for title in ["LINK", "STATE", "VER", "REMOTE", "TX", "RX", "CRC", "REPLAY", "RECOVERY"] {
+                ui.label(RichText::new(title).size(10.0).color(TEXT_SECONDARY));
+            }
+            ui.end_row();
+
+            let rate = |kb_s: Option<u64>| match kb_s {
+                Some(kb_s) => format!("{}/s", text::fmt_kb_s(kb_s as f64)),
+                None => "-".into(),
+            };
+            let count = |n: u64| {
+                let color = if n > 0 { ACCENT_RED } else { TEXT_SECONDARY };
+                cell(n.to_string(), color)
+            };
+            for link in &snapshot.nvlinks {
+                ui.label(cell(link.link.to_string(), TEXT_SECONDARY));
+                if !link.active {
+                    // Down while others are up usually means a bad bridge or cable
+                    ui.label(cell("down".into(), ACCENT_AMBER));
+                    ui.end_row();
+                    continue;
+                }
+                ui.label(cell("up".into(), NVIDIA_GREEN));
+                ui.label(cell(format!("v{}", link.version), TEXT_SECONDARY));
+                ui.label(cell(text::nvlink_remote(link), TEXT_PRIMARY))
+                    .on_hover_text(format!("PCI {}", link.remote_bus_id));
+                ui.label(cell(rate(link.tx_kb_s), ACCENT_CYAN));
+                ui.label(cell(rate(link.rx_kb_s), ACCENT_CYAN));
+                ui.label(count(link.crc_errors))
+                    .on_hover_text("flit and data CRC errors since the driver loaded");
+                ui.label(count(link.replay_errors));
+                ui.label(count(link.recovery_errors));
+                ui.end_row();
+            }
+        });
+}
+
+/// Energy panel for one GPU: used since nvdash started and since the last
+/// reset, then the processes that used the most over their lifetime
+pub fn draw_energy(ui: &mut egui::Ui, index: u32, history: &GpuHistory, rates: &EnergyRates) {
+    let meter = &history.energy;
+    let mut totals = Vec::new();
+    if let Some(start_ms) = meter.start_ms {
+        totals.push((format!("since {}", text::fmt_clock(start_ms)), meter.total_j));
+    }
+    if let Some(reset_ms) = meter.reset_ms {
+        totals.push((
+            format!("since reset {}", text::fmt_clock(reset_ms)),
+            meter.since_reset_j,
+        ));
+    }
+    for (label, joules) in totals {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!(" {:<20}", label))
+                    .color(TEXT_DIM)
+                    .font(FontId::monospace(FONT_SIZE)),
+            );
+            ui.label(
+                RichText::new(text::energy_summary(joules, rates))
+                    .color(TEXT_PRIMARY)
+                    .font(FontId::monospace(FONT_SIZE)),
+            );
+        });
+    }
+
+    let mut records: Vec<&ProcessRecord> = history
+        .processes
+        .records()
+        .filter(|r| r.energy_j > 0.0)
+        .collect();
+    if records.is_empty() {
+        return;
+    }
+    records.sort_by(|a, b| b.energy_j.total_cmp(&a.energy_j));
+    let cell = |s: String, color| {
+        RichText::new(s)
+            .color(color)
+            .font(FontId::monospace(FONT_SIZE))
+    };
+    egui::Grid::new(("energy", index))
+        .striped(true)
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            for title in ["PID", "NAME", "ENERGY", "COST", "CO₂"] {
+                ui.label(RichText::new(title).size(10.0).color(TEXT_SECONDARY));
+            }
+            ui.end_row();
+            for r in records.iter().take(ENERGY_PROCESSES) {
+                let color = if r.exited { TEXT_DIM } else { TEXT_PRIMARY };
+                ui.label(cell(r.key.pid.to_string(), TEXT_SECONDARY));
+                ui.label(cell(text::truncate(&r.name, 40).to_string(), color))
+                    .on_hover_text(process_summary(r));
+                ui.label(cell(text::fmt_energy(r.energy_j), ACCENT_CYAN));
+                ui.label(cell(
+                    format!("{}{:.2}", rates.currency, rates.cost(r.energy_j)),
+                    TEXT_SECONDARY,
+                ));
+                ui.label(cell(text::fmt_co2(rates.co2_g(r.energy_j)), TEXT_SECONDARY));
+                ui.end_row();
+            }
+        });
+}
+
+/// Footer: clocks + fan in a single line; hover for how long each throttle
+/// reason held the clocks down over the window
+pub fn draw_footer(ui: &mut egui::Ui, snapshot: &GpuSnapshot, history: &GpuHistory, window_ms: i64) {
+    let resp = ui.label(
+        RichText::new(format!(" {}", text::clock_summary(snapshot)))
             .color(TEXT_DIM)
             .font(FontId::monospace(FONT_SIZE)),
     );
+    let throttling = throttle_shares(history, window_ms).unwrap_or_else(|| {
+        format!(
+            "last {}: no clock throttling",
+            text::fmt_window(window_ms)
+        )
+    });
+    resp.on_hover_text(throttling);
 }